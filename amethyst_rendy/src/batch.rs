@@ -1,15 +1,25 @@
 use crate::util::TapCountIter;
 use derivative::Derivative;
+#[cfg(feature = "serde-1")]
+use serde::{Deserialize, Deserializer, Serialize};
 use smallvec::{smallvec, SmallVec};
 use std::{
     collections::hash_map::Entry,
+    convert::Infallible,
     iter::{Extend, FromIterator},
     ops::Range,
 };
+use streaming_iterator::StreamingIterator;
+
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
+/// Initial capacity of the buffer used by `GroupIterator::for_each_group`.
+const DEFAULT_GROUP_CAPACITY: usize = 64;
+
 pub trait GroupIterator<K, V>
 where
     Self: Iterator<Item = (K, V)> + Sized,
@@ -20,67 +30,376 @@ where
     fn for_each_group<F>(self, on_group: F)
     where
         F: FnMut(K, &mut Vec<V>);
+
+    /// Same as `for_each_group`, with an explicit initial capacity for the
+    /// group buffer instead of the default of 64 items.
+    fn for_each_group_with_capacity<F>(self, capacity: usize, on_group: F)
+    where
+        F: FnMut(K, &mut Vec<V>);
+
+    /// Fallible version of `for_each_group`. Stops iterating and returns
+    /// the first error produced by the closure.
+    fn try_for_each_group<F, E>(self, on_group: F) -> Result<(), E>
+    where
+        F: FnMut(K, &mut Vec<V>) -> Result<(), E>;
+
+    /// Same grouping as `for_each_group`, but groups are submitted last to first,
+    /// while the order of data within each group is preserved.
+    ///
+    /// Every group has to be buffered before the first one can be submitted, so
+    /// this holds the whole iterator contents in memory at once. Use it to reuse
+    /// a front-to-back sorted stream for back-to-front (transparent) drawing.
+    fn for_each_group_rev<F>(self, on_group: F)
+    where
+        F: FnMut(K, &mut Vec<V>);
+
+    /// Same grouping as `for_each_group`, but the closure also receives the identifier
+    /// of the group submitted after the current one, or `None` for the last group.
+    ///
+    /// Useful to decide whether state has to be flushed before the next group.
+    fn for_each_group_peek<F>(self, on_group: F)
+    where
+        F: FnMut(K, &mut Vec<V>, Option<&K>);
+
+    /// Collects every contiguous list of data with the same group identifier,
+    /// in order. Allocates a vector per group, prefer `for_each_group` in hot code.
+    fn group_by(self) -> Vec<(K, Vec<V>)> {
+        let mut groups = Vec::new();
+        self.for_each_group(|group_id, group_buffer| {
+            groups.push((group_id, std::mem::replace(group_buffer, Vec::new())));
+        });
+        groups
+    }
 }
 
 // This would be an iterator adaptor if `Item` type would allow a borrow on iterator itself.
-// FIXME: Implement once `StreamingIterator` is a thing.
+// When the data is already contiguous in memory, `GroupBy` provides that adaptor.
 impl<K, V, I> GroupIterator<K, V> for I
 where
     K: PartialEq,
     I: Iterator<Item = (K, V)>,
 {
-    fn for_each_group<F>(self, mut on_group: F)
+    fn for_each_group<F>(self, on_group: F)
+    where
+        F: FnMut(K, &mut Vec<V>),
+    {
+        self.for_each_group_with_capacity(DEFAULT_GROUP_CAPACITY, on_group)
+    }
+
+    fn for_each_group_with_capacity<F>(self, capacity: usize, mut on_group: F)
+    where
+        F: FnMut(K, &mut Vec<V>),
+    {
+        try_for_each_group_with_capacity(self, capacity, |group_id, group_buffer, _| {
+            on_group(group_id, group_buffer);
+            Ok::<(), Infallible>(())
+        })
+        .unwrap_or_else(|never| match never {})
+    }
+
+    fn try_for_each_group<F, E>(self, mut on_group: F) -> Result<(), E>
+    where
+        F: FnMut(K, &mut Vec<V>) -> Result<(), E>,
+    {
+        try_for_each_group_with_capacity(
+            self,
+            DEFAULT_GROUP_CAPACITY,
+            |group_id, group_buffer, _| on_group(group_id, group_buffer),
+        )
+    }
+
+    fn for_each_group_rev<F>(self, mut on_group: F)
     where
         F: FnMut(K, &mut Vec<V>),
     {
         #[cfg(feature = "profiler")]
-        profile_scope!("for_each_group");
+        profile_scope!("for_each_group_rev");
 
-        let mut block: Option<(K, Vec<V>)> = None;
+        let mut groups: Vec<(K, Vec<V>)> = Vec::new();
 
         for (next_group_id, value) in self {
-            match &mut block {
-                slot @ None => {
-                    let mut group_buffer = Vec::with_capacity(64);
-                    group_buffer.push(value);
-                    slot.replace((next_group_id, group_buffer));
-                }
+            match groups.last_mut() {
                 Some((group_id, group_buffer)) if group_id == &next_group_id => {
                     group_buffer.push(value);
                 }
-                Some((group_id, ref mut group_buffer)) => {
-                    let submitted_group_id = std::mem::replace(group_id, next_group_id);
-                    on_group(submitted_group_id, group_buffer);
-                    group_buffer.clear();
-                    group_buffer.push(value);
-                }
+                _ => groups.push((next_group_id, vec![value])),
             }
         }
 
-        if let Some((group_id, mut group_buffer)) = block.take() {
+        for (group_id, mut group_buffer) in groups.into_iter().rev() {
             on_group(group_id, &mut group_buffer);
         }
     }
+
+    fn for_each_group_peek<F>(self, mut on_group: F)
+    where
+        F: FnMut(K, &mut Vec<V>, Option<&K>),
+    {
+        try_for_each_group_with_capacity(
+            self,
+            DEFAULT_GROUP_CAPACITY,
+            |group_id, group_buffer, next_group_id| {
+                on_group(group_id, group_buffer, next_group_id);
+                Ok::<(), Infallible>(())
+            },
+        )
+        .unwrap_or_else(|never| match never {})
+    }
 }
 
-#[derive(Derivative, Debug)]
+/// The grouping loop shared by all `GroupIterator` methods but `for_each_group_rev`.
+/// `on_group` also gets the identifier of the group after the submitted one, if any.
+fn try_for_each_group_with_capacity<K, V, F, E>(
+    iter: impl Iterator<Item = (K, V)>,
+    capacity: usize,
+    mut on_group: F,
+) -> Result<(), E>
+where
+    K: PartialEq,
+    F: FnMut(K, &mut Vec<V>, Option<&K>) -> Result<(), E>,
+{
+    #[cfg(feature = "profiler")]
+    profile_scope!("for_each_group");
+
+    let mut block: Option<(K, Vec<V>)> = None;
+
+    for (next_group_id, value) in iter {
+        match &mut block {
+            slot @ None => {
+                // The buffer is reused for every following group, so only this
+                // allocation is affected by the capacity hint.
+                let mut group_buffer = Vec::with_capacity(capacity);
+                group_buffer.push(value);
+                slot.replace((next_group_id, group_buffer));
+            }
+            Some((group_id, group_buffer)) if group_id == &next_group_id => {
+                group_buffer.push(value);
+            }
+            Some((group_id, ref mut group_buffer)) => {
+                let submitted_group_id = std::mem::replace(group_id, next_group_id);
+                on_group(submitted_group_id, group_buffer, Some(&*group_id))?;
+                group_buffer.clear();
+                group_buffer.push(value);
+            }
+        }
+    }
+
+    if let Some((group_id, mut group_buffer)) = block.take() {
+        on_group(group_id, &mut group_buffer, None)?;
+    }
+
+    Ok(())
+}
+
+/// Streaming adaptor yielding every contiguous run of slice elements that share
+/// the same key, as `(key, run)` pairs borrowing directly from the slice.
+///
+/// Unlike `GroupIterator::for_each_group`, no data is copied into a group buffer.
+#[derive(Debug)]
+pub struct GroupBy<'a, K, V, F> {
+    data: &'a [V],
+    key_fn: F,
+    offset: usize,
+    current: Option<(K, &'a [V])>,
+}
+
+impl<'a, K, V, F> GroupBy<'a, K, V, F>
+where
+    K: PartialEq,
+    F: FnMut(&V) -> K,
+{
+    /// Group `data` by the key computed with `key_fn`.
+    pub fn new(data: &'a [V], key_fn: F) -> Self {
+        GroupBy {
+            data,
+            key_fn,
+            offset: 0,
+            current: None,
+        }
+    }
+}
+
+impl<'a, K, V, F> StreamingIterator for GroupBy<'a, K, V, F>
+where
+    K: PartialEq,
+    F: FnMut(&V) -> K,
+{
+    type Item = (K, &'a [V]);
+
+    fn advance(&mut self) {
+        let data = self.data;
+        let rest = &data[self.offset..];
+        let key_fn = &mut self.key_fn;
+
+        self.current = rest.split_first().map(|(first, tail)| {
+            let key = key_fn(first);
+            let len = 1 + tail.iter().take_while(|value| key_fn(value) == key).count();
+            (key, &rest[..len])
+        });
+
+        if let Some((_, group)) = &self.current {
+            self.offset += group.len();
+        }
+    }
+
+    fn get(&self) -> Option<&Self::Item> {
+        self.current.as_ref()
+    }
+}
+
+/// Secondary key of a `TwoLevelBatch`, deciding which sub-batches `insert` and `append`
+/// combine.
+///
+/// By default only equal keys are combined. Override `can_combine` to also merge keys that
+/// differ in ways the pass doesn't care about. The combined sub-batch keeps the key it
+/// was created with.
+///
+/// It is implemented for the primitive types, `String`, `Option` and tuples of up to six
+/// comparable values. Other keys only need an empty impl to combine when equal.
+pub trait BatchKey: PartialEq {
+    /// Whether data inserted with `other` can be added to the sub-batch of this key.
+    fn can_combine(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+macro_rules! impl_batch_key {
+    ($($ty:ty),*) => {
+        $(impl BatchKey for $ty {})*
+    };
+}
+
+impl_batch_key!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    bool,
+    char,
+    (),
+    String,
+    &'static str
+);
+
+impl<T: PartialEq> BatchKey for Option<T> {}
+
+macro_rules! impl_batch_key_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: PartialEq),*> BatchKey for ($($name,)*) {}
+    };
+}
+
+impl_batch_key_tuple!(A);
+impl_batch_key_tuple!(A, B);
+impl_batch_key_tuple!(A, B, C);
+impl_batch_key_tuple!(A, B, C, D);
+impl_batch_key_tuple!(A, B, C, D, E);
+impl_batch_key_tuple!(A, B, C, D, E, F);
+
+/// Collection of a `TwoLevelBatch` that can release the memory it holds beyond its contents,
+/// used by `TwoLevelBatch::shrink_to_fit`.
+pub trait ShrinkToFit {
+    /// Shrinks the capacity of the collection as much as possible.
+    fn shrink_to_fit(&mut self);
+}
+
+impl<T> ShrinkToFit for Vec<T> {
+    fn shrink_to_fit(&mut self) {
+        Vec::shrink_to_fit(self);
+    }
+}
+
+impl<A: smallvec::Array> ShrinkToFit for SmallVec<A> {
+    fn shrink_to_fit(&mut self) {
+        SmallVec::shrink_to_fit(self);
+    }
+}
+
+/// Fragmentation statistics of a `TwoLevelBatch`.
+///
+/// A high ratio of sub-batches to primary keys usually means the combine scan
+/// window is too small for the data being inserted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchStats {
+    /// Number of primary keys.
+    pub primary_keys: usize,
+    /// Total number of sub-batches across all primary keys.
+    pub sub_batches: usize,
+    /// Total number of data items.
+    pub data_items: usize,
+    /// Largest number of sub-batches held by a single primary key.
+    pub max_sub_batches: usize,
+    /// Average number of sub-batches per primary key.
+    pub avg_sub_batches: f32,
+}
+
+/// Number of sub-batches `TwoLevelBatch::insert` scans by default when looking
+/// for a batch with the same secondary key to combine with.
+pub const DEFAULT_COMBINE_SCAN_LIMIT: usize = 8;
+
+/// Batches are `Clone` and `PartialEq` when their keys and collections are,
+/// so a previous frame's batch can be kept around and compared against.
+///
+/// With the `serde-1` feature, they are also `Serialize` and `Deserialize` when their keys
+/// and collections are, so batch contents can be dumped for debugging. The item count is
+/// not stored, it is recomputed from the collections when deserializing.
+#[derive(Derivative, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-1", derive(Serialize))]
 #[derivative(Default(bound = ""))]
 pub struct TwoLevelBatch<PK, SK, C>
 where
     PK: Eq + std::hash::Hash,
 {
     map: fnv::FnvHashMap<PK, SmallVec<[(SK, C); 1]>>,
+    #[cfg_attr(feature = "serde-1", serde(skip_serializing))]
     data_count: usize,
+    #[derivative(Default(value = "DEFAULT_COMBINE_SCAN_LIMIT"))]
+    combine_scan_limit: usize,
 }
 
 impl<PK, SK, C> TwoLevelBatch<PK, SK, C>
 where
     PK: Eq + std::hash::Hash,
-    SK: PartialEq,
+    SK: BatchKey,
     C: IntoIterator,
     C: FromIterator<<C as IntoIterator>::Item>,
     C: Extend<<C as IntoIterator>::Item>,
 {
+    /// Sets how many sub-batches of a primary key are scanned for a matching
+    /// secondary key before a new sub-batch is started. A larger window fragments
+    /// less when many secondary keys share a primary key, at the cost of more
+    /// comparisons per insert.
+    pub fn with_combine_scan_limit(mut self, limit: usize) -> Self {
+        self.combine_scan_limit = limit;
+        self
+    }
+
+    pub fn combine_scan_limit(&self) -> usize {
+        self.combine_scan_limit
+    }
+
+    /// Reserves room for at least `primary_keys` more primary keys, so they can be
+    /// inserted without growing the map.
+    pub fn reserve(&mut self, primary_keys: usize) {
+        self.map.reserve(primary_keys);
+    }
+
+    /// Reserves room for at least `sub` more sub-batches under the primary key `pk`.
+    /// Does nothing when `pk` isn't in the batch yet.
+    pub fn reserve_sub(&mut self, pk: &PK, sub: usize) {
+        if let Some(batch) = self.map.get_mut(pk) {
+            batch.reserve(sub);
+        }
+    }
+
     pub fn clear_inner(&mut self) {
         self.data_count = 0;
         for (_, data) in self.map.iter_mut() {
@@ -92,6 +411,35 @@ where
         self.map.retain(|_, b| b.len() > 0);
     }
 
+    /// Releases the memory the map, the sub-batch lists and the collections hold beyond
+    /// their contents, for example after a frame that batched far more than usual.
+    ///
+    /// Primary keys emptied by `clear_inner` keep their map slot, call `prune` first to
+    /// release those too.
+    pub fn shrink_to_fit(&mut self)
+    where
+        C: ShrinkToFit,
+    {
+        for batch in self.map.values_mut() {
+            for (_, data) in batch.iter_mut() {
+                data.shrink_to_fit();
+            }
+            batch.shrink_to_fit();
+        }
+        self.map.shrink_to_fit();
+    }
+
+    /// Sorts the sub-batches of every primary key by their secondary key,
+    /// so that `iter` yields them in a stable order regardless of insertion order.
+    pub fn sort_sub_batches(&mut self)
+    where
+        SK: Ord,
+    {
+        for batch in self.map.values_mut() {
+            batch.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+    }
+
     pub fn insert(&mut self, pk: PK, sk: SK, data: impl IntoIterator<Item = C::Item>) {
         #[cfg(feature = "profiler")]
         profile_scope!("twolevel_insert");
@@ -103,7 +451,11 @@ where
                 let e = e.get_mut();
                 // scan for the same key to try to combine batches.
                 // Scanning limited slots to limit complexity.
-                if let Some(batch) = e.iter_mut().take(8).find(|(k, _)| k == &sk) {
+                if let Some(batch) = e
+                    .iter_mut()
+                    .take(self.combine_scan_limit)
+                    .find(|(k, _)| k.can_combine(&sk))
+                {
                     batch.1.extend(instance_data);
                 } else {
                     e.push((sk, instance_data.collect()));
@@ -115,6 +467,36 @@ where
         }
     }
 
+    /// Moves all batches of `other` into this one, combining sub-batches
+    /// with compatible secondary keys the same way `insert` does. Sub-batches of
+    /// primary keys found in both batches are merged, not replaced.
+    ///
+    /// The combine scan limit of this batch is used, the one of `other` is ignored.
+    pub fn append(&mut self, other: Self) {
+        self.data_count += other.data_count;
+        for (pk, batches) in other.map {
+            match self.map.entry(pk) {
+                Entry::Occupied(mut e) => {
+                    let e = e.get_mut();
+                    for (sk, data) in batches {
+                        if let Some(batch) = e
+                            .iter_mut()
+                            .take(self.combine_scan_limit)
+                            .find(|(k, _)| k.can_combine(&sk))
+                        {
+                            batch.1.extend(data);
+                        } else {
+                            e.push((sk, data));
+                        }
+                    }
+                }
+                Entry::Vacant(e) => {
+                    e.insert(batches);
+                }
+            }
+        }
+    }
+
     pub fn data<'a>(&'a self) -> impl Iterator<Item = &'a C> {
         self.map
             .iter()
@@ -125,9 +507,253 @@ where
         self.map.iter().map(|(pk, batch)| (pk, batch.iter()))
     }
 
+    /// Like `iter`, but skips sub-batches without data and primary keys left without
+    /// any sub-batch with data, so no draw calls are issued for zero instances.
+    pub fn iter_non_empty<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a PK, impl Iterator<Item = &'a (SK, C)>)>
+    where
+        &'a C: IntoIterator,
+    {
+        self.iter().filter_map(|(pk, batch)| {
+            let mut batch = batch
+                .filter(|batch: &&'a (SK, C)| {
+                    let data: &'a C = &batch.1;
+                    data.into_iter().next().is_some()
+                })
+                .peekable();
+            batch.peek()?;
+            Some((pk, batch))
+        })
+    }
+
+    /// Like `iter`, but yields the primary keys in the order of the values `f` maps them to,
+    /// for example to draw the pipelines sharing bindings one after another.
+    ///
+    /// Keys mapped to equal values come in no particular order. Every call collects and
+    /// sorts the primary keys, so the order isn't kept between calls.
+    pub fn iter_sorted_by<'a, F, O>(
+        &'a self,
+        f: F,
+    ) -> impl Iterator<Item = (&'a PK, impl Iterator<Item = &'a (SK, C)>)>
+    where
+        F: Fn(&PK) -> O,
+        O: Ord,
+    {
+        let mut batches = self.map.iter().collect::<Vec<_>>();
+        batches.sort_by_key(|(pk, _)| f(*pk));
+        batches.into_iter().map(|(pk, batch)| (pk, batch.iter()))
+    }
+
+    /// Iterator over the primary keys currently in the batch, in no particular order.
+    pub fn keys<'a>(&'a self) -> impl Iterator<Item = &'a PK> {
+        self.map.keys()
+    }
+
+    /// Whether the primary key `pk` is in the batch.
+    ///
+    /// A key stays in the batch after `clear_inner` until `prune` is called.
+    pub fn contains_key(&self, pk: &PK) -> bool {
+        self.map.contains_key(pk)
+    }
+
+    /// Number of sub-batches stored under the primary key `pk`, or 0 when it isn't in the batch.
+    pub fn sub_batch_count(&self, pk: &PK) -> usize {
+        self.map.get(pk).map_or(0, |batch| batch.len())
+    }
+
     pub fn count(&self) -> usize {
         self.data_count
     }
+
+    /// Moves all batched data out, leaving the batch empty while keeping
+    /// the map allocation for reuse.
+    ///
+    /// Dropping the iterator early discards the remaining data, so the batch
+    /// is always left empty.
+    pub fn drain<'a>(&'a mut self) -> impl Iterator<Item = (PK, SK, C)> + 'a
+    where
+        PK: Clone,
+    {
+        self.data_count = 0;
+        self.map.drain().flat_map(|(pk, batch)| {
+            batch
+                .into_iter()
+                .map(move |(sk, data)| (pk.clone(), sk, data))
+        })
+    }
+
+    /// Computes fragmentation statistics of the current batch contents.
+    pub fn stats(&self) -> BatchStats {
+        let primary_keys = self.map.len();
+        let (sub_batches, max_sub_batches) = self.map.values().fold((0, 0), |(sum, max), batch| {
+            (sum + batch.len(), max.max(batch.len()))
+        });
+
+        BatchStats {
+            primary_keys,
+            sub_batches,
+            data_items: self.data_count,
+            max_sub_batches,
+            avg_sub_batches: if primary_keys == 0 {
+                0.0
+            } else {
+                sub_batches as f32 / primary_keys as f32
+            },
+        }
+    }
+
+    /// Ends a frame: returns the statistics of the batched contents, then empties the
+    /// batch with `clear_inner` for the next frame.
+    ///
+    /// Primary keys keep their slot for reuse. Call `prune` afterwards to drop them too,
+    /// when the set of primary keys changes a lot between frames.
+    pub fn commit(&mut self) -> BatchStats {
+        let stats = self.stats();
+        self.clear_inner();
+        stats
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<PK, SK, C> TwoLevelBatch<PK, SK, C>
+where
+    PK: Eq + std::hash::Hash + Send,
+    SK: BatchKey + Send,
+    C: IntoIterator + Send,
+    C: FromIterator<<C as IntoIterator>::Item>,
+    C: Extend<<C as IntoIterator>::Item>,
+{
+    /// Builds a batch from a parallel stream of `(primary key, secondary key, data)`.
+    ///
+    /// Every rayon job fills its own batch, which are merged afterwards.
+    /// The resulting sub-batch layout can differ from one built serially,
+    /// but the data stored under each key pair is the same up to ordering.
+    pub fn from_par_iter<I, D>(iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (PK, SK, D)>,
+        D: IntoIterator<Item = C::Item>,
+    {
+        #[cfg(feature = "profiler")]
+        profile_scope!("twolevel_from_par_iter");
+
+        iter.into_par_iter()
+            .fold(Self::default, |mut batch, (pk, sk, data)| {
+                batch.insert(pk, sk, data);
+                batch
+            })
+            .reduce(Self::default, |mut batch, other| {
+                batch.append(other);
+                batch
+            })
+    }
+}
+
+impl<PK, SK, C> TwoLevelBatch<PK, SK, C>
+where
+    PK: Eq + std::hash::Hash,
+    for<'a> &'a C: IntoIterator,
+{
+    /// Removes a primary key together with all of its sub-batches and returns them.
+    pub fn remove(&mut self, pk: &PK) -> Option<SmallVec<[(SK, C); 1]>> {
+        let removed = self.map.remove(pk)?;
+        let removed_count = removed
+            .iter()
+            .map(|(_, data)| data.into_iter().count())
+            .sum::<usize>();
+        self.data_count = self.data_count.saturating_sub(removed_count);
+        Some(removed)
+    }
+
+    /// Number of items stored under the primary key `pk` across all of its sub-batches,
+    /// or 0 when it isn't in the batch.
+    pub fn data_count_for(&self, pk: &PK) -> usize {
+        self.map.get(pk).map_or(0, |batch| {
+            batch.iter().map(|(_, data)| data.into_iter().count()).sum()
+        })
+    }
+
+    /// Keeps only the sub-batches for which the predicate returns `true`.
+    /// Primary keys left without any sub-batch are removed.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&PK, &SK, &C) -> bool,
+    {
+        let data_count = &mut self.data_count;
+        self.map.retain(|pk, batch| {
+            batch.retain(|(sk, data)| {
+                let keep = f(pk, sk, data);
+                if !keep {
+                    *data_count = data_count.saturating_sub(data.into_iter().count());
+                }
+                keep
+            });
+            !batch.is_empty()
+        });
+    }
+
+    /// Mutable access to the sub-batches of a primary key.
+    ///
+    /// Changing the number of items stored in the collections through this
+    /// reference is not reflected by `count`.
+    pub fn get_mut(&mut self, pk: &PK) -> Option<&mut SmallVec<[(SK, C); 1]>> {
+        self.map.get_mut(pk)
+    }
+
+    /// Iterator that returns primary keys and mutable access to the data of
+    /// all inner submitted batches.
+    ///
+    /// Changing the number of items stored in the collections through this
+    /// iterator is not reflected by `count` until `recount` is called.
+    pub fn iter_mut<'a>(
+        &'a mut self,
+    ) -> impl Iterator<Item = (&'a PK, impl Iterator<Item = (&'a SK, &'a mut C)>)> {
+        self.map
+            .iter_mut()
+            .map(|(pk, batch)| (pk, batch.iter_mut().map(|(sk, data)| (&*sk, data))))
+    }
+
+    /// Recomputes the number of stored items from the collections.
+    pub fn recount(&mut self) {
+        self.data_count = self
+            .map
+            .values()
+            .flat_map(|batch| batch.iter())
+            .map(|(_, data)| data.into_iter().count())
+            .sum();
+    }
+}
+
+#[cfg(feature = "serde-1")]
+impl<'de, PK, SK, C> Deserialize<'de> for TwoLevelBatch<PK, SK, C>
+where
+    PK: Eq + std::hash::Hash + Deserialize<'de>,
+    SK: Deserialize<'de>,
+    C: Deserialize<'de>,
+    for<'a> &'a C: IntoIterator,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Fields<PK: Eq + std::hash::Hash, SK, C> {
+            map: fnv::FnvHashMap<PK, SmallVec<[(SK, C); 1]>>,
+            combine_scan_limit: usize,
+        }
+
+        let Fields {
+            map,
+            combine_scan_limit,
+        } = Fields::deserialize(deserializer)?;
+        let mut batch = TwoLevelBatch {
+            map,
+            data_count: 0,
+            combine_scan_limit,
+        };
+        batch.recount();
+        Ok(batch)
+    }
 }
 
 #[derive(Derivative, Debug)]
@@ -330,3 +956,770 @@ where
         self.data_list.len()
     }
 }
+
+/// Batch collection storing each distinct item once, with the number of times it was added.
+///
+/// Use it as the collection of a `TwoLevelBatch` when the same instance data is submitted
+/// many times, to upload every distinct item once and draw it `count` times. Items keep the
+/// order they were first added in. Consuming the collection yields every item as many
+/// times as it was added, so it can be used anywhere a `Vec` of the data is expected.
+#[derive(Derivative, Debug, Clone)]
+#[derivative(Default(bound = ""))]
+pub struct DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash,
+{
+    items: Vec<(V, usize)>,
+    index: fnv::FnvHashMap<V, usize>,
+}
+
+impl<V> DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `count` copies of `item`.
+    pub fn insert_n(&mut self, item: V, count: usize) {
+        match self.index.entry(item) {
+            Entry::Occupied(e) => self.items[*e.get()].1 += count,
+            Entry::Vacant(e) => {
+                self.items.push((e.key().clone(), count));
+                e.insert(self.items.len() - 1);
+            }
+        }
+    }
+
+    /// Iterator over the distinct items and how many times each was added.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a V, usize)> {
+        self.items.iter().map(|(item, count)| (item, *count))
+    }
+
+    /// Number of distinct items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Number of items added, duplicates included.
+    pub fn count(&self) -> usize {
+        self.items.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.index.clear();
+    }
+}
+
+impl<V> ShrinkToFit for DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash,
+{
+    fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+        self.index.shrink_to_fit();
+    }
+}
+
+impl<V> PartialEq for DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl<V> Extend<V> for DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash + Clone,
+{
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert_n(item, 1);
+        }
+    }
+}
+
+impl<V> FromIterator<V> for DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut data = Self::default();
+        data.extend(iter);
+        data
+    }
+}
+
+impl<V> IntoIterator for DedupBatchData<V>
+where
+    V: Eq + std::hash::Hash + Clone,
+{
+    type Item = V;
+    type IntoIter = std::iter::FlatMap<
+        std::vec::IntoIter<(V, usize)>,
+        std::iter::Take<std::iter::Repeat<V>>,
+        fn((V, usize)) -> std::iter::Take<std::iter::Repeat<V>>,
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        fn repeat<V: Clone>((item, count): (V, usize)) -> std::iter::Take<std::iter::Repeat<V>> {
+            std::iter::repeat(item).take(count)
+        }
+        self.items.into_iter().flat_map(repeat as fn(_) -> _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_level_batch_combines_per_key() {
+        let mut batch = OneLevelBatch::<u32, u32>::default();
+        batch.insert(1, vec![1, 2]);
+        batch.insert(2, vec![3]);
+        batch.insert(1, vec![4]);
+
+        assert_eq!(batch.count(), 4);
+        assert_eq!(batch.data().count(), 2);
+
+        let mut ranges = batch.iter().map(|(_, range)| range).collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[1].end, 4);
+
+        batch.clear_inner();
+        assert_eq!(batch.count(), 0);
+        batch.prune();
+        assert_eq!(batch.iter().count(), 0);
+    }
+
+    fn interleaved_sub_batches(batch: &mut TwoLevelBatch<u32, u32, Vec<u32>>) -> usize {
+        for _ in 0..2 {
+            for sk in 0..10 {
+                batch.insert(0, sk, Some(sk));
+            }
+        }
+        assert_eq!(batch.count(), 20);
+        batch.iter().map(|(_, sub)| sub.count()).sum()
+    }
+
+    #[test]
+    fn combine_scan_limit_bounds_merging() {
+        let mut batch = TwoLevelBatch::default();
+        assert_eq!(batch.combine_scan_limit(), DEFAULT_COMBINE_SCAN_LIMIT);
+        // Only the first 8 secondary keys are found again on the second pass.
+        assert_eq!(interleaved_sub_batches(&mut batch), 12);
+
+        let mut batch = TwoLevelBatch::default().with_combine_scan_limit(20);
+        assert_eq!(interleaved_sub_batches(&mut batch), 10);
+
+        let mut batch = TwoLevelBatch::default().with_combine_scan_limit(0);
+        assert_eq!(interleaved_sub_batches(&mut batch), 20);
+    }
+
+    #[test]
+    fn try_for_each_group_stops_on_error() {
+        let items = vec![(1, 'a'), (1, 'b'), (2, 'c'), (3, 'd')];
+
+        let mut seen = Vec::new();
+        let result = items.iter().cloned().try_for_each_group(|key, group| {
+            seen.push((key, group.clone()));
+            if key == 2 {
+                Err("upload failed")
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(result, Err("upload failed"));
+        assert_eq!(seen, vec![(1, vec!['a', 'b']), (2, vec!['c'])]);
+
+        let mut keys = Vec::new();
+        let result = items.into_iter().try_for_each_group(|key, _| {
+            keys.push(key);
+            Ok::<(), ()>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(keys, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn for_each_group_with_capacity_handles_large_groups() {
+        let items = (0..100).map(|i| (0, i)).chain((0..3).map(|i| (1, i)));
+
+        let mut groups = Vec::new();
+        items.for_each_group_with_capacity(4, |key, group| groups.push((key, group.len())));
+        assert_eq!(groups, vec![(0, 100), (1, 3)]);
+
+        let mut groups = Vec::new();
+        (0..100)
+            .map(|i| (i / 70, i))
+            .for_each_group(|key, group| groups.push((key, group.len())));
+        assert_eq!(groups, vec![(0, 70), (1, 30)]);
+    }
+
+    #[test]
+    fn for_each_group_rev_reverses_groups() {
+        let items = vec![(1, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (3, 'e'), (1, 'f')];
+
+        let mut groups = Vec::new();
+        items
+            .into_iter()
+            .for_each_group_rev(|key, group| groups.push((key, group.clone())));
+
+        assert_eq!(
+            groups,
+            vec![
+                (1, vec!['f']),
+                (3, vec!['d', 'e']),
+                (2, vec!['c']),
+                (1, vec!['a', 'b']),
+            ]
+        );
+    }
+
+    #[test]
+    fn for_each_group_peek_sees_next_key() {
+        let items = vec![(1, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (3, 'e'), (1, 'f')];
+
+        let mut groups = Vec::new();
+        items.into_iter().for_each_group_peek(|key, group, next| {
+            groups.push((key, group.clone(), next.cloned()))
+        });
+
+        assert_eq!(
+            groups,
+            vec![
+                (1, vec!['a', 'b'], Some(2)),
+                (2, vec!['c'], Some(3)),
+                (3, vec!['d', 'e'], Some(1)),
+                (1, vec!['f'], None),
+            ]
+        );
+        for pair in groups.windows(2) {
+            assert_eq!(pair[0].2, Some(pair[1].0));
+        }
+    }
+
+    #[test]
+    fn commit_returns_stats_and_clears() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        batch.insert(1, 0, vec![4]);
+        let before = batch.stats();
+
+        let stats = batch.commit();
+        assert_eq!(stats, before);
+        assert_eq!(stats.data_items, 4);
+        assert_eq!(stats.sub_batches, 3);
+
+        assert_eq!(batch.count(), 0);
+        assert_eq!(batch.iter().map(|(_, sub)| sub.count()).sum::<usize>(), 0);
+        assert_eq!(batch.commit().data_items, 0);
+        batch.prune();
+        assert_eq!(batch.stats(), BatchStats::default());
+    }
+
+    #[test]
+    fn two_level_batch_stats() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        assert_eq!(batch.stats(), BatchStats::default());
+
+        for sk in 0..4 {
+            batch.insert(0, sk, vec![sk, sk]);
+        }
+        batch.insert(1, 0, vec![10]);
+        batch.insert(1, 0, vec![11]);
+
+        let stats = batch.stats();
+        assert_eq!(stats.primary_keys, 2);
+        assert_eq!(stats.sub_batches, 5);
+        assert_eq!(stats.data_items, 10);
+        assert_eq!(stats.max_sub_batches, 4);
+        assert_eq!(stats.avg_sub_batches, 2.5);
+    }
+
+    #[test]
+    fn two_level_batch_retain() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        batch.insert(1, 0, vec![4, 5, 6]);
+        batch.insert(2, 1, vec![7]);
+
+        batch.retain(|pk, sk, _| (*pk != 1 && *sk == 0) || *pk == 2);
+
+        assert_eq!(batch.count(), 3);
+        let mut contents = batch
+            .iter()
+            .flat_map(|(pk, sub)| sub.map(move |(sk, data)| (*pk, *sk, data.clone())))
+            .collect::<Vec<_>>();
+        contents.sort();
+        assert_eq!(contents, vec![(0, 0, vec![1, 2]), (2, 1, vec![7])]);
+        assert!(batch.get_mut(&1).is_none());
+    }
+
+    #[test]
+    fn group_by_borrows_contiguous_runs() {
+        let data = [(1, 'a'), (1, 'b'), (2, 'c'), (1, 'd')];
+        let mut groups = GroupBy::new(&data, |&(key, _)| key);
+
+        let mut keys = Vec::new();
+        while let Some((key, group)) = groups.next() {
+            assert!(group.iter().all(|(k, _)| k == key));
+            keys.push((*key, group.len()));
+        }
+        assert_eq!(keys, vec![(1, 2), (2, 1), (1, 1)]);
+
+        let empty: [(u32, char); 0] = [];
+        assert!(GroupBy::new(&empty, |&(key, _)| key).next().is_none());
+    }
+
+    #[test]
+    fn two_level_batch_equality() {
+        let mut a = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        let mut b = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        for batch in &mut [&mut a, &mut b] {
+            batch.insert(0, 1, vec![1, 2]);
+            batch.insert(2, 3, vec![4]);
+        }
+        assert_eq!(a, b);
+
+        b.insert(2, 3, vec![5]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn two_level_batch_clone_is_independent() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 1, vec![1, 2]);
+
+        let snapshot = batch.clone();
+        assert_eq!(snapshot, batch);
+
+        batch.insert(0, 1, vec![3]);
+        batch.insert(1, 1, vec![4]);
+        assert_eq!(batch.count(), 4);
+        assert_eq!(snapshot.count(), 2);
+        assert_eq!(snapshot.data().collect::<Vec<_>>(), vec![&vec![1, 2]]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn two_level_batch_from_par_iter_matches_serial() {
+        // 997 is prime, so stepping by 389 visits every index once in a scrambled order.
+        let items = (0..997u32)
+            .map(|i| (i * 389) % 997)
+            .map(|i| (i % 5, i % 3, vec![i]))
+            .collect::<Vec<_>>();
+
+        let mut serial = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        for (pk, sk, data) in items.clone() {
+            serial.insert(pk, sk, data);
+        }
+        let parallel = TwoLevelBatch::<u32, u32, Vec<u32>>::from_par_iter(items);
+
+        fn totals(batch: &TwoLevelBatch<u32, u32, Vec<u32>>) -> Vec<(u32, u32, usize)> {
+            let mut totals = std::collections::BTreeMap::new();
+            for (pk, batches) in batch.iter() {
+                for (sk, data) in batches {
+                    *totals.entry((*pk, *sk)).or_insert(0) += data.len();
+                }
+            }
+            totals
+                .into_iter()
+                .map(|((pk, sk), n)| (pk, sk, n))
+                .collect()
+        }
+
+        assert_eq!(parallel.count(), serial.count());
+        assert_eq!(totals(&parallel), totals(&serial));
+    }
+
+    #[test]
+    fn two_level_batch_iter_mut() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 1, vec![1, 2]);
+        batch.insert(0, 2, vec![3]);
+
+        for (_, batches) in batch.iter_mut() {
+            for (sk, data) in batches {
+                if *sk == 2 {
+                    data[0] = 30;
+                    data.push(31);
+                }
+            }
+        }
+
+        let mut data = batch.data().cloned().collect::<Vec<_>>();
+        data.sort();
+        assert_eq!(data, vec![vec![1, 2], vec![30, 31]]);
+
+        assert_eq!(batch.count(), 3);
+        batch.recount();
+        assert_eq!(batch.count(), 4);
+    }
+
+    #[test]
+    fn two_level_batch_remove() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        batch.insert(1, 0, vec![4, 5, 6]);
+        assert_eq!(batch.count(), 6);
+
+        assert!(batch.remove(&2).is_none());
+        assert_eq!(batch.count(), 6);
+
+        let removed = batch.remove(&0).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(batch.count(), 3);
+        assert!(batch.get_mut(&0).is_none());
+
+        batch.insert(0, 0, vec![7]);
+        assert_eq!(batch.count(), 4);
+        assert_eq!(batch.get_mut(&0).unwrap()[0], (0, vec![7]));
+
+        // A collection emptied behind the batch's back must not underflow the count.
+        batch.data_count = 0;
+        assert!(batch.remove(&1).is_some());
+        assert_eq!(batch.count(), 0);
+    }
+
+    #[test]
+    fn two_level_batch_sort_sub_batches() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        for &sk in &[3, 1, 4, 0, 2] {
+            batch.insert(0, sk, vec![sk]);
+        }
+        batch.insert(0, 1, vec![10]);
+
+        batch.sort_sub_batches();
+        let (_, sub_batches) = batch.iter().next().unwrap();
+        assert_eq!(
+            sub_batches.collect::<Vec<_>>(),
+            vec![
+                &(0, vec![0]),
+                &(1, vec![1, 10]),
+                &(2, vec![2]),
+                &(3, vec![3]),
+                &(4, vec![4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn two_level_batch_drain() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        batch.insert(1, 0, vec![4]);
+        let capacity = batch.map.capacity();
+
+        let mut drained = batch.drain().collect::<Vec<_>>();
+        drained.sort();
+        assert_eq!(
+            drained,
+            vec![(0, 0, vec![1, 2]), (0, 1, vec![3]), (1, 0, vec![4])]
+        );
+        assert_eq!(batch.count(), 0);
+        assert_eq!(batch.data().count(), 0);
+        assert!(batch.map.capacity() >= capacity);
+
+        batch.insert(2, 0, vec![5]);
+        batch.insert(3, 0, vec![6]);
+        assert!(batch.drain().next().is_some());
+        assert_eq!(batch.count(), 0);
+        assert_eq!(batch.data().count(), 0);
+    }
+
+    #[test]
+    fn two_level_batch_with_smallvec_collections() {
+        let mut batch = TwoLevelBatch::<u32, u32, SmallVec<[u32; 4]>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        // Combines into the existing sub-batch, spilling past the inline capacity.
+        batch.insert(0, 0, vec![4, 5, 6]);
+        batch.insert(1, 0, Some(7));
+        assert_eq!(batch.count(), 7);
+
+        let mut data = batch
+            .data()
+            .map(|data| data.iter().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        data.sort();
+        assert_eq!(data, vec![vec![1, 2, 4, 5, 6], vec![3], vec![7]]);
+
+        batch.retain(|_, sk, _| *sk == 0);
+        assert_eq!(batch.count(), 6);
+    }
+
+    #[test]
+    fn custom_can_combine_merges_compatible_keys() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct DrawKey {
+            pipeline: u32,
+            scissor: [u32; 4],
+        }
+
+        // Scissor rects are unioned by the pass, so only the pipeline has to match.
+        impl BatchKey for DrawKey {
+            fn can_combine(&self, other: &Self) -> bool {
+                self.pipeline == other.pipeline
+            }
+        }
+
+        let key = |pipeline, x| DrawKey {
+            pipeline,
+            scissor: [x, 0, 10, 10],
+        };
+        let mut batch = TwoLevelBatch::<u32, DrawKey, Vec<u32>>::default();
+        batch.insert(0, key(1, 0), vec![1]);
+        batch.insert(0, key(1, 5), vec![2]);
+        batch.insert(0, key(2, 5), vec![3]);
+
+        let sub_batches = batch
+            .iter()
+            .flat_map(|(_, sub)| sub.cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sub_batches,
+            vec![(key(1, 0), vec![1, 2]), (key(2, 5), vec![3])]
+        );
+    }
+
+    #[cfg(feature = "serde-1")]
+    #[test]
+    fn two_level_batch_serde_round_trip() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default().with_combine_scan_limit(4);
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        batch.insert(1, 0, vec![4, 5, 6]);
+
+        let serialized = serde_json::to_string(&batch).unwrap();
+        let deserialized: TwoLevelBatch<u32, u32, Vec<u32>> =
+            serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, batch);
+        assert_eq!(deserialized.count(), 6);
+        assert_eq!(deserialized.combine_scan_limit(), 4);
+    }
+
+    #[test]
+    fn tuple_keys_combine_when_equal() {
+        let mut batch = TwoLevelBatch::<u32, (u32, i16), Vec<u32>>::default();
+        batch.insert(0, (1, -1), vec![1]);
+        batch.insert(0, (1, -1), vec![2]);
+        batch.insert(0, (1, 2), vec![3]);
+        assert_eq!(batch.sub_batch_count(&0), 2);
+        assert_eq!(batch.data_count_for(&0), 3);
+    }
+
+    #[test]
+    fn counts_by_primary_key() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(0, 1, vec![3]);
+        batch.insert(0, 0, vec![4]);
+        batch.insert(1, 0, vec![5]);
+
+        assert_eq!(batch.sub_batch_count(&0), 2);
+        assert_eq!(batch.data_count_for(&0), 4);
+        assert_eq!(batch.sub_batch_count(&1), 1);
+        assert_eq!(batch.data_count_for(&1), 1);
+        assert_eq!(batch.sub_batch_count(&2), 0);
+        assert_eq!(batch.data_count_for(&2), 0);
+    }
+
+    #[test]
+    fn two_level_batch_reserve() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.reserve(100);
+        let capacity = batch.map.capacity();
+        assert!(capacity >= 100);
+        for pk in 0..100 {
+            batch.insert(pk, 0, vec![pk]);
+        }
+        assert_eq!(batch.map.capacity(), capacity);
+
+        batch.reserve_sub(&0, 16);
+        let sub_capacity = batch.map[&0].capacity();
+        assert!(sub_capacity >= 17);
+        for sk in 1..17 {
+            batch.insert(0, sk, vec![sk]);
+        }
+        assert_eq!(batch.map[&0].capacity(), sub_capacity);
+
+        // Keys that aren't batched yet are left alone.
+        batch.reserve_sub(&1000, 16);
+        assert!(!batch.map.contains_key(&1000));
+    }
+
+    #[test]
+    fn two_level_batch_keys() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(3, 0, vec![1]);
+        batch.insert(1, 0, vec![2]);
+        batch.insert(3, 1, vec![3]);
+        batch.insert(2, 0, vec![4]);
+
+        let mut keys = batch.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec![1, 2, 3]);
+        assert!(batch.contains_key(&2));
+        assert!(!batch.contains_key(&0));
+
+        batch.clear_inner();
+        assert!(batch.contains_key(&2));
+        batch.prune();
+        assert!(!batch.contains_key(&2));
+        assert_eq!(batch.keys().count(), 0);
+    }
+
+    #[test]
+    fn two_level_batch_shrink_to_fit() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        for pk in 0..1000 {
+            batch.insert(pk, 0, vec![pk]);
+        }
+        for sk in 1..100 {
+            batch.insert(0, sk, vec![sk]);
+        }
+        let capacity = batch.map.capacity();
+
+        batch.clear_inner();
+        batch.insert(0, 0, vec![1]);
+        batch.map.get_mut(&0).unwrap()[0].1.reserve(100);
+        assert!(batch.map[&0].capacity() >= 100);
+        batch.prune();
+        batch.shrink_to_fit();
+
+        assert!(batch.map.capacity() < capacity);
+        assert!(batch.map[&0].capacity() < 100);
+        assert!(batch.map[&0][0].1.capacity() < 100);
+        assert_eq!(batch.data().collect::<Vec<_>>(), vec![&vec![1]]);
+        assert_eq!(batch.count(), 1);
+    }
+
+    #[test]
+    fn two_level_batch_append() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(0, 0, vec![1, 2]);
+        batch.insert(1, 0, vec![3]);
+        let mut other = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        other.insert(0, 0, vec![4]);
+        other.insert(0, 1, vec![5]);
+        other.insert(2, 0, vec![6, 7]);
+
+        batch.append(other);
+        assert_eq!(batch.count(), 7);
+        batch.sort_sub_batches();
+        let mut contents = batch
+            .iter()
+            .flat_map(|(pk, sub_batches)| {
+                sub_batches.map(move |(sk, data)| (*pk, *sk, data.clone()))
+            })
+            .collect::<Vec<_>>();
+        contents.sort();
+        assert_eq!(
+            contents,
+            vec![
+                (0, 0, vec![1, 2, 4]),
+                (0, 1, vec![5]),
+                (1, 0, vec![3]),
+                (2, 0, vec![6, 7]),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_by_collects_groups_in_order() {
+        let groups = vec![(1, 'a'), (1, 'b'), (2, 'c'), (1, 'd')]
+            .into_iter()
+            .group_by();
+        assert_eq!(
+            groups,
+            vec![(1, vec!['a', 'b']), (2, vec!['c']), (1, vec!['d'])]
+        );
+        assert!(Vec::<(u32, char)>::new().into_iter().group_by().is_empty());
+    }
+
+    #[test]
+    fn dedup_batch_data_collapses_duplicates() {
+        let mut batch = TwoLevelBatch::<u32, u32, DedupBatchData<u32>>::default();
+        batch.insert(0, 0, vec![7, 3, 7]);
+        batch.insert(0, 0, vec![3, 7, 9]);
+
+        assert_eq!(batch.count(), 6);
+        let data = batch.data().next().unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(data.count(), 6);
+        assert_eq!(
+            data.iter().collect::<Vec<_>>(),
+            vec![(&7, 3), (&3, 2), (&9, 1)]
+        );
+        assert_eq!(
+            data.clone().into_iter().collect::<Vec<_>>(),
+            vec![7, 7, 7, 3, 3, 9]
+        );
+    }
+
+    #[test]
+    fn iter_non_empty_skips_empty_sub_batches() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        batch.insert(1, 0, vec![]);
+        batch.insert(1, 1, vec![5]);
+        batch.insert(2, 0, vec![]);
+        batch.insert(3, 0, vec![6]);
+        batch.clear_inner();
+        batch.insert(1, 0, vec![]);
+        batch.insert(1, 1, vec![5]);
+        batch.insert(2, 0, vec![]);
+
+        assert_eq!(batch.iter().count(), 3);
+        let non_empty = batch
+            .iter_non_empty()
+            .map(|(pk, batches)| (*pk, batches.cloned().collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(non_empty, vec![(1, vec![(1, vec![5])])]);
+    }
+
+    #[test]
+    fn iter_sorted_by_follows_projection() {
+        let mut batch = TwoLevelBatch::<u32, u32, Vec<u32>>::default();
+        for pk in &[4, 9, 1, 16, 7] {
+            batch.insert(*pk, 0, vec![*pk]);
+        }
+
+        let ascending = batch
+            .iter_sorted_by(|pk| *pk)
+            .map(|(pk, _)| *pk)
+            .collect::<Vec<_>>();
+        assert_eq!(ascending, vec![1, 4, 7, 9, 16]);
+
+        // Odd keys first, each group in descending order.
+        let projected = batch
+            .iter_sorted_by(|pk| (pk % 2 == 0, std::cmp::Reverse(*pk)))
+            .map(|(pk, batches)| (*pk, batches.cloned().collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            projected,
+            vec![
+                (9, vec![(0, vec![9])]),
+                (7, vec![(0, vec![7])]),
+                (1, vec![(0, vec![1])]),
+                (16, vec![(0, vec![16])]),
+                (4, vec![(0, vec![4])]),
+            ]
+        );
+    }
+}
@@ -8,6 +8,7 @@ layout(location = 0) in vec2 coords;
 layout(location = 1) in vec2 dimensions;
 layout(location = 2) in vec4 tex_coord_bounds;
 layout(location = 3) in vec4 color;
+layout(location = 4) in vec2 rotation;
 
 layout(location = 0) out vec2 out_tex_coords;
 layout(location = 1) out vec4 out_color;
@@ -28,7 +29,13 @@ void main() {
 
     vec2 center = coords * inverse_window_size;
     center.y = 1.0 - center.y; 
-    vec2 final_pos = (center + dimensions * inverse_window_size * pos) * 2.0 - vec2(1.0);
+    // Rotated counterclockwise on screen, where y grows downwards.
+    vec2 corner = dimensions * pos;
+    corner = vec2(
+        corner.x * rotation.x + corner.y * rotation.y,
+        corner.y * rotation.x - corner.x * rotation.y
+    );
+    vec2 final_pos = (center + corner * inverse_window_size) * 2.0 - vec2(1.0);
 
     gl_Position = vec4(final_pos, 0.0, 1.0);
 }
\ No newline at end of file
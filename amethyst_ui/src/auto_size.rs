@@ -0,0 +1,186 @@
+use amethyst_assets::AssetStorage;
+use amethyst_core::ecs::prelude::{
+    Component, DenseVecStorage, Entities, Join, Read, ReadStorage, System, WriteStorage,
+};
+use glyph_brush::rusttype::Scale;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    glyphs::{password_sections, MIN_LINE_HEIGHT},
+    FontAsset, UiText, UiTransform,
+};
+
+/// Makes the `UiTransform` of an entity shrink-wrap its `UiText`.
+///
+/// The `AutoSizeSystem` sets the width and height of the transform to the size of the
+/// text at its font size, plus `padding` on every side. Text isn't wrapped for measuring,
+/// so only hard line breaks add lines. The size is in UI pixels, so the transform should
+/// use `ScaleMode::Pixel` and no `Stretch` along the sized axes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AutoSize {
+    /// Whether the width of the transform follows the text.
+    pub width: bool,
+    /// Whether the height of the transform follows the text.
+    pub height: bool,
+    /// Space added around the text on every side, in pixels.
+    #[serde(default)]
+    pub padding: f32,
+}
+
+impl AutoSize {
+    /// Creates a new component sizing the given axes, without padding.
+    pub fn new(width: bool, height: bool) -> Self {
+        AutoSize {
+            width,
+            height,
+            padding: 0.0,
+        }
+    }
+
+    /// Adds `padding` pixels around the text on every side.
+    pub fn with_padding(mut self, padding: f32) -> Self {
+        self.padding = padding;
+        self
+    }
+}
+
+impl Component for AutoSize {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Sets the size of the `UiTransform` of entities with an `AutoSize` to their `UiText`.
+///
+/// Transforms are only written when the size changes, which lays them out again in the
+/// `UiTransformSystem`, so it should run before that system.
+#[derive(Debug, Default)]
+pub struct AutoSizeSystem;
+
+impl AutoSizeSystem {
+    /// Creates a new AutoSizeSystem.
+    pub fn new() -> Self {
+        AutoSizeSystem
+    }
+}
+
+impl<'a> System<'a> for AutoSizeSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, AutoSize>,
+        ReadStorage<'a, UiText>,
+        WriteStorage<'a, UiTransform>,
+        Read<'a, AssetStorage<FontAsset>>,
+    );
+
+    fn run(&mut self, (entities, auto_sizes, texts, mut transforms, fonts): Self::SystemData) {
+        for (entity, auto_size, text) in (&*entities, &auto_sizes, &texts).join() {
+            let font = match fonts.get(&text.font) {
+                Some(font) => font,
+                None => continue,
+            };
+            let transform = match transforms.get(entity) {
+                Some(transform) => transform,
+                None => continue,
+            };
+            let (width, height) = text_size(font, text);
+            let padding = auto_size.padding * 2.0;
+            let size = (
+                if auto_size.width {
+                    width + padding
+                } else {
+                    transform.width
+                },
+                if auto_size.height {
+                    height + padding
+                } else {
+                    transform.height
+                },
+            );
+            // Getting the transform mutably flags it as modified, so only do it on changes.
+            if (transform.width, transform.height) != size {
+                let transform = transforms.get_mut(entity).unwrap();
+                transform.width = size.0;
+                transform.height = size.1;
+            }
+        }
+    }
+}
+
+/// The width and height of `text` as drawn by the `UiGlyphsSystem`, without wrapping.
+fn text_size(font: &FontAsset, text: &UiText) -> (f32, f32) {
+    let metrics = if text.password {
+        let masked = password_sections(text.text.graphemes(true).count()).collect::<String>();
+        font.measure(&masked, text.font_size)
+    } else {
+        font.measure(&text.text, text.font_size)
+    };
+    let line_height = metrics.ascent - metrics.descent;
+    let spacing = match text.line_height {
+        Some(line_height_scale) => line_height_scale.max(MIN_LINE_HEIGHT) * line_height,
+        None => {
+            let v_metrics = font.0.v_metrics(Scale::uniform(text.font_size));
+            line_height + v_metrics.line_gap
+        }
+    };
+    (
+        metrics.width,
+        line_height + spacing * (metrics.line_count - 1) as f32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Anchor;
+    use amethyst_core::ecs::prelude::{Builder, Entity, RunNow, World};
+
+    fn label(world: &mut World, text: &str, auto_size: AutoSize) -> Entity {
+        let font = FontAsset::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = world
+            .write_resource::<AssetStorage<FontAsset>>()
+            .insert(font);
+        world
+            .create_entity()
+            .with(UiTransform::new(
+                "label".to_string(),
+                Anchor::TopLeft,
+                Anchor::TopLeft,
+                0.0,
+                0.0,
+                0.0,
+                10.0,
+                10.0,
+            ))
+            .with(UiText::new(font, text.to_string(), [1.0; 4], 20.0))
+            .with(auto_size)
+            .build()
+    }
+
+    #[test]
+    fn width_matches_measured_text() {
+        let mut world = World::new();
+        let mut system = AutoSizeSystem::new();
+        System::setup(&mut system, &mut world.res);
+        let hello = label(&mut world, "Hello", AutoSize::new(true, false));
+        let padded = label(
+            &mut world,
+            "Hello\nHello",
+            AutoSize::new(true, true).with_padding(4.0),
+        );
+        system.run_now(&world.res);
+
+        let fonts = world.read_resource::<AssetStorage<FontAsset>>();
+        let texts = world.read_storage::<UiText>();
+        let transforms = world.read_storage::<UiTransform>();
+        let font = fonts.get(&texts.get(hello).unwrap().font).unwrap();
+        let metrics = font.measure("Hello", 20.0);
+        let transform = transforms.get(hello).unwrap();
+        assert_eq!(transform.width, metrics.width);
+        assert_eq!(transform.height, 10.0);
+
+        let transform = transforms.get(padded).unwrap();
+        assert_eq!(transform.width, metrics.width + 8.0);
+        assert!(transform.height > 2.0 * (metrics.ascent - metrics.descent));
+    }
+}
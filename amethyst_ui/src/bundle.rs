@@ -1,9 +1,11 @@
 //! ECS rendering bundle
 
 use crate::{
-    BlinkSystem, CacheSelectionOrderSystem, FontAsset, NoCustomUi, ResizeSystem,
-    SelectionKeyboardSystem, SelectionMouseSystem, TextEditingInputSystem, TextEditingMouseSystem,
-    ToNativeWidget, UiButtonActionRetriggerSystem, UiButtonSystem, UiGlyphsSystem, UiLoaderSystem,
+    AutoSizeSystem, BlinkSystem, CacheSelectionOrderSystem, DragSystem, DropdownSystem, FontAsset,
+    LayoutSystem, NoCustomUi, ProgressBarSystem, ResizeSystem, ScrollSystem,
+    SelectionKeyboardSystem, SelectionMouseSystem, SliderSystem, TextEditingInputSystem,
+    TextEditingMouseSystem, TextRevealSystem, ToNativeWidget, ToggleSystem, TooltipSystem,
+    UiButtonActionRetriggerSystem, UiButtonSystem, UiCursorSystem, UiGlyphsSystem, UiLoaderSystem,
     UiMouseSystem, UiSoundRetriggerSystem, UiSoundSystem, UiTransformSystem, WidgetId,
 };
 use amethyst_assets::Processor;
@@ -22,10 +24,83 @@ use std::marker::PhantomData;
 /// Will fail with error 'No resource with the given id' if the InputBundle is not added.
 #[derive(new)]
 pub struct UiBundle<B: Backend, T: BindingTypes, C = NoCustomUi, W = u32, G = ()> {
+    #[new(value = "\"ui_glyphs_system\"")]
+    text_system_name: &'static str,
+    #[new(value = "\"font_processor\"")]
+    font_processor_name: &'static str,
+    #[new(value = "\"ui_button_system\"")]
+    button_system_name: &'static str,
+    #[new(value = "\"ui_resize_system\"")]
+    resize_system_name: &'static str,
+    #[new(value = "true")]
+    resize: bool,
+    #[new(value = "(4096, 4096)")]
+    glyph_cache_size: (u32, u32),
+    #[new(value = "4096")]
+    glyph_cache_capacity: usize,
     #[new(default)]
     _marker: PhantomData<(B, T, C, W, G)>,
 }
 
+impl<B: Backend, T: BindingTypes, C, W, G> UiBundle<B, T, C, W, G> {
+    /// Sets the name the text layout system is registered under.
+    /// Defaults to `"ui_glyphs_system"`.
+    pub fn with_text_system_name(mut self, name: &'static str) -> Self {
+        self.text_system_name = name;
+        self
+    }
+
+    /// Sets the name the font asset processor is registered under.
+    /// Defaults to `"font_processor"`.
+    pub fn with_font_processor_name(mut self, name: &'static str) -> Self {
+        self.font_processor_name = name;
+        self
+    }
+
+    /// Sets the name the `UiButtonSystem` is registered under.
+    /// Defaults to `"ui_button_system"`.
+    pub fn with_button_system_name(mut self, name: &'static str) -> Self {
+        self.button_system_name = name;
+        self
+    }
+
+    /// Sets the name the resize system is registered under.
+    /// Defaults to `"ui_resize_system"`.
+    pub fn with_resize_system_name(mut self, name: &'static str) -> Self {
+        self.resize_system_name = name;
+        self
+    }
+
+    /// Enables or disables the resize system. Enabled by default.
+    ///
+    /// With a window that never changes size `UiResize` components are never
+    /// updated, so the system can be left out. The `UiResize` storage then has
+    /// to be registered by hand if the component is still used.
+    pub fn with_resize(mut self, enabled: bool) -> Self {
+        self.resize = enabled;
+        self
+    }
+
+    /// Sets the largest size the glyph cache texture can grow to, in pixels.
+    /// Defaults to 4096x4096.
+    ///
+    /// Least recently used glyphs are evicted when the cache is full, so it only has to be
+    /// large enough for the text visible in a single frame.
+    pub fn with_glyph_cache_size(mut self, size: (u32, u32)) -> Self {
+        self.glyph_cache_size = size;
+        self
+    }
+
+    /// Sets how many rasterized glyphs the glyph cache keeps. Defaults to 4096.
+    ///
+    /// Past that, the least recently used glyphs are evicted and rasterized again when they
+    /// are shown again, which bounds the cache for text that keeps changing, like logs.
+    pub fn with_glyph_cache_capacity(mut self, capacity: usize) -> Self {
+        self.glyph_cache_capacity = capacity;
+        self
+    }
+}
+
 impl<'a, 'b, B, T, C, W, G> SystemBundle<'a, 'b> for UiBundle<B, T, C, W, G>
 where
     B: Backend,
@@ -40,14 +115,36 @@ where
             "ui_loader",
             &[],
         );
+        // Every system reacting to the mouse reads the position it sets.
+        builder.add(UiCursorSystem::<T>::new(), "ui_cursor_system", &[]);
+        builder.add(
+            ScrollSystem::<T>::new(),
+            "ui_scroll_system",
+            &["ui_cursor_system"],
+        );
+        builder.add(ProgressBarSystem::new(), "ui_progress_bar_system", &[]);
+        builder.add(
+            LayoutSystem::new(),
+            "ui_layout_system",
+            &["transform_system"],
+        );
+        builder.add(AutoSizeSystem::new(), "ui_auto_size_system", &[]);
         builder.add(
             UiTransformSystem::default(),
             "ui_transform",
-            &["transform_system"],
+            // Scrolled containers, progress bars and layouts move and size their children
+            // during layout, auto sized labels fit their text.
+            &[
+                "transform_system",
+                "ui_scroll_system",
+                "ui_progress_bar_system",
+                "ui_layout_system",
+                "ui_auto_size_system",
+            ],
         );
         builder.add(
             Processor::<FontAsset>::new(),
-            "font_processor",
+            self.font_processor_name,
             &["ui_loader"],
         );
         builder.add(
@@ -77,22 +174,49 @@ where
             // Hard requirement. The system assumes the text to edit is selected.
             &["ui_mouse_selection", "ui_keyboard_selection"],
         );
-        builder.add(ResizeSystem::new(), "ui_resize_system", &[]);
+        if self.resize {
+            builder.add(ResizeSystem::new(), self.resize_system_name, &[]);
+        }
         builder.add(
             UiMouseSystem::<T>::new(),
             "ui_mouse_system",
-            &["ui_transform"],
+            &["ui_transform", "ui_cursor_system"],
+        );
+        builder.add(
+            DragSystem::<T>::new(),
+            "ui_drag_system",
+            &["ui_mouse_system"],
+        );
+        builder.add(
+            TooltipSystem::<T>::new(),
+            "ui_tooltip_system",
+            &["ui_mouse_system"],
+        );
+        builder.add(
+            SliderSystem::<T>::new(),
+            "ui_slider_system",
+            &["ui_mouse_system"],
+        );
+        builder.add(
+            ToggleSystem::new(),
+            "ui_toggle_system",
+            &["ui_mouse_system"],
+        );
+        builder.add(
+            DropdownSystem::<T>::new(),
+            "ui_dropdown_system",
+            &["ui_mouse_system"],
         );
         builder.add(
             UiButtonSystem::new(),
-            "ui_button_system",
+            self.button_system_name,
             &["ui_mouse_system"],
         );
 
         builder.add(
             UiButtonActionRetriggerSystem::new(),
             "ui_button_action_retrigger_system",
-            &["ui_button_system"],
+            &[self.button_system_name],
         );
         builder.add(UiSoundSystem::new(), "ui_sound_system", &[]);
         builder.add(
@@ -103,18 +227,80 @@ where
 
         // Required for text editing. You want the cursor image to blink.
         builder.add(BlinkSystem, "blink_system", &[]);
+        builder.add(TextRevealSystem::new(), "ui_text_reveal_system", &[]);
         builder.add(
-            UiGlyphsSystem::<B>::new(),
-            "ui_glyphs_system",
+            UiGlyphsSystem::<B>::new()
+                .with_max_cache_size(self.glyph_cache_size)
+                .with_cache_capacity(self.glyph_cache_capacity),
+            self.text_system_name,
             &[
                 "ui_loader",
                 "ui_transform",
-                "font_processor",
+                self.font_processor_name,
                 "ui_text_editing_mouse_system",
                 "ui_text_editing_input_system",
+                "ui_text_reveal_system",
             ],
         );
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::System;
+    use amethyst_input::StringBindings;
+    use amethyst_rendy::rendy::empty::Backend as EmptyBackend;
+
+    struct NoopSystem;
+
+    impl<'a> System<'a> for NoopSystem {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    fn dispatcher_builder<'a, 'b>() -> DispatcherBuilder<'a, 'b> {
+        let mut builder = DispatcherBuilder::new();
+        builder.add(NoopSystem, "transform_system", &[]);
+        builder
+    }
+
+    #[test]
+    fn custom_system_names() {
+        let mut builder = dispatcher_builder();
+        UiBundle::<EmptyBackend, StringBindings>::new()
+            .with_text_system_name("menu_text")
+            .with_font_processor_name("menu_font_processor")
+            .with_button_system_name("menu_buttons")
+            .with_resize_system_name("menu_resize")
+            .build(&mut builder)
+            .unwrap();
+
+        // Adding a system panics when one of its dependencies isn't registered.
+        builder.add(
+            NoopSystem,
+            "after_ui",
+            &[
+                "menu_text",
+                "menu_font_processor",
+                "menu_buttons",
+                "menu_resize",
+            ],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "No such system registered (\"ui_resize_system\")")]
+    fn resize_system_disabled() {
+        let mut builder = dispatcher_builder();
+        UiBundle::<EmptyBackend, StringBindings>::new()
+            .with_resize(false)
+            .build(&mut builder)
+            .unwrap();
+
+        builder.add(NoopSystem, "after_resize", &["ui_resize_system"]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::marker::PhantomData;
+
+use amethyst_core::ecs::prelude::{Read, ReadExpect, System, Write};
+use amethyst_input::{BindingTypes, InputHandler};
+use amethyst_window::ScreenDimensions;
+
+use crate::UiScale;
+
+/// Resource holding the position of the mouse cursor over the UI, updated once per frame by
+/// the `UiCursorSystem`.
+///
+/// Both positions are from the bottom left of the screen, unlike the position of the
+/// `InputHandler` which starts at the top left. They are `None` while no cursor is over the
+/// window.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UiCursor {
+    /// The position in UI pixels, the unit of `ScaleMode::Pixel` transforms, which doesn't
+    /// change with the hidpi factor of the window or the `UiScale`.
+    pub position: Option<(f32, f32)>,
+    /// The position in screen pixels, the unit of the laid out `pixel_x` and `pixel_y` of
+    /// transforms. This is what elements are hit-tested against.
+    pub pixel_position: Option<(f32, f32)>,
+}
+
+/// Converts the physical position of the cursor, from the top left of the window, into the
+/// screen pixels from the bottom left used by the layout.
+pub(crate) fn cursor_pixels(x: f32, y: f32, screen_dimensions: &ScreenDimensions) -> (f32, f32) {
+    (x, screen_dimensions.height() - y)
+}
+
+/// Updates the `UiCursor` from the mouse position of the `InputHandler`.
+///
+/// Every system reacting to the mouse reads the `UiCursor`, so this should run before them.
+/// The generic type T represent the T generic parameter of the InputHandler<T>.
+#[derive(Debug)]
+pub struct UiCursorSystem<T: BindingTypes> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: BindingTypes> UiCursorSystem<T> {
+    /// Creates a new UiCursorSystem.
+    pub fn new() -> Self {
+        UiCursorSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: BindingTypes> System<'a> for UiCursorSystem<T> {
+    type SystemData = (
+        Read<'a, InputHandler<T>>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiScale>,
+        Write<'a, UiCursor>,
+    );
+
+    fn run(&mut self, (input, screen_dimensions, ui_scale, mut cursor): Self::SystemData) {
+        let scale = ui_scale.effective(&screen_dimensions);
+        cursor.pixel_position = input
+            .mouse_position()
+            .map(|(x, y)| cursor_pixels(x as f32, y as f32, &screen_dimensions));
+        cursor.position = cursor.pixel_position.map(|(x, y)| (x / scale, y / scale));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::{
+        ecs::prelude::{RunNow, World},
+        shrev::EventChannel,
+    };
+    use amethyst_input::StringBindings;
+    use winit::{dpi::LogicalPosition, DeviceId, Event, ModifiersState, WindowEvent, WindowId};
+
+    #[test]
+    fn hidpi_halves_ui_position() {
+        let mut world = World::new();
+        let mut system = UiCursorSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(400, 300, 2.0));
+        system.run_now(&world.res);
+        assert_eq!(*world.read_resource::<UiCursor>(), UiCursor::default());
+
+        // The logical position of winit, 200x100 physical pixels from the top left.
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: LogicalPosition::new(100.0, 50.0),
+                modifiers: ModifiersState::default(),
+            },
+        };
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_event(&event, &mut EventChannel::new(), 2.0);
+        system.run_now(&world.res);
+
+        let cursor = *world.read_resource::<UiCursor>();
+        assert_eq!(cursor.pixel_position, Some((200.0, 200.0)));
+        assert_eq!(cursor.position, Some((100.0, 100.0)));
+
+        world.add_resource(UiScale::new(2.0));
+        system.run_now(&world.res);
+        let cursor = *world.read_resource::<UiCursor>();
+        assert_eq!(cursor.pixel_position, Some((200.0, 200.0)));
+        assert_eq!(cursor.position, Some((50.0, 50.0)));
+    }
+}
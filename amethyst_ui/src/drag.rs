@@ -0,0 +1,367 @@
+use amethyst_core::{
+    ecs::{
+        prelude::{
+            Component, Entities, Entity, Join, Read, ReadStorage, ReaderId, Resources, System,
+            SystemData, Write, WriteStorage,
+        },
+        storage::NullStorage,
+    },
+    math::Vector2,
+    shrev::EventChannel,
+    Hidden, HiddenPropagate,
+};
+use amethyst_input::{BindingTypes, InputHandler};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use winit::{Event, MouseButton, WindowEvent};
+
+use crate::{
+    hit_shape::{hit, UiHitShape},
+    targeted, Interactable, UiCursor, UiEvent, UiEventType, UiHovered, UiTransform,
+};
+
+/// A component that tags an entity as draggable with the left mouse button.
+/// Will only work if the entity also has a `UiTransform` and is `Interactable`.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Draggable;
+
+impl Component for Draggable {
+    type Storage = NullStorage<Draggable>;
+}
+
+struct ActiveDrag {
+    entity: Entity,
+    start_position: (f32, f32),
+    /// In UI pixels, like the local position of the transform.
+    start_cursor: (f32, f32),
+    element_offset: Vector2<f32>,
+}
+
+/// Moves `Draggable` entities with the cursor while the mouse button is held on them.
+///
+/// Writes `UiEventType::Dragging` while the element moves, and `UiEventType::Dropped` when
+/// the button is released, targeting the dragged element. Losing window focus cancels the
+/// drag and moves the element back to where it started.
+/// The generic type T represent the T generic parameter of the InputHandler<T>.
+pub struct DragSystem<T: BindingTypes> {
+    dragging: Option<ActiveDrag>,
+    ui_reader_id: Option<ReaderId<UiEvent>>,
+    window_reader_id: Option<ReaderId<Event>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BindingTypes> DragSystem<T> {
+    /// Creates a new DragSystem.
+    pub fn new() -> Self {
+        DragSystem {
+            dragging: None,
+            ui_reader_id: None,
+            window_reader_id: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: BindingTypes> System<'a> for DragSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiTransform>,
+        ReadStorage<'a, UiHitShape>,
+        ReadStorage<'a, Draggable>,
+        ReadStorage<'a, Interactable>,
+        ReadStorage<'a, Hidden>,
+        ReadStorage<'a, HiddenPropagate>,
+        Read<'a, InputHandler<T>>,
+        Read<'a, UiCursor>,
+        Read<'a, EventChannel<Event>>,
+        Write<'a, EventChannel<UiEvent>>,
+        Option<Read<'a, UiHovered>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut transforms,
+            hit_shapes,
+            draggables,
+            interactables,
+            hiddens,
+            hidden_propagates,
+            input,
+            ui_cursor,
+            window_events,
+            mut ui_events,
+            hovered,
+        ): Self::SystemData,
+    ) {
+        // Elements move by the distance in UI pixels, but are hit in screen pixels.
+        let cursor = match (ui_cursor.position, ui_cursor.pixel_position) {
+            (Some(position), Some(pixels)) => Some((position, pixels)),
+            _ => None,
+        };
+
+        let focus_lost = window_events
+            .read(
+                self.window_reader_id
+                    .as_mut()
+                    .expect("`DragSystem::setup` was not called before `DragSystem::run`"),
+            )
+            .any(|event| match event {
+                Event::WindowEvent {
+                    event: WindowEvent::Focused(false),
+                    ..
+                } => true,
+                _ => false,
+            });
+        if focus_lost {
+            if let Some(drag) = self.dragging.take() {
+                if let Some(transform) = transforms.get_mut(drag.entity) {
+                    transform.local_x = drag.start_position.0;
+                    transform.local_y = drag.start_position.1;
+                }
+            }
+        }
+
+        for event in ui_events.read(
+            self.ui_reader_id
+                .as_mut()
+                .expect("`DragSystem::setup` was not called before `DragSystem::run`"),
+        ) {
+            if event.event_type != UiEventType::ClickStart || !draggables.contains(event.target) {
+                continue;
+            }
+            if let (Some(transform), Some((position, pixels))) =
+                (transforms.get(event.target), cursor)
+            {
+                self.dragging = Some(ActiveDrag {
+                    entity: event.target,
+                    start_position: (transform.local_x, transform.local_y),
+                    start_cursor: position,
+                    element_offset: Vector2::new(
+                        pixels.0 - transform.pixel_x,
+                        pixels.1 - transform.pixel_y,
+                    ),
+                });
+            }
+        }
+
+        let (drag, (position, pixels)) = match (self.dragging.as_ref(), cursor) {
+            (Some(drag), Some(cursor)) => (drag, cursor),
+            _ => return,
+        };
+
+        if let Some(transform) = transforms.get_mut(drag.entity) {
+            let x = drag.start_position.0 + position.0 - drag.start_cursor.0;
+            let y = drag.start_position.1 + position.1 - drag.start_cursor.1;
+            if transform.local_x != x || transform.local_y != y {
+                transform.local_x = x;
+                transform.local_y = y;
+                ui_events.single_write(UiEvent::new(
+                    UiEventType::Dragging {
+                        element_offset: drag.element_offset,
+                    },
+                    drag.entity,
+                ));
+            }
+        }
+
+        if !input.mouse_button_is_down(MouseButton::Left) {
+            let dragged = drag.entity;
+            let dropped_on = match hovered.map(|hovered| hovered.entity) {
+                Some(Some(entity)) if entity != dragged => {
+                    Some(entity).filter(|entity| interactables.contains(*entity))
+                }
+                Some(None) => None,
+                // The dragged element follows the cursor, so it usually covers the element it
+                // is dropped on. Without a `UiMouseSystem` nothing is hovered either.
+                _ => targeted(
+                    pixels,
+                    (
+                        &*entities,
+                        &transforms,
+                        hit_shapes.maybe(),
+                        interactables.maybe(),
+                        !&hiddens,
+                        !&hidden_propagates,
+                    )
+                        .join()
+                        .filter(|(entity, transform, shape, _, _, _)| {
+                            *entity != dragged && hit(transform, *shape, pixels)
+                        })
+                        .map(|(entity, transform, _, interactable, _, _)| {
+                            (entity, transform, interactable)
+                        }),
+                ),
+            };
+            ui_events.single_write(UiEvent::new(UiEventType::Dropped { dropped_on }, dragged));
+            self.dragging = None;
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.ui_reader_id = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+        self.window_reader_id = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Anchor, UiCursorSystem};
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use amethyst_input::StringBindings;
+    use amethyst_window::ScreenDimensions;
+    use winit::{dpi::LogicalPosition, DeviceId, ElementState, ModifiersState, WindowId};
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    fn send_input(world: &World, event: WindowEvent) {
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_event(&window_event(event), &mut EventChannel::new(), 1.0);
+    }
+
+    fn move_cursor(world: &World, x: f64, y: f64) {
+        send_input(
+            world,
+            WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: LogicalPosition::new(x, y),
+                modifiers: ModifiersState::default(),
+            },
+        );
+        UiCursorSystem::<StringBindings>::new().run_now(&world.res);
+    }
+
+    fn left_mouse(world: &World, state: ElementState) {
+        send_input(
+            world,
+            WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state,
+                button: MouseButton::Left,
+                modifiers: ModifiersState::default(),
+            },
+        );
+    }
+
+    fn square(id: &str, x: f32, y: f32, z: f32, size: f32) -> UiTransform {
+        UiTransform::new(
+            id.to_string(),
+            Anchor::BottomLeft,
+            Anchor::Middle,
+            x,
+            y,
+            z,
+            size,
+            size,
+        )
+    }
+
+    fn setup() -> (
+        World,
+        DragSystem<StringBindings>,
+        ReaderId<UiEvent>,
+        Entity,
+        Entity,
+    ) {
+        let mut world = World::new();
+        let mut system = DragSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        System::setup(&mut UiCursorSystem::<StringBindings>::new(), &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+        let reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let item = world
+            .create_entity()
+            .with(square("item", 50.0, 50.0, 1.0, 20.0))
+            .with(Interactable)
+            .with(Draggable)
+            .build();
+        let slot = world
+            .create_entity()
+            .with(square("slot", 150.0, 150.0, 0.0, 50.0))
+            .with(Interactable)
+            .build();
+
+        // Press on the item, 5 pixels right of its center.
+        move_cursor(&world, 55.0, 150.0);
+        left_mouse(&world, ElementState::Pressed);
+        world
+            .write_resource::<EventChannel<UiEvent>>()
+            .single_write(UiEvent::new(UiEventType::ClickStart, item));
+        system.run_now(&world.res);
+
+        (world, system, reader, item, slot)
+    }
+
+    fn position(world: &World, entity: Entity) -> (f32, f32) {
+        let transforms = world.read_storage::<UiTransform>();
+        let transform = transforms.get(entity).unwrap();
+        (transform.local_x, transform.local_y)
+    }
+
+    #[test]
+    fn drag_and_drop_on_target() {
+        let (world, mut system, mut reader, item, slot) = setup();
+
+        move_cursor(&world, 155.0, 50.0);
+        system.run_now(&world.res);
+        left_mouse(&world, ElementState::Released);
+        system.run_now(&world.res);
+
+        assert_eq!(position(&world, item), (150.0, 150.0));
+        let events = world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .filter(|event| event.event_type != UiEventType::ClickStart)
+            .map(|event| (event.event_type.clone(), event.target))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (
+                    UiEventType::Dragging {
+                        element_offset: Vector2::new(5.0, 0.0),
+                    },
+                    item
+                ),
+                (
+                    UiEventType::Dropped {
+                        dropped_on: Some(slot),
+                    },
+                    item
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn losing_focus_cancels_drag() {
+        let (world, mut system, _reader, item, _slot) = setup();
+
+        move_cursor(&world, 155.0, 50.0);
+        system.run_now(&world.res);
+        assert_eq!(position(&world, item), (150.0, 150.0));
+
+        world
+            .write_resource::<EventChannel<Event>>()
+            .single_write(window_event(WindowEvent::Focused(false)));
+        system.run_now(&world.res);
+        assert_eq!(position(&world, item), (50.0, 50.0));
+
+        // The cursor moving afterwards doesn't pick the drag back up.
+        move_cursor(&world, 100.0, 100.0);
+        system.run_now(&world.res);
+        assert_eq!(position(&world, item), (50.0, 50.0));
+    }
+}
@@ -0,0 +1,376 @@
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReaderId, Resources, System,
+        SystemData, Write, WriteStorage,
+    },
+    shrev::EventChannel,
+    Hidden, Parent,
+};
+use amethyst_input::{BindingTypes, InputHandler};
+use std::marker::PhantomData;
+use winit::MouseButton;
+
+use crate::{Anchor, Interactable, UiEvent, UiEventType, UiImage, UiText, UiTransform};
+
+/// Z offset of the option list from its dropdown, so it is drawn over the elements around.
+const OPTION_LIST_Z: f32 = 100.0;
+
+/// A dropdown, or combo box, selecting one of `options`.
+///
+/// The entity needs a `UiTransform`, a `UiText` and the `Interactable` component. The
+/// `DropdownSystem` keeps its text set to the selected option, and while `expanded` lists
+/// the options below it, one row per option drawn with `row_image` and the font and colors
+/// of the dropdown's `UiText`.
+#[derive(Debug, Clone)]
+pub struct UiDropdown {
+    /// The options to choose from.
+    pub options: Vec<String>,
+    /// Index of the selected option.
+    pub selected: usize,
+    /// Whether the option list is shown.
+    pub expanded: bool,
+    /// Background image of the option rows.
+    pub row_image: UiImage,
+    rows: Vec<Entity>,
+}
+
+impl UiDropdown {
+    /// Creates a collapsed dropdown with the first option selected.
+    pub fn new(options: Vec<String>, row_image: UiImage) -> Self {
+        UiDropdown {
+            options,
+            selected: 0,
+            expanded: false,
+            row_image,
+            rows: Vec::new(),
+        }
+    }
+
+    /// The selected option, if `selected` is a valid index.
+    pub fn selected_option(&self) -> Option<&str> {
+        self.options.get(self.selected).map(String::as_str)
+    }
+
+    /// The entities of the option rows, in the order of `options`, once they were created
+    /// by the `DropdownSystem`.
+    pub fn row_entities(&self) -> &[Entity] {
+        &self.rows
+    }
+}
+
+impl Component for UiDropdown {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Opens and closes `UiDropdown`s when clicked, and selects the option clicked in the list.
+///
+/// Pressing the left mouse button anywhere outside an expanded dropdown and its list closes
+/// it. Selecting an option writes a `UiEventType::ValueChange` event targeting the dropdown;
+/// the new index can be read from its `UiDropdown` component.
+/// The generic type T represent the T generic parameter of the InputHandler<T>.
+pub struct DropdownSystem<T: BindingTypes> {
+    mouse_was_down: bool,
+    ui_reader_id: Option<ReaderId<UiEvent>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BindingTypes> DropdownSystem<T> {
+    /// Creates a new DropdownSystem.
+    pub fn new() -> Self {
+        DropdownSystem {
+            mouse_was_down: false,
+            ui_reader_id: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: BindingTypes> System<'a> for DropdownSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiDropdown>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiImage>,
+        WriteStorage<'a, Parent>,
+        WriteStorage<'a, Interactable>,
+        WriteStorage<'a, Hidden>,
+        Read<'a, InputHandler<T>>,
+        Write<'a, EventChannel<UiEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut dropdowns,
+            mut texts,
+            mut transforms,
+            mut images,
+            mut parents,
+            mut interactables,
+            mut hiddens,
+            input,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        let mouse_down = input.mouse_button_is_down(MouseButton::Left);
+        let pressed = mouse_down && !self.mouse_was_down;
+        self.mouse_was_down = mouse_down;
+
+        let mut pressed_on = Vec::new();
+        let mut clicked = Vec::new();
+        for event in events.read(
+            self.ui_reader_id
+                .as_mut()
+                .expect("`DropdownSystem::setup` was not called before `DropdownSystem::run`"),
+        ) {
+            match event.event_type {
+                UiEventType::ClickStart => pressed_on.push(event.target),
+                UiEventType::Click => clicked.push(event.target),
+                _ => {}
+            }
+        }
+
+        let mut emitted = Vec::new();
+        for (entity, dropdown) in (&*entities, &mut dropdowns).join() {
+            let is_part = |target: &Entity| *target == entity || dropdown.rows.contains(target);
+            if pressed && !pressed_on.iter().any(is_part) {
+                dropdown.expanded = false;
+            }
+            for target in &clicked {
+                if *target == entity {
+                    dropdown.expanded = !dropdown.expanded;
+                } else if let Some(index) = dropdown.rows.iter().position(|row| row == target) {
+                    dropdown.expanded = false;
+                    if index != dropdown.selected {
+                        dropdown.selected = index;
+                        emitted.push(UiEvent::new(UiEventType::ValueChange, entity));
+                    }
+                }
+            }
+
+            match (texts.get_mut(entity), dropdown.selected_option()) {
+                (Some(text), Some(option)) => {
+                    if text.text != option {
+                        text.text = option.to_string();
+                    }
+                }
+                (Some(_), None) => {}
+                (None, _) => continue,
+            }
+            let (id, width, height) = match transforms.get(entity) {
+                Some(t) => (t.id.clone(), t.pixel_width, t.pixel_height),
+                None => continue,
+            };
+
+            if !dropdown.expanded {
+                for row in &dropdown.rows {
+                    if !hiddens.contains(*row) {
+                        hiddens
+                            .insert(*row, Hidden)
+                            .expect("unreachable: We are inserting");
+                    }
+                }
+                continue;
+            }
+
+            // Rows are created when the dropdown is first opened, and replaced when the
+            // options changed since.
+            let rows_alive = dropdown.rows.iter().all(|row| entities.is_alive(*row));
+            if dropdown.rows.len() != dropdown.options.len() || !rows_alive {
+                for row in dropdown.rows.drain(..) {
+                    // A row that is already gone doesn't need deleting.
+                    let _ = entities.delete(row);
+                }
+                for _ in &dropdown.options {
+                    let row = entities.create();
+                    parents
+                        .insert(row, Parent { entity })
+                        .expect("unreachable: We are inserting");
+                    interactables
+                        .insert(row, Interactable)
+                        .expect("unreachable: We are inserting");
+                    dropdown.rows.push(row);
+                }
+            }
+
+            for (index, (row, option)) in dropdown.rows.iter().zip(&dropdown.options).enumerate() {
+                // Only touch the rows when they changed, so the layout isn't redone every frame.
+                let y = -(index as f32) * height;
+                let up_to_date = transforms.get(*row).map_or(false, |t| {
+                    t.local_y == y && t.width == width && t.height == height
+                });
+                if !up_to_date {
+                    let transform = UiTransform::new(
+                        format!("{}_option_{}", id, index),
+                        Anchor::BottomMiddle,
+                        Anchor::TopMiddle,
+                        0.0,
+                        y,
+                        OPTION_LIST_Z,
+                        width,
+                        height,
+                    );
+                    transforms
+                        .insert(*row, transform)
+                        .expect("unreachable: We are inserting");
+                }
+                if texts.get(*row).map_or(true, |text| text.text != *option) {
+                    let mut text = texts
+                        .get(entity)
+                        .cloned()
+                        .expect("unreachable: We checked the dropdown has a text");
+                    text.text = option.clone();
+                    texts
+                        .insert(*row, text)
+                        .expect("unreachable: We are inserting");
+                }
+                if images.get(*row) != Some(&dropdown.row_image) {
+                    images
+                        .insert(*row, dropdown.row_image.clone())
+                        .expect("unreachable: We are inserting");
+                }
+                hiddens.remove(*row);
+            }
+        }
+        events.iter_write(emitted);
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.ui_reader_id = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FontAsset;
+    use amethyst_assets::AssetStorage;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use amethyst_input::StringBindings;
+    use glyph_brush::rusttype::Font;
+    use winit::{DeviceId, ElementState, Event, ModifiersState, WindowEvent, WindowId};
+
+    fn setup() -> (World, DropdownSystem<StringBindings>, Entity) {
+        let mut world = World::new();
+        let mut system = DropdownSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let options = vec!["Low", "Medium", "High"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let transform = UiTransform::new(
+            "quality".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            100.0,
+            20.0,
+        );
+        let quality = world
+            .create_entity()
+            .with(transform)
+            .with(UiText::new(font, String::new(), [1.0; 4], 16.0))
+            .with(Interactable)
+            .with(UiDropdown::new(options, UiImage::SolidColor([0.2; 4])))
+            .build();
+        system.run_now(&world.res);
+
+        (world, system, quality)
+    }
+
+    fn click(world: &World, target: Entity) {
+        let mut events = world.write_resource::<EventChannel<UiEvent>>();
+        events.single_write(UiEvent::new(UiEventType::ClickStart, target));
+        events.single_write(UiEvent::new(UiEventType::ClickStop, target));
+        events.single_write(UiEvent::new(UiEventType::Click, target));
+    }
+
+    fn rows(world: &World, dropdown: Entity) -> Vec<Entity> {
+        world
+            .read_storage::<UiDropdown>()
+            .get(dropdown)
+            .unwrap()
+            .row_entities()
+            .to_vec()
+    }
+
+    #[test]
+    fn selecting_an_option_collapses() {
+        let (world, mut system, quality) = setup();
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+        assert_eq!(
+            world.read_storage::<UiText>().get(quality).unwrap().text,
+            "Low"
+        );
+
+        click(&world, quality);
+        system.run_now(&world.res);
+        let options = rows(&world, quality);
+        assert_eq!(options.len(), 3);
+        {
+            let hiddens = world.read_storage::<Hidden>();
+            assert!(options.iter().all(|row| !hiddens.contains(*row)));
+            let transforms = world.read_storage::<UiTransform>();
+            assert_eq!(transforms.get(options[2]).unwrap().local_y, -40.0);
+            let texts = world.read_storage::<UiText>();
+            assert_eq!(texts.get(options[1]).unwrap().text, "Medium");
+        }
+
+        click(&world, options[1]);
+        system.run_now(&world.res);
+        {
+            let dropdowns = world.read_storage::<UiDropdown>();
+            let dropdown = dropdowns.get(quality).unwrap();
+            assert_eq!(dropdown.selected, 1);
+            assert!(!dropdown.expanded);
+            let hiddens = world.read_storage::<Hidden>();
+            assert!(options.iter().all(|row| hiddens.contains(*row)));
+        }
+        assert_eq!(
+            world.read_storage::<UiText>().get(quality).unwrap().text,
+            "Medium"
+        );
+        let value_changes = world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .filter(|event| event.event_type == UiEventType::ValueChange)
+            .map(|event| event.target)
+            .collect::<Vec<_>>();
+        assert_eq!(value_changes, vec![quality]);
+    }
+
+    #[test]
+    fn pressing_outside_collapses() {
+        let (world, mut system, quality) = setup();
+        click(&world, quality);
+        system.run_now(&world.res);
+
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                modifiers: ModifiersState::default(),
+            },
+        };
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_event(&event, &mut EventChannel::new(), 1.0);
+        system.run_now(&world.res);
+
+        let dropdowns = world.read_storage::<UiDropdown>();
+        assert!(!dropdowns.get(quality).unwrap().expanded);
+    }
+}
@@ -1,16 +1,18 @@
-use crate::transform::UiTransform;
+use crate::{
+    hit_shape::{hit, UiHitShape},
+    transform::UiTransform,
+    UiCursor,
+};
 use amethyst_core::{
     ecs::{
-        prelude::{
-            Component, Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, Write,
-        },
+        prelude::{Component, Entities, Entity, Join, Read, ReadStorage, System, Write},
         storage::NullStorage,
     },
     math::Vector2,
     shrev::EventChannel,
+    Hidden, HiddenPropagate,
 };
 use amethyst_input::{BindingTypes, InputHandler};
-use amethyst_window::ScreenDimensions;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 use winit::MouseButton;
@@ -43,8 +45,8 @@ pub enum UiEventType {
     },
     /// When stopping to drag a `Draggable` Ui element.
     Dropped {
-        /// The entity on which the dragged object was dropped.
-        dropped_on: Entity,
+        /// The interactable entity on which the dragged object was dropped, if any.
+        dropped_on: Option<Entity>,
     },
     /// When the value of a UiText element has been changed by user input.
     ValueChange,
@@ -54,9 +56,16 @@ pub enum UiEventType {
     Focus,
     /// When an editable UiText element has lost focus.
     Blur,
+    /// When a `UiTextReveal` has shown the whole text of its UiText element.
+    TextRevealed,
 }
 
 /// A ui event instance.
+///
+/// Ui events are written to the `EventChannel<UiEvent>` resource, mostly by the
+/// `UiMouseSystem`, which runs after the `UiTransformSystem`. There is no ordering
+/// relative to `ui_resize_system`, so hit-testing can see sizes from before a
+/// resize for one frame.
 #[derive(Debug, Clone)]
 pub struct UiEvent {
     /// The type of ui event.
@@ -88,6 +97,16 @@ impl Component for Interactable {
     type Storage = NullStorage<Interactable>;
 }
 
+/// The top-most ui element under the cursor, updated every frame by the `UiMouseSystem`.
+///
+/// Only opaque elements that aren't `Hidden` are hovered, whether they are `Interactable`
+/// or not. Read this instead of testing the cursor against every `UiTransform` again.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UiHovered {
+    /// The hovered element, `None` when the cursor is outside of the ui or the window.
+    pub entity: Option<Entity>,
+}
+
 /// The system that generates events for `Interactable` enabled entities.
 /// The generic types A and B represent the A and B generic parameter of the InputHandler<A,B>.
 pub struct UiMouseSystem<T: BindingTypes> {
@@ -113,15 +132,30 @@ impl<'a, T: BindingTypes> System<'a> for UiMouseSystem<T> {
     type SystemData = (
         Entities<'a>,
         ReadStorage<'a, UiTransform>,
+        ReadStorage<'a, UiHitShape>,
         ReadStorage<'a, Interactable>,
+        ReadStorage<'a, Hidden>,
+        ReadStorage<'a, HiddenPropagate>,
         Read<'a, InputHandler<T>>,
-        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiCursor>,
         Write<'a, EventChannel<UiEvent>>,
+        Write<'a, UiHovered>,
     );
 
     fn run(
         &mut self,
-        (entities, transform, react, input, screen_dimensions, mut events): Self::SystemData,
+        (
+            entities,
+            transform,
+            hit_shapes,
+            react,
+            hiddens,
+            hidden_propagates,
+            input,
+            cursor,
+            mut events,
+            mut hovered,
+        ): Self::SystemData,
     ) {
         let down = input.mouse_button_is_down(MouseButton::Left);
 
@@ -129,11 +163,29 @@ impl<'a, T: BindingTypes> System<'a> for UiMouseSystem<T> {
         let click_started = down && !self.was_down;
         let click_stopped = !down && self.was_down;
 
-        if let Some((pos_x, pos_y)) = input.mouse_position() {
-            let x = pos_x as f32;
-            let y = screen_dimensions.height() - pos_y as f32;
-
-            let target = targeted((x, y), (&*entities, &transform, react.maybe()).join());
+        hovered.entity = None;
+        if let Some((x, y)) = cursor.pixel_position {
+            // Hidden elements neither receive events nor block the ones below them, neither
+            // do positions outside of the hit shape of an element.
+            hovered.entity = (
+                &*entities,
+                &transform,
+                hit_shapes.maybe(),
+                !&hiddens,
+                !&hidden_propagates,
+            )
+                .join()
+                .filter(|(_, transform, shape, _, _)| {
+                    transform.opaque && hit(transform, *shape, (x, y))
+                })
+                .max_by(|(_, t1, _, _, _), (_, t2, _, _, _)| {
+                    t1.global_z
+                        .partial_cmp(&t2.global_z)
+                        .expect("Unexpected NaN")
+                })
+                .map(|(entity, _, _, _, _)| entity);
+            // Non-interactable elements block the ones below them.
+            let target = hovered.entity.filter(|entity| react.contains(*entity));
             if target != self.last_target {
                 if let Some(last_target) = self.last_target {
                     events.single_write(UiEvent::new(UiEventType::HoverStop, last_target));
@@ -187,3 +239,264 @@ where
         })
         .and_then(|(e, _, m)| m.map(|_m| e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Anchor, UiCursorSystem};
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use amethyst_input::StringBindings;
+    use amethyst_window::ScreenDimensions;
+    use winit::{
+        dpi::LogicalPosition, DeviceId, ElementState, Event, ModifiersState, WindowEvent, WindowId,
+    };
+
+    fn send_input(world: &World, event: WindowEvent) {
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_event(&event, &mut EventChannel::new(), 1.0);
+    }
+
+    fn move_cursor(world: &World, x: f64, y: f64) {
+        send_input(
+            world,
+            WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: LogicalPosition::new(x, y),
+                modifiers: ModifiersState::default(),
+            },
+        );
+        UiCursorSystem::<StringBindings>::new().run_now(&world.res);
+    }
+
+    fn left_mouse(world: &World, state: ElementState) {
+        send_input(
+            world,
+            WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state,
+                button: MouseButton::Left,
+                modifiers: ModifiersState::default(),
+            },
+        );
+    }
+
+    fn square(id: &str, z: f32) -> UiTransform {
+        UiTransform::new(
+            id.to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            100.0,
+            100.0,
+            z,
+            50.0,
+            50.0,
+        )
+    }
+
+    #[test]
+    fn ui_event_channel_round_trip() {
+        let mut world = World::new();
+        let target = world.create_entity().build();
+
+        let mut channel = EventChannel::<UiEvent>::new();
+        let mut reader = channel.register_reader();
+        channel.single_write(UiEvent::new(UiEventType::Click, target));
+        channel.single_write(UiEvent::new(UiEventType::ValueChange, target));
+
+        let events = channel
+            .read(&mut reader)
+            .map(|event| (event.event_type.clone(), event.get_target()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (UiEventType::Click, target),
+                (UiEventType::ValueChange, target),
+            ]
+        );
+        assert_eq!(channel.read(&mut reader).count(), 0);
+    }
+
+    #[test]
+    fn targeted_hits_topmost_interactable() {
+        let mut world = World::new();
+        let back = world.create_entity().build();
+        let front = world.create_entity().build();
+        let label = world.create_entity().build();
+
+        let back_tr = UiTransform::new(
+            "back".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            50.0,
+            50.0,
+            0.0,
+            100.0,
+            100.0,
+        );
+        let front_tr = UiTransform::new(
+            "front".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            75.0,
+            75.0,
+            1.0,
+            50.0,
+            50.0,
+        );
+        let label_tr = UiTransform::new(
+            "label".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            25.0,
+            25.0,
+            2.0,
+            10.0,
+            10.0,
+        );
+        let interactable = Interactable;
+        let items = vec![
+            (back, &back_tr, Some(&interactable)),
+            (front, &front_tr, Some(&interactable)),
+            (label, &label_tr, None),
+        ];
+
+        assert_eq!(
+            targeted((80.0, 80.0), items.clone().into_iter()),
+            Some(front)
+        );
+        assert_eq!(
+            targeted((10.0, 10.0), items.clone().into_iter()),
+            Some(back)
+        );
+        // Opaque elements that aren't interactable block the ones below them.
+        assert_eq!(targeted((25.0, 25.0), items.clone().into_iter()), None);
+        assert_eq!(targeted((150.0, 150.0), items.into_iter()), None);
+    }
+
+    #[test]
+    fn hidden_elements_are_not_hit() {
+        let mut world = World::new();
+        let mut system = UiMouseSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        System::setup(&mut UiCursorSystem::<StringBindings>::new(), &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let back = world
+            .create_entity()
+            .with(square("back", 0.0))
+            .with(Interactable)
+            .build();
+        world
+            .create_entity()
+            .with(square("front", 1.0))
+            .with(Interactable)
+            .with(Hidden)
+            .build();
+        world
+            .create_entity()
+            .with(square("blocker", 2.0))
+            .with(HiddenPropagate)
+            .build();
+
+        move_cursor(&world, 100.0, 100.0);
+        left_mouse(&world, ElementState::Pressed);
+        system.run_now(&world.res);
+        left_mouse(&world, ElementState::Released);
+        system.run_now(&world.res);
+
+        let events = world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .map(|event| (event.event_type.clone(), event.target))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (UiEventType::HoverStart, back),
+                (UiEventType::ClickStart, back),
+                (UiEventType::Click, back),
+                (UiEventType::ClickStop, back),
+            ]
+        );
+    }
+
+    #[test]
+    fn hovered_is_the_highest_element() {
+        let mut world = World::new();
+        let mut system = UiMouseSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        System::setup(&mut UiCursorSystem::<StringBindings>::new(), &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+
+        let back = world
+            .create_entity()
+            .with(square("back", 0.0))
+            .with(Interactable)
+            .build();
+        let front = world.create_entity().with(square("front", 1.0)).build();
+        let hovered = |world: &World| world.read_resource::<UiHovered>().entity;
+
+        system.run_now(&world.res);
+        assert_eq!(hovered(&world), None);
+
+        move_cursor(&world, 100.0, 100.0);
+        system.run_now(&world.res);
+        assert_eq!(hovered(&world), Some(front));
+
+        world
+            .write_storage::<Hidden>()
+            .insert(front, Hidden)
+            .unwrap();
+        system.run_now(&world.res);
+        assert_eq!(hovered(&world), Some(back));
+
+        move_cursor(&world, 10.0, 10.0);
+        system.run_now(&world.res);
+        assert_eq!(hovered(&world), None);
+    }
+
+    #[test]
+    fn round_button_ignores_clicks_in_corners() {
+        let mut world = World::new();
+        let mut system = UiMouseSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        System::setup(&mut UiCursorSystem::<StringBindings>::new(), &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let button = world
+            .create_entity()
+            .with(square("button", 0.0))
+            .with(UiHitShape::Circle { radius: 25.0 })
+            .with(Interactable)
+            .build();
+        let mut click_at = |x, y| {
+            move_cursor(&world, x, y);
+            left_mouse(&world, ElementState::Pressed);
+            system.run_now(&world.res);
+            left_mouse(&world, ElementState::Released);
+            system.run_now(&world.res);
+            world
+                .read_resource::<EventChannel<UiEvent>>()
+                .read(&mut reader)
+                .filter(|event| event.event_type == UiEventType::Click)
+                .map(|event| event.target)
+                .collect::<Vec<_>>()
+        };
+
+        // The corner is inside the square transform, but outside the circle.
+        assert_eq!(click_at(122.0, 78.0), vec![]);
+        assert_eq!(click_at(110.0, 95.0), vec![button]);
+    }
+}
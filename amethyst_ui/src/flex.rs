@@ -0,0 +1,215 @@
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Join, ReadExpect, ReadStorage, System, WriteStorage,
+    },
+    ParentHierarchy,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Anchor, ScaleMode, UiTransform};
+
+/// The axis the children of a `UiLayout` are packed along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayoutDirection {
+    /// Children are placed left to right.
+    Row,
+    /// Children are placed top to bottom.
+    Column,
+}
+
+/// How the space left along the main axis of a `UiLayout` is distributed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JustifyContent {
+    /// Children are packed at the start of the layout.
+    Start,
+    /// Children are packed in the middle of the layout.
+    Center,
+    /// Children are packed at the end of the layout.
+    End,
+    /// The first and last children are at the edges, the space left is shared between them.
+    SpaceBetween,
+}
+
+/// Positions the children of the entity one after the other, like a flexbox.
+///
+/// The `LayoutSystem` moves every child with a `UiTransform`, in the order they were parented,
+/// overriding their anchor, pivot and position. Children are aligned to the top of rows and to
+/// the left of columns. Children sized in `ScaleMode::Percent` are sized against the content
+/// box of the layout, inside the padding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UiLayout {
+    /// The axis children are packed along.
+    pub direction: LayoutDirection,
+    /// Space between two children, in pixels.
+    pub spacing: f32,
+    /// Space between the edges of the layout and its children, in pixels, in the order
+    /// top, right, bottom, left.
+    pub padding: [f32; 4],
+    /// How the space left is distributed.
+    pub justify: JustifyContent,
+}
+
+impl UiLayout {
+    /// Creates a new layout.
+    pub fn new(
+        direction: LayoutDirection,
+        spacing: f32,
+        padding: [f32; 4],
+        justify: JustifyContent,
+    ) -> Self {
+        UiLayout {
+            direction,
+            spacing,
+            padding,
+            justify,
+        }
+    }
+
+    /// The size of the area children are packed in, for a layout of the given size.
+    pub fn content_size(&self, width: f32, height: f32) -> (f32, f32) {
+        let [top, right, bottom, left] = self.padding;
+        (
+            (width - left - right).max(0.0),
+            (height - top - bottom).max(0.0),
+        )
+    }
+
+    /// The offsets of the top left corners of children of the given sizes from the top left
+    /// corner of a layout of `size`, with y growing downwards.
+    fn pack(&self, size: (f32, f32), children: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        let content = self.content_size(size.0, size.1);
+        let main = |(width, height): (f32, f32)| match self.direction {
+            LayoutDirection::Row => width,
+            LayoutDirection::Column => height,
+        };
+        let used = children.iter().cloned().map(main).sum::<f32>()
+            + self.spacing * children.len().saturating_sub(1) as f32;
+        let free = (main(content) - used).max(0.0);
+        let (mut offset, spacing) = match self.justify {
+            JustifyContent::Start => (0.0, self.spacing),
+            JustifyContent::Center => (free * 0.5, self.spacing),
+            JustifyContent::End => (free, self.spacing),
+            JustifyContent::SpaceBetween if children.len() > 1 => {
+                (0.0, self.spacing + free / (children.len() - 1) as f32)
+            }
+            JustifyContent::SpaceBetween => (0.0, self.spacing),
+        };
+
+        let [top, _, _, left] = self.padding;
+        children
+            .iter()
+            .map(|child| {
+                let position = match self.direction {
+                    LayoutDirection::Row => (left + offset, top),
+                    LayoutDirection::Column => (left, top + offset),
+                };
+                offset += main(*child) + spacing;
+                position
+            })
+            .collect()
+    }
+}
+
+impl Component for UiLayout {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Packs the children of every `UiLayout`.
+///
+/// It runs before the `UiTransformSystem`, from the size the layout had on the last frame.
+#[derive(Debug, Default)]
+pub struct LayoutSystem;
+
+impl LayoutSystem {
+    /// Creates a new LayoutSystem.
+    pub fn new() -> Self {
+        LayoutSystem
+    }
+}
+
+impl<'a> System<'a> for LayoutSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, UiLayout>,
+        WriteStorage<'a, UiTransform>,
+        ReadExpect<'a, ParentHierarchy>,
+    );
+
+    fn run(&mut self, (entities, layouts, mut transforms, hierarchy): Self::SystemData) {
+        for (entity, layout) in (&*entities, &layouts).join() {
+            let (width, height) = match transforms.get(entity) {
+                Some(t) => (t.pixel_width, t.pixel_height),
+                None => continue,
+            };
+            let content = layout.content_size(width, height);
+            let children = hierarchy
+                .children(entity)
+                .iter()
+                .filter_map(|child| transforms.get(*child).map(|t| (*child, t)))
+                .map(|(child, t)| {
+                    let size = match t.scale_mode {
+                        ScaleMode::Pixel => (t.width, t.height),
+                        ScaleMode::Percent => (t.width * content.0, t.height * content.1),
+                    };
+                    (child, size)
+                })
+                .collect::<Vec<_>>();
+            let sizes = children.iter().map(|(_, size)| *size).collect::<Vec<_>>();
+
+            for ((child, _), (x, y)) in children.iter().zip(layout.pack((width, height), &sizes)) {
+                let transform = transforms
+                    .get(*child)
+                    .expect("unreachable: We only kept children with a transform");
+                let (x, y) = match transform.scale_mode {
+                    ScaleMode::Pixel => (x, -y),
+                    ScaleMode::Percent if width > 0.0 && height > 0.0 => (x / width, -y / height),
+                    ScaleMode::Percent => (0.0, 0.0),
+                };
+                // Only touch the children when they moved, so the layout isn't redone every
+                // frame.
+                let placed = transform.anchor == Anchor::TopLeft
+                    && transform.pivot == Anchor::TopLeft
+                    && (transform.local_x, transform.local_y) == (x, y);
+                if !placed {
+                    let transform = transforms
+                        .get_mut(*child)
+                        .expect("unreachable: We just read the transform");
+                    transform.anchor = Anchor::TopLeft;
+                    transform.pivot = Anchor::TopLeft;
+                    transform.local_x = x;
+                    transform.local_y = y;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_space_between_reaches_both_edges() {
+        let layout = UiLayout::new(
+            LayoutDirection::Row,
+            10.0,
+            [0.0; 4],
+            JustifyContent::SpaceBetween,
+        );
+        let positions = layout.pack((300.0, 50.0), &[(50.0, 20.0), (60.0, 30.0), (40.0, 50.0)]);
+        assert_eq!(positions, vec![(0.0, 0.0), (125.0, 0.0), (260.0, 0.0)]);
+    }
+
+    #[test]
+    fn column_honors_padding_and_spacing() {
+        let layout = UiLayout::new(
+            LayoutDirection::Column,
+            5.0,
+            [10.0, 4.0, 6.0, 8.0],
+            JustifyContent::End,
+        );
+        assert_eq!(layout.content_size(100.0, 116.0), (88.0, 100.0));
+        let positions = layout.pack((100.0, 116.0), &[(20.0, 30.0), (20.0, 40.0)]);
+        assert_eq!(positions, vec![(8.0, 35.0), (8.0, 70.0)]);
+    }
+}
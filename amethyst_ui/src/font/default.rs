@@ -3,7 +3,7 @@ use std::fs;
 use font_kit::handle::Handle as FontKitHandle;
 use log::{error, warn};
 
-use amethyst_assets::{AssetStorage, Format, Loader};
+use amethyst_assets::{AssetStorage, Format, Loader, Progress};
 
 use crate::{
     font::systemfont::default_system_font,
@@ -13,6 +13,15 @@ use crate::{
 /// Get the system default fonts.
 /// If unable to, gets the local square.ttf font.
 pub fn get_default_font(loader: &Loader, storage: &AssetStorage<FontAsset>) -> FontHandle {
+    load_default_font(loader, (), storage)
+}
+
+/// Loads the system default font like `get_default_font`, tracking the load with `progress`.
+pub(crate) fn load_default_font<P: Progress>(
+    loader: &Loader,
+    progress: P,
+    storage: &AssetStorage<FontAsset>,
+) -> FontHandle {
     let system_font = default_system_font();
 
     match system_font {
@@ -33,7 +42,7 @@ pub fn get_default_font(loader: &Loader, storage: &AssetStorage<FontAsset>) -> F
                     if let Some(format) = format {
                         match fs::read(&path) {
                             Ok(bytes) => match format.import_simple(bytes) {
-                                Ok(data) => return loader.load_from_data(data, (), storage),
+                                Ok(data) => return loader.load_from_data(data, progress, storage),
                                 Err(err) => warn!("System font at '{}' cannot be loaded. Fallback to default. Error: {}", path.display(), err),
                             },
                             Err(err) => warn!("System font at '{}' is not available for use. Fallback to default. Error: {}", path.display(), err)
@@ -48,7 +57,7 @@ pub fn get_default_font(loader: &Loader, storage: &AssetStorage<FontAsset>) -> F
             FontKitHandle::Memory { bytes, .. } => {
                 let font_data = TtfFormat.import_simple(bytes.to_vec());
                 match font_data {
-                    Ok(data) => return loader.load_from_data(data, (), storage),
+                    Ok(data) => return loader.load_from_data(data, progress, storage),
                     Err(e) => warn!("Failed to load default system font from bytes. Falling back to built-in.\nError: {:?}", e),
                 }
             }
@@ -63,7 +72,7 @@ pub fn get_default_font(loader: &Loader, storage: &AssetStorage<FontAsset>) -> F
         TtfFormat
             .import_simple(include_bytes!("./square.ttf").to_vec())
             .expect("Unable to import fallback font './square.ttf'"),
-        (),
+        progress,
         storage,
     )
 }
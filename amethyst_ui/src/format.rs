@@ -1,17 +1,117 @@
-use glyph_brush::rusttype::Font;
+use glyph_brush::rusttype::{Font, Scale};
 use serde::{Deserialize, Serialize};
 
-use amethyst_assets::{Asset, Format, Handle, ProcessableAsset, ProcessingState};
-use amethyst_core::ecs::prelude::VecStorage;
+use amethyst_assets::{
+    Asset, AssetStorage, Format, Handle, Loader, ProcessableAsset, ProcessingState, Progress,
+};
+use amethyst_core::ecs::prelude::{Read, ReadExpect, VecStorage};
 use amethyst_error::{format_err, Error, ResultExt};
+use shred_derive::SystemData;
+
+use crate::font::default::load_default_font;
 
 /// A loaded set of fonts from a file.
 #[derive(Clone)]
 pub struct FontAsset(pub Font<'static>);
 
+impl FontAsset {
+    /// Parses a TrueType font from memory, for example one embedded with `include_bytes!`.
+    ///
+    /// The resulting asset can be inserted into an `AssetStorage<FontAsset>` directly,
+    /// without going through the loader.
+    pub fn from_bytes(data: Vec<u8>) -> Result<FontAsset, Error> {
+        Font::from_bytes(data)
+            .map(FontAsset)
+            .with_context(|_| format_err!("Font parsing error"))
+    }
+
+    /// Measures `text` rendered at `size` pixels, without wrapping.
+    ///
+    /// Advances include the font's kerning pairs, as in the layout done by the
+    /// `UiGlyphsSystem`. An empty string is one line with zero width, and a trailing
+    /// newline starts an extra, empty line.
+    pub fn measure(&self, text: &str, size: f32) -> TextMetrics {
+        let scale = Scale::uniform(size);
+        let v_metrics = self.0.v_metrics(scale);
+        let mut line_count = 0;
+        let width = text
+            .split('\n')
+            .map(|line| {
+                line_count += 1;
+                let mut previous = None;
+                line.chars()
+                    .map(|c| {
+                        let glyph = self.0.glyph(c).scaled(scale);
+                        let kerning = previous
+                            .map(|previous| self.0.pair_kerning(scale, previous, glyph.id()))
+                            .unwrap_or(0.0);
+                        previous = Some(glyph.id());
+                        kerning + glyph.h_metrics().advance_width
+                    })
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max);
+
+        TextMetrics {
+            width,
+            ascent: v_metrics.ascent,
+            descent: v_metrics.descent,
+            line_count,
+        }
+    }
+}
+
+/// The size of a laid out piece of text, as returned by `FontAsset::measure`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextMetrics {
+    /// Width of the widest line, in pixels.
+    pub width: f32,
+    /// Distance from the baseline to the top of the tallest glyph, in pixels.
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest glyph, in pixels.
+    /// This is negative, as it is below the baseline.
+    pub descent: f32,
+    /// Number of lines, counting hard line breaks only.
+    pub line_count: usize,
+}
+
 /// A handle to font data stored with `amethyst_assets`.
 pub type FontHandle = Handle<FontAsset>;
 
+/// Wrapper around the `Loader` for loading TrueType fonts.
+///
+/// Pass a `&mut ProgressCounter` as `progress` to poll the loads from a loading screen, with
+/// `num_loading`, `num_finished` and `errors`. Fonts count as finished once the font processor
+/// added them to the `AssetStorage<FontAsset>`.
+///
+/// ### Example:
+///
+/// ```rust,ignore
+/// let mut progress = ProgressCounter::new();
+/// let font = world.exec(|fonts: UiFontLoader<'_>| fonts.load("font/square.ttf", &mut progress));
+/// ```
+#[derive(SystemData)]
+pub struct UiFontLoader<'a> {
+    loader: ReadExpect<'a, Loader>,
+    storage: Read<'a, AssetStorage<FontAsset>>,
+}
+
+impl<'a> UiFontLoader<'a> {
+    /// Load a font file from disc.
+    pub fn load<N, P>(&self, name: N, progress: P) -> FontHandle
+    where
+        N: Into<String>,
+        P: Progress,
+    {
+        self.loader.load(name, TtfFormat, progress, &self.storage)
+    }
+
+    /// Load the system default font, or the built-in square.ttf if it isn't available.
+    pub fn load_default<P: Progress>(&self, progress: P) -> FontHandle {
+        load_default_font(&self.loader, progress, &self.storage)
+    }
+}
+
 #[derive(Clone)]
 pub struct FontData(Font<'static>);
 
@@ -44,8 +144,106 @@ impl Format<FontData> for TtfFormat {
     }
 
     fn import_simple(&self, bytes: Vec<u8>) -> Result<FontData, Error> {
-        Font::from_bytes(bytes)
-            .map(FontData)
-            .with_context(|_| format_err!("Font parsing error"))
+        FontAsset::from_bytes(bytes).map(|font| FontData(font.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_assets::ProgressCounter;
+    use amethyst_core::ecs::prelude::World;
+    use rayon::ThreadPoolBuilder;
+    use std::{
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn font_from_embedded_bytes() {
+        let font = FontAsset::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let advance = font
+            .0
+            .glyph('a')
+            .scaled(Scale::uniform(16.0))
+            .h_metrics()
+            .advance_width;
+        assert!(advance > 0.0);
+    }
+
+    #[test]
+    fn measure_matches_glyph_advances() {
+        let font = FontAsset::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let advances = "Hello"
+            .chars()
+            .map(|c| {
+                font.0
+                    .glyph(c)
+                    .scaled(Scale::uniform(16.0))
+                    .h_metrics()
+                    .advance_width
+            })
+            .sum::<f32>();
+
+        let metrics = font.measure("Hello", 16.0);
+        assert!((metrics.width - advances).abs() < 0.001);
+        assert_eq!(metrics.line_count, 1);
+        assert!(metrics.ascent > 0.0);
+        assert!(metrics.descent <= 0.0);
+
+        let metrics = font.measure("Hello\nHi", 16.0);
+        assert!((metrics.width - advances).abs() < 0.001);
+        assert_eq!(metrics.line_count, 2);
+
+        assert_eq!(font.measure("Hi\n", 16.0).line_count, 2);
+        let empty = font.measure("", 16.0);
+        assert_eq!((empty.width, empty.line_count), (0.0, 1));
+    }
+
+    #[test]
+    fn load_progress_reports_failed_fonts() {
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        world.add_resource(Loader::new(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/font"),
+            pool.clone(),
+        ));
+        world.add_resource(AssetStorage::<FontAsset>::new());
+
+        let mut progress = ProgressCounter::new();
+        world.exec(|fonts: UiFontLoader<'_>| {
+            fonts.load("square.ttf", &mut progress);
+            fonts.load("missing.ttf", &mut progress);
+        });
+        assert_eq!(progress.num_assets(), 2);
+
+        let start = Instant::now();
+        while progress.num_loading() > 0 {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "Fonts never loaded"
+            );
+            world.write_resource::<AssetStorage<FontAsset>>().process(
+                <FontAsset as ProcessableAsset>::process,
+                0,
+                &pool,
+                None,
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(progress.num_finished(), 1);
+        assert_eq!(progress.num_failed(), 1);
+        let errors = progress.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].asset_name, "missing.ttf");
+    }
+
+    #[test]
+    fn font_from_invalid_bytes() {
+        assert!(FontAsset::from_bytes(b"not a font".to_vec()).is_err());
     }
 }
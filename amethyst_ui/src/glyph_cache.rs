@@ -0,0 +1,557 @@
+//! Cache of the rasterized glyphs of the UI, packed into the glyph texture.
+
+use glyph_brush::{
+    rusttype::{point, Rect, Scale},
+    FontId,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+/// Space left between glyphs in the texture, so sampling at the edge of a glyph doesn't
+/// pick up its neighbours.
+const PADDING: u32 = 1;
+
+/// How many subpixel offsets glyphs are rasterized at per pixel, in each direction. Glyphs
+/// are drawn at most half a step away from where they were laid out.
+pub(crate) const SUBPIXEL_STEPS: u8 = 10;
+
+/// Identifies a rasterized glyph: a character of one font at one size, with its origin at
+/// one subpixel offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct GlyphKey {
+    font: FontId,
+    c: char,
+    /// The bits of the height of the glyph scale.
+    size: u32,
+    /// The offset of the origin from the top left of its pixel, in `1 / SUBPIXEL_STEPS`
+    /// pixels.
+    subpixel: (u8, u8),
+}
+
+impl GlyphKey {
+    pub(crate) fn new(font: FontId, c: char, scale: Scale, subpixel: (u8, u8)) -> Self {
+        GlyphKey {
+            font,
+            c,
+            size: scale.y.to_bits(),
+            subpixel,
+        }
+    }
+
+    /// The offset of the origin from the top left of its pixel, in pixels.
+    pub(crate) fn subpixel_offset(&self) -> (f32, f32) {
+        let step = 1.0 / f32::from(SUBPIXEL_STEPS);
+        (
+            f32::from(self.subpixel.0) * step,
+            f32::from(self.subpixel.1) * step,
+        )
+    }
+}
+
+/// The coverage of a glyph rasterized with its origin at the subpixel offset of its key.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Raster {
+    /// Position of the top left pixel relative to the pixel holding the origin of the glyph.
+    pub(crate) offset: (i32, i32),
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// One byte per pixel, row by row.
+    pub(crate) coverage: Vec<u8>,
+}
+
+/// Where a cached glyph is drawn from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CachedRaster {
+    /// Position of the top left pixel relative to the pixel holding the origin of the glyph.
+    pub(crate) offset: (i32, i32),
+    /// The pixels of the glyph in the texture.
+    pub(crate) tex_rect: Rect<u32>,
+}
+
+/// How the glyph texture has to be updated.
+#[derive(Debug, PartialEq)]
+pub(crate) enum TextureUpdate {
+    /// Glyphs were added, their coverage is copied to these pixels.
+    Glyphs(Vec<(Rect<u32>, Vec<u8>)>),
+    /// Glyphs were moved or the texture grew, it is replaced by the whole `GlyphCache::image`.
+    All,
+}
+
+#[derive(Debug)]
+struct Entry {
+    raster: Raster,
+    /// The top left pixel in the texture.
+    position: (u32, u32),
+    /// The frame the glyph was last used on.
+    last_used: u64,
+    /// The key of the glyph in `GlyphCache::lru`.
+    use_order: u64,
+}
+
+impl Entry {
+    fn tex_rect(&self) -> Rect<u32> {
+        let (x, y) = self.position;
+        Rect {
+            min: point(x, y),
+            max: point(x + self.raster.width, y + self.raster.height),
+        }
+    }
+
+    /// The texture area taken by the glyph, padding included.
+    fn area(&self) -> u32 {
+        (self.raster.width + PADDING) * (self.raster.height + PADDING)
+    }
+}
+
+/// Packs rectangles into rows of a texture. Each row is as high as the first rectangle put
+/// in it.
+#[derive(Debug)]
+struct Shelves {
+    size: (u32, u32),
+    /// The top, height and used width of every row.
+    rows: Vec<(u32, u32, u32)>,
+}
+
+impl Shelves {
+    fn new(size: (u32, u32)) -> Self {
+        Shelves {
+            size,
+            rows: Vec::new(),
+        }
+    }
+
+    /// The top left pixel of a free `width` by `height` rectangle, `None` when the texture
+    /// has no room left for it.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + PADDING, height + PADDING);
+        let texture_width = self.size.0;
+        // The lowest row the rectangle fits in wastes the least space.
+        let row = self
+            .rows
+            .iter_mut()
+            .filter(|(_, row_height, used)| *row_height >= height && used + width <= texture_width)
+            .min_by_key(|(_, row_height, _)| *row_height);
+        if let Some((top, _, used)) = row {
+            let left = *used;
+            *used += width;
+            return Some((left, *top));
+        }
+        let top = self.rows.last().map_or(0, |(top, height, _)| top + height);
+        if width > texture_width || top + height > self.size.1 {
+            return None;
+        }
+        self.rows.push((top, height, width));
+        Some((0, top))
+    }
+}
+
+/// The size of the texture grown once, `None` when it is already `max` in both directions.
+fn grow_size(current: (u32, u32), max: (u32, u32)) -> Option<(u32, u32)> {
+    let size = (
+        (current.0 * 2).min(max.0).max(current.0),
+        (current.1 * 2).min(max.1).max(current.1),
+    );
+    if size != current {
+        Some(size)
+    } else {
+        None
+    }
+}
+
+/// Least recently used cache of rasterized glyphs, keyed by font, character and size, and
+/// packed into a texture.
+///
+/// When more than `capacity` glyphs are cached, or when the texture is full, the glyphs used
+/// least recently are evicted, and the remaining ones are packed again to reclaim the space.
+/// Evicted glyphs are rasterized again on their next use. Glyphs used on the current frame
+/// are never evicted, so the texture only grows, up to `max_size`, when the glyphs of a
+/// single frame don't fit.
+#[derive(Debug)]
+pub(crate) struct GlyphCache {
+    entries: HashMap<GlyphKey, Entry>,
+    /// The cached glyphs in the order they were last used in, least recently used first.
+    lru: BTreeMap<u64, GlyphKey>,
+    /// Increased whenever a glyph is used on a new frame, orders `lru`.
+    uses: u64,
+    shelves: Shelves,
+    max_size: (u32, u32),
+    capacity: usize,
+    frame: u64,
+    /// Glyphs added since the texture was last updated.
+    added: Vec<GlyphKey>,
+    /// Whether glyphs moved since the texture was last updated.
+    moved: bool,
+}
+
+impl GlyphCache {
+    pub(crate) fn new(size: (u32, u32), max_size: (u32, u32), capacity: usize) -> Self {
+        GlyphCache {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            uses: 0,
+            shelves: Shelves::new(size),
+            max_size,
+            capacity,
+            frame: 0,
+            added: Vec::new(),
+            moved: false,
+        }
+    }
+
+    pub(crate) fn set_max_size(&mut self, max_size: (u32, u32)) {
+        self.max_size = max_size;
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+    }
+
+    /// The size of the texture in pixels.
+    pub(crate) fn size(&self) -> (u32, u32) {
+        self.shelves.size
+    }
+
+    /// The number of cached glyphs.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Starts a new frame, the glyphs used until now may be evicted again.
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Marks the glyph as used on this frame, rasterizing it with `rasterize` when it isn't
+    /// cached. Returns false when it doesn't fit, even in a texture of `max_size` holding
+    /// nothing but the glyphs of this frame.
+    ///
+    /// Adding glyphs can move the others, so they are looked up with `get` once all glyphs of
+    /// the frame are added.
+    pub(crate) fn insert(&mut self, key: GlyphKey, rasterize: impl FnOnce() -> Raster) -> bool {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            // Glyphs used again on the same frame are already last in `lru`, behind every
+            // glyph that isn't used on this frame.
+            if entry.last_used != self.frame {
+                self.lru.remove(&entry.use_order);
+                self.uses += 1;
+                self.lru.insert(self.uses, key);
+                entry.use_order = self.uses;
+                entry.last_used = self.frame;
+            }
+            return true;
+        }
+        let raster = rasterize();
+        while self.entries.len() >= self.capacity && self.evict_least_recent().is_some() {}
+        let position = match self.shelves.allocate(raster.width, raster.height) {
+            Some(position) => position,
+            None => match self.make_room(raster.width, raster.height) {
+                Some(position) => position,
+                None => return false,
+            },
+        };
+        self.uses += 1;
+        self.lru.insert(self.uses, key);
+        self.entries.insert(
+            key,
+            Entry {
+                raster,
+                position,
+                last_used: self.frame,
+                use_order: self.uses,
+            },
+        );
+        self.added.push(key);
+        true
+    }
+
+    pub(crate) fn get(&self, key: &GlyphKey) -> Option<CachedRaster> {
+        self.entries.get(key).map(|entry| CachedRaster {
+            offset: entry.raster.offset,
+            tex_rect: entry.tex_rect(),
+        })
+    }
+
+    /// How the texture changed since the last call, `None` when it didn't.
+    pub(crate) fn take_update(&mut self) -> Option<TextureUpdate> {
+        if self.moved {
+            self.moved = false;
+            self.added.clear();
+            return Some(TextureUpdate::All);
+        }
+        if self.added.is_empty() {
+            return None;
+        }
+        let entries = &self.entries;
+        let glyphs = self
+            .added
+            .drain(..)
+            .filter_map(|key| entries.get(&key))
+            .map(|entry| (entry.tex_rect(), entry.raster.coverage.clone()))
+            .collect();
+        Some(TextureUpdate::Glyphs(glyphs))
+    }
+
+    /// The whole texture, one byte of coverage per pixel, row by row.
+    pub(crate) fn image(&self) -> Vec<u8> {
+        let (width, height) = self.size();
+        let mut image = vec![0; (width * height) as usize];
+        for entry in self.entries.values() {
+            let (left, top) = entry.position;
+            let raster = &entry.raster;
+            for (y, row) in raster
+                .coverage
+                .chunks(raster.width.max(1) as usize)
+                .enumerate()
+            {
+                let start = ((top + y as u32) * width + left) as usize;
+                image[start..start + row.len()].copy_from_slice(row);
+            }
+        }
+        image
+    }
+
+    /// Evicts the glyph used least recently, unless it was used on this frame. Returns the
+    /// texture area it took.
+    fn evict_least_recent(&mut self) -> Option<u32> {
+        let (&use_order, key) = self.lru.iter().next()?;
+        // When the least recently used glyph was used on this frame, all of them were.
+        if self.entries[key].last_used == self.frame {
+            return None;
+        }
+        let key = self.lru.remove(&use_order)?;
+        self.entries.remove(&key).map(|entry| entry.area())
+    }
+
+    /// Evicts glyphs and packs the others again until a `width` by `height` glyph fits,
+    /// growing the texture once only glyphs of this frame are left. Returns where the glyph
+    /// goes, `None` when it doesn't fit in a texture of `max_size`.
+    fn make_room(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let needed = (width + PADDING) * (height + PADDING);
+        loop {
+            // At least the area of the glyph is evicted at once, so the texture isn't packed
+            // again for every evicted glyph.
+            let mut freed = 0;
+            while freed < needed {
+                match self.evict_least_recent() {
+                    Some(area) => freed += area,
+                    None => break,
+                }
+            }
+            let size = if freed > 0 {
+                self.size()
+            } else {
+                grow_size(self.size(), self.max_size)?
+            };
+            self.repack(size);
+            if let Some(position) = self.shelves.allocate(width, height) {
+                return Some(position);
+            }
+        }
+    }
+
+    /// Packs the cached glyphs into a `size` texture again, tallest first. Glyphs that don't
+    /// fit anymore are evicted.
+    fn repack(&mut self, size: (u32, u32)) {
+        self.shelves = Shelves::new(size);
+        let mut keys = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (*key, entry.raster.width, entry.raster.height))
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|&(_, width, height)| (Reverse(height), Reverse(width)));
+        for (key, width, height) in keys {
+            match self.shelves.allocate(width, height) {
+                Some(position) => {
+                    if let Some(entry) = self.entries.get_mut(&key) {
+                        entry.position = position;
+                    }
+                }
+                None => {
+                    if let Some(entry) = self.entries.remove(&key) {
+                        self.lru.remove(&entry.use_order);
+                    }
+                }
+            }
+        }
+        self.added.clear();
+        self.moved = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glyph_brush::rusttype::Font;
+    use std::cell::Cell;
+
+    const FONT_SIZE: f32 = 24.0;
+
+    fn rasterize(font: &Font<'static>, c: char) -> Raster {
+        let glyph = font
+            .glyph(c)
+            .scaled(Scale::uniform(FONT_SIZE))
+            .positioned(point(0.0, 0.0));
+        let bounds = glyph.pixel_bounding_box().unwrap();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let mut coverage = vec![0; (width * height) as usize];
+        glyph.draw(|x, y, v| coverage[(y * width + x) as usize] = (v * 255.0) as u8);
+        // Every character of square.ttf is the same square, tell them apart in the texture.
+        coverage[0] = c as u8;
+        Raster {
+            offset: (bounds.min.x, bounds.min.y),
+            width,
+            height,
+            coverage,
+        }
+    }
+
+    /// A copy of the glyph texture, kept up to date like the one drawn from.
+    struct Texture {
+        size: (u32, u32),
+        pixels: Vec<u8>,
+    }
+
+    impl Texture {
+        fn update(&mut self, cache: &mut GlyphCache) {
+            match cache.take_update() {
+                Some(TextureUpdate::All) => {
+                    self.size = cache.size();
+                    self.pixels = cache.image();
+                }
+                Some(TextureUpdate::Glyphs(glyphs)) => {
+                    for (rect, coverage) in glyphs {
+                        for (y, row) in coverage.chunks(rect.width() as usize).enumerate() {
+                            let start =
+                                ((rect.min.y + y as u32) * self.size.0 + rect.min.x) as usize;
+                            self.pixels[start..start + row.len()].copy_from_slice(row);
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+
+        /// The coverage of the glyph as drawn from the texture.
+        fn glyph(&self, cache: &GlyphCache, key: &GlyphKey) -> Vec<u8> {
+            let rect = cache.get(key).unwrap().tex_rect;
+            (rect.min.y..rect.max.y)
+                .flat_map(|y| {
+                    let start = (y * self.size.0) as usize;
+                    self.pixels[start + rect.min.x as usize..start + rect.max.x as usize].to_vec()
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn evicted_glyphs_are_rasterized_again() {
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let capacity = 8;
+        let mut cache = GlyphCache::new((256, 256), (256, 256), capacity);
+        let mut texture = Texture {
+            size: cache.size(),
+            pixels: vec![0; 256 * 256],
+        };
+        let key = |c| GlyphKey::new(FontId(0), c, Scale::uniform(FONT_SIZE), (0, 0));
+        let rasterized = Cell::new(0);
+        let frame = |cache: &mut GlyphCache, texture: &mut Texture, text: &str| {
+            cache.next_frame();
+            for c in text.chars() {
+                assert!(cache.insert(key(c), || {
+                    rasterized.set(rasterized.get() + 1);
+                    rasterize(&font, c)
+                }));
+            }
+            texture.update(cache);
+            // Every glyph of the frame is drawn as it was rasterized.
+            for c in text.chars() {
+                assert_eq!(texture.glyph(cache, &key(c)), rasterize(&font, c).coverage);
+            }
+        };
+
+        // A scrolling log uses many more glyphs than the cache holds.
+        for line in &["abcd", "efgh", "ijkl", "mnop", "qrst", "uvwx"] {
+            frame(&mut cache, &mut texture, line);
+            assert!(cache.len() <= capacity);
+            assert_eq!(cache.lru.len(), cache.len());
+        }
+        assert_eq!(rasterized.get(), 24);
+        assert_eq!(cache.get(&key('a')), None);
+        assert!(cache.get(&key('x')).is_some());
+
+        // The first line comes back and is rasterized again.
+        frame(&mut cache, &mut texture, "abcd");
+        assert_eq!(rasterized.get(), 28);
+        assert!(cache.len() <= capacity);
+        // Cached glyphs aren't rasterized again.
+        frame(&mut cache, &mut texture, "abcdwx");
+        assert_eq!(rasterized.get(), 28);
+    }
+
+    #[test]
+    fn full_texture_is_packed_again() {
+        let raster = |size| Raster {
+            offset: (0, 0),
+            width: size,
+            height: size,
+            coverage: vec![255; (size * size) as usize],
+        };
+        let key = |c| GlyphKey::new(FontId(0), c, Scale::uniform(FONT_SIZE), (0, 0));
+        // Room for four 7x7 glyphs and their padding.
+        let mut cache = GlyphCache::new((16, 16), (16, 16), 100);
+        for c in "abcd".chars() {
+            cache.next_frame();
+            assert!(cache.insert(key(c), || raster(7)));
+        }
+        assert!(cache.take_update().is_some());
+
+        cache.next_frame();
+        assert!(cache.insert(key('b'), || raster(7)));
+        assert!(cache.insert(key('e'), || raster(7)));
+        // "a" was used least recently, the others were packed again around "e".
+        assert_eq!(cache.get(&key('a')), None);
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.take_update(), Some(TextureUpdate::All));
+        let mut rects = "bcde"
+            .chars()
+            .map(|c| cache.get(&key(c)).unwrap().tex_rect.min)
+            .map(|min| (min.x, min.y))
+            .collect::<Vec<_>>();
+        rects.sort();
+        rects.dedup();
+        assert_eq!(rects.len(), 4);
+    }
+
+    #[test]
+    fn texture_grows_for_the_glyphs_of_one_frame() {
+        let raster = Raster {
+            offset: (0, 0),
+            width: 7,
+            height: 7,
+            coverage: vec![255; 49],
+        };
+        let key = |c| GlyphKey::new(FontId(0), c, Scale::uniform(FONT_SIZE), (0, 0));
+        let mut cache = GlyphCache::new((16, 16), (32, 16), 100);
+        cache.next_frame();
+        for c in "abcdefgh".chars() {
+            assert!(cache.insert(key(c), || raster.clone()));
+        }
+        assert_eq!(cache.size(), (32, 16));
+        assert_eq!(cache.len(), 8);
+        // Nothing can be evicted on this frame and the texture can't grow anymore.
+        assert!(!cache.insert(key('i'), || raster.clone()));
+        assert_eq!(cache.len(), 8);
+    }
+
+    #[test]
+    fn growth_is_bounded() {
+        let max = (1024, 1024);
+        assert_eq!(grow_size((512, 512), max), Some((1024, 1024)));
+        assert_eq!(grow_size((1024, 512), max), Some((1024, 1024)));
+        assert_eq!(grow_size((1024, 1024), max), None);
+        assert_eq!(grow_size((512, 512), (768, 4096)), Some((768, 1024)));
+    }
+}
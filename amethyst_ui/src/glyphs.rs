@@ -1,14 +1,18 @@
-//! Module containing the system managing glyphbrush state for visible UI Text components.
+//! Module containing the system laying out and rasterizing the glyphs of visible UI Text
+//! components.
 
 use crate::{
-    pass::UiArgs, text::CachedGlyph, FontAsset, LineMode, Selected, TextEditing, UiText,
-    UiTransform,
+    glyph_cache::{CachedRaster, GlyphCache, GlyphKey, Raster, TextureUpdate, SUBPIXEL_STEPS},
+    pass::{UiArgs, UiDirty, UiRenderStats},
+    text::CachedGlyph,
+    Anchor, FontAsset, FontHandle, Justify, LineMode, Overflow, RasterMode, Selected, TabWidth,
+    TextBaseline, TextEditing, UiScale, UiText, UiTransform,
 };
 use amethyst_assets::{AssetStorage, Handle};
 use amethyst_core::{
     ecs::{
-        Component, DenseVecStorage, Entities, Join, Read, ReadStorage, Resources, System,
-        SystemData, Write, WriteExpect, WriteStorage,
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage,
+        Resources, System, SystemData, Write, WriteExpect, WriteStorage,
     },
     Hidden, HiddenPropagate,
 };
@@ -22,11 +26,20 @@ use amethyst_rendy::{
     resources::Tint,
     Backend, Texture,
 };
+use amethyst_window::ScreenDimensions;
 use glyph_brush::{
-    rusttype::Scale, BrushAction, BrushError, BuiltInLineBreaker, FontId, GlyphBrush,
-    GlyphBrushBuilder, GlyphCruncher, Layout, LineBreak, LineBreaker, SectionText, VariedSection,
+    rusttype::{point, Font, GlyphId, Point, PositionedGlyph, Rect, Scale, VMetrics},
+    BuiltInLineBreaker, FontId, FontMap, GlyphPositioner, HorizontalAlign, Layout, LineBreak,
+    LineBreaker, SectionGeometry, SectionText, VariedSection, VerticalAlign,
+};
+use log::error;
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Range,
 };
-use std::{collections::HashMap, marker::PhantomData};
 use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug)]
@@ -53,17 +66,46 @@ impl Component for UiGlyphs {
     type Storage = DenseVecStorage<Self>;
 }
 
+/// Appended to text shortened by `Overflow::Ellipsis`.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// The smallest `UiText::line_height` used, smaller values are clamped to it.
+pub(crate) const MIN_LINE_HEIGHT: f32 = 0.1;
+
+/// The lowest coverage drawn as opaque by `RasterMode::Sharp`, lower coverage is cut away.
+const SHARP_THRESHOLD: u8 = 128;
+
+/// The font size of superscripts and subscripts, relative to the font size of the text.
+const SCRIPT_SCALE: f32 = 0.7;
+
+/// How far superscripts are raised above the baseline, relative to the font size of the text.
+const SUPERSCRIPT_RISE: f32 = 0.4;
+
+/// How far subscripts are lowered below the baseline, relative to the font size of the text.
+const SUBSCRIPT_DROP: f32 = 0.2;
+
 #[derive(Debug)]
 enum FontState {
     NotFound,
-    Ready(FontId),
+    /// The font was added to the fonts of the system from this version of the asset.
+    Ready(FontId, u32),
 }
 
 impl FontState {
     fn id(&self) -> Option<FontId> {
         match self {
             FontState::NotFound => None,
-            FontState::Ready(id) => Some(*id),
+            FontState::Ready(id, _) => Some(*id),
+        }
+    }
+
+    /// Whether this state matches the asset version currently in storage.
+    /// A hot-reloaded font bumps the version and has to be added again.
+    fn is_current(&self, version: Option<u32>) -> bool {
+        match (self, version) {
+            (FontState::NotFound, None) => true,
+            (FontState::Ready(_, ready), Some(version)) => *ready == version,
+            _ => false,
         }
     }
 }
@@ -84,9 +126,23 @@ impl LineBreaker for CustomLineBreaker {
 }
 
 /// Manages the text editing cursor create, deletion and position.
+///
+/// Rasterized glyphs are kept in a texture shared by all text, holding at most the number
+/// of glyphs set with `with_cache_capacity`. When it holds that many, or the texture is full,
+/// the glyphs used least recently are evicted to make room and rasterized again on their next
+/// use. The texture only grows when the glyphs of a single frame don't fit, up to the maximum
+/// set with `with_max_cache_size`.
+///
+/// Marks the `UiDirty` resource, if there is one, when the glyphs drawn change.
+///
+/// Fonts are added once per `RasterMode`, so smooth and sharp glyphs are cached separately.
 pub struct UiGlyphsSystem<B: Backend> {
-    glyph_brush: GlyphBrush<'static, (u32, UiArgs)>,
-    fonts_map: HashMap<u32, FontState>,
+    fonts: Vec<Font<'static>>,
+    fonts_map: HashMap<(u32, RasterMode), FontState>,
+    layout_cache: LayoutCache,
+    glyph_cache: GlyphCache,
+    /// Hash of everything drawn on the last frame.
+    drawn_state: u64,
     marker: PhantomData<B>,
 }
 
@@ -94,13 +150,27 @@ impl<B: Backend> UiGlyphsSystem<B> {
     /// Create new UI glyphs system
     pub fn new() -> Self {
         Self {
-            glyph_brush: GlyphBrushBuilder::using_fonts(vec![])
-                .initial_cache_size((512, 512))
-                .build(),
+            fonts: Vec::new(),
             fonts_map: Default::default(),
+            layout_cache: Default::default(),
+            glyph_cache: GlyphCache::new((512, 512), (4096, 4096), 4096),
+            drawn_state: 0,
             marker: PhantomData,
         }
     }
+
+    /// Sets the largest size the glyph cache texture can grow to, in pixels.
+    /// Defaults to 4096x4096.
+    pub fn with_max_cache_size(mut self, max_cache_size: (u32, u32)) -> Self {
+        self.glyph_cache.set_max_size(max_cache_size);
+        self
+    }
+
+    /// Sets how many rasterized glyphs are kept in the glyph cache. Defaults to 4096.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.glyph_cache.set_capacity(capacity);
+        self
+    }
 }
 
 impl<'a, B: Backend> System<'a> for UiGlyphsSystem<B> {
@@ -119,6 +189,9 @@ impl<'a, B: Backend> System<'a> for UiGlyphsSystem<B> {
         Write<'a, AssetStorage<Texture>>,
         Read<'a, AssetStorage<FontAsset>>,
         WriteExpect<'a, UiGlyphsResource>,
+        Read<'a, UiScale>,
+        Option<ReadExpect<'a, ScreenDimensions>>,
+        Option<Write<'a, UiDirty>>,
     );
 
     fn run(
@@ -138,6 +211,9 @@ impl<'a, B: Backend> System<'a> for UiGlyphsSystem<B> {
             mut tex_storage,
             font_storage,
             mut glyphs_res,
+            ui_scale,
+            screen_dimensions,
+            mut ui_dirty,
         ): Self::SystemData,
     ) {
         let (factory, queue) =
@@ -149,18 +225,28 @@ impl<'a, B: Backend> System<'a> for UiGlyphsSystem<B> {
                 return;
             };
 
+        let glyph_cache = &mut self.glyph_cache;
         let glyph_tex = glyphs_res.glyph_tex.get_or_insert_with(|| {
-            let (w, h) = self.glyph_brush.texture_dimensions();
-            tex_storage.insert(create_glyph_texture(factory, *queue, w, h))
+            let (w, h) = glyph_cache.size();
+            tex_storage.insert(create_glyph_texture(
+                factory,
+                *queue,
+                w,
+                h,
+                &glyph_cache.image(),
+            ))
         });
 
-        let mut tex = tex_storage
-            .get(glyph_tex)
-            .and_then(B::unwrap_texture)
-            .expect("Glyph texture is created synchronously");
-
+        let ui_scale = screen_dimensions.map_or(ui_scale.factor, |dim| ui_scale.effective(&dim));
         let fonts_map_ref = &mut self.fonts_map;
-        let glyph_brush_ref = &mut self.glyph_brush;
+        let fonts_ref = &mut self.fonts;
+        let layout_cache = &mut self.layout_cache;
+        layout_cache.retain(|entity| entities.is_alive(entity));
+        glyph_cache.next_frame();
+        // The glyphs of every text, drawn once all of them are in the glyph cache.
+        let mut drawn = Vec::new();
+        let mut drawn_state = DefaultHasher::new();
+        let mut missing = 0;
 
         for (entity, transform, ui_text, editing, tint, _, _) in (
             &entities,
@@ -173,368 +259,1745 @@ impl<'a, B: Backend> System<'a> for UiGlyphsSystem<B> {
         )
             .join()
         {
-            let font_lookup = fonts_map_ref.entry(ui_text.font.id()).or_insert_with(|| {
-                if let Some(font) = font_storage.get(&ui_text.font) {
-                    FontState::Ready(glyph_brush_ref.add_font(font.0.clone()))
-                } else {
-                    FontState::NotFound
-                }
-            });
-
-            ui_text.cached_glyphs.clear();
+            let font_asset = font_storage.get_with_version(&ui_text.font);
+            let raster_mode = ui_text.raster_mode;
+            let font_id = cached_font_id(
+                fonts_map_ref,
+                fonts_ref,
+                &font_storage,
+                &ui_text.font,
+                raster_mode,
+            );
+            // Fallbacks that aren't loaded yet are skipped.
+            let fallbacks = ui_text
+                .fallback_fonts
+                .iter()
+                .filter_map(|handle| {
+                    let id = cached_font_id(
+                        fonts_map_ref,
+                        fonts_ref,
+                        &font_storage,
+                        handle,
+                        raster_mode,
+                    )?;
+                    Some((id, &font_storage.get(handle)?.0))
+                })
+                .collect::<Vec<_>>();
 
-            if let Some(font_id) = font_lookup.id() {
-                let tint_color = tint.map_or([1., 1., 1., 1.], |t| {
-                    let (r, g, b, a) = t.0.into_components();
-                    [r, g, b, a]
-                });
+            if let Some(font_id) = font_id {
+                let tint_color = tint_components(tint);
                 let base_color = mul_blend(&ui_text.color, &tint_color);
 
-                let scale = Scale::uniform(ui_text.font_size);
+                let font_size = ui_text.font_size * ui_scale;
+                let scale = Scale::uniform(font_size);
 
-                let text = match (ui_text.password, editing) {
-                    (false, None) => vec![SectionText {
-                        text: &ui_text.text,
-                        scale,
-                        color: base_color,
+                // Editable text is never shortened, as editing works on the whole string.
+                let display_text = match editing {
+                    None => shortened_text(
+                        &ui_text.text,
+                        ui_text,
+                        transform,
+                        font_asset.map(|(font, _)| font),
+                        &*fonts_ref,
                         font_id,
-                    }],
-                    (false, Some(sel)) => {
-                        if let Some((start, end)) = selection_span(sel, &ui_text.text) {
-                            vec![
-                                SectionText {
-                                    text: &ui_text.text[..start],
-                                    scale,
-                                    color: base_color,
-                                    font_id,
-                                },
-                                SectionText {
-                                    text: &ui_text.text[start..end],
-                                    scale,
-                                    color: mul_blend(&sel.selected_text_color, &tint_color),
-                                    font_id,
-                                },
-                                SectionText {
-                                    text: &ui_text.text[end..],
-                                    scale,
-                                    color: base_color,
-                                    font_id,
-                                },
-                            ]
-                        } else {
-                            vec![SectionText {
-                                text: &ui_text.text,
-                                scale,
-                                color: base_color,
-                                font_id,
-                            }]
-                        }
+                        font_size,
+                    ),
+                    Some(_) => Cow::Borrowed(ui_text.text.as_str()),
+                };
+                ui_text.truncated = *display_text != *ui_text.text;
+                // Spans index the whole text, so they are moved to what is shown of it.
+                let truncation = Truncation::of(&ui_text.text, &display_text);
+                let full_len = ui_text.text.len();
+                let to_display = |range: Range<usize>| {
+                    let range = range.start.min(full_len)..range.end.min(full_len);
+                    match truncation {
+                        Some(cut) => cut.map(range),
+                        None => range,
                     }
-                    (true, None) => {
-                        let string_len = ui_text.text.graphemes(true).count();
-                        password_sections(string_len)
-                            .map(|text| SectionText {
-                                text,
-                                scale,
-                                color: base_color,
-                                font_id,
-                            })
-                            .collect()
+                };
+
+                // The input method composition is shown at the cursor, without being part
+                // of the text, so spans after it are moved past it.
+                let (display_text, composition) = match editing
+                    .filter(|_| !ui_text.password)
+                    .and_then(|editing| with_composition(&ui_text.text, editing))
+                {
+                    Some((composed, range)) => (Cow::Owned(composed), Some(range)),
+                    None => (display_text, None),
+                };
+                let skip_composition = |range: Range<usize>| match &composition {
+                    Some(c) => {
+                        let start = if range.start >= c.start {
+                            range.start + c.len()
+                        } else {
+                            range.start
+                        };
+                        let end = if range.end > c.start {
+                            range.end + c.len()
+                        } else {
+                            range.end
+                        };
+                        start..end
                     }
-                    (true, Some(sel)) => {
-                        let string_len = ui_text.text.graphemes(true).count();
-                        let pos = sel.cursor_position;
-                        let pos_highlight = sel.cursor_position + sel.highlight_vector;
-                        let start = pos.min(pos_highlight) as usize;
-                        let to_end = pos.max(pos_highlight) as usize - start;
-                        let rest = string_len - start - to_end;
-                        [
-                            (start, base_color),
-                            (to_end, mul_blend(&sel.selected_text_color, &tint_color)),
-                            (rest, base_color),
-                        ]
+                    None => range,
+                };
+
+                let (text, baseline_shifts) = if ui_text.password {
+                    let text = password_section_texts(ui_text, editing, scale, font_id, tint_color);
+                    (text, Vec::new())
+                } else {
+                    let mut spans = ui_text
+                        .spans
                         .iter()
-                        .cloned()
-                        .flat_map(|(subsection_len, color)| {
-                            password_sections(subsection_len).map(move |text| SectionText {
-                                text,
-                                scale,
-                                color,
-                                font_id,
-                            })
+                        .map(|span| {
+                            (
+                                skip_composition(to_display(span.range.clone())),
+                                mul_blend(&span.color, &tint_color),
+                            )
                         })
-                        .collect()
+                        .collect::<Vec<_>>();
+                    // Selection is applied last, so it wins over the colored spans.
+                    if let Some(sel) = editing {
+                        if let Some((start, end)) = selection_span(sel, &ui_text.text) {
+                            spans.push((
+                                skip_composition(start..end),
+                                mul_blend(&sel.selected_text_color, &tint_color),
+                            ));
+                        }
                     }
+                    let baselines = ui_text
+                        .spans
+                        .iter()
+                        .map(|span| {
+                            (
+                                skip_composition(to_display(span.range.clone())),
+                                span.baseline,
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    let fonts = font_asset
+                        .map(|(font, _)| (font_id, &font.0))
+                        .into_iter()
+                        .chain(fallbacks.iter().cloned())
+                        .collect::<Vec<_>>();
+                    span_section_texts(
+                        &display_text,
+                        base_color,
+                        &spans,
+                        &baselines,
+                        &fonts,
+                        font_id,
+                        font_size,
+                    )
                 };
 
-                let layout = match ui_text.line_mode {
-                    LineMode::Single => Layout::SingleLine {
-                        line_breaker: CustomLineBreaker::None,
-                        h_align: ui_text.align.horizontal_align(),
-                        v_align: ui_text.align.vertical_align(),
-                    },
-                    LineMode::Wrap => Layout::Wrap {
-                        line_breaker: CustomLineBreaker::BuiltIn(
-                            BuiltInLineBreaker::UnicodeLineBreaker,
-                        ),
-                        h_align: ui_text.align.horizontal_align(),
-                        v_align: ui_text.align.vertical_align(),
-                    },
-                };
+                let layout = TextLayout::new(
+                    ui_text,
+                    transform,
+                    font_asset.map(|(font, _)| font),
+                    font_size,
+                    ui_scale,
+                    baseline_shifts,
+                );
+                // Glyphs not revealed yet are laid out, so the text doesn't move as more is
+                // revealed, but not drawn.
+                let visible_glyphs = ui_text
+                    .visible_graphemes
+                    .filter(|_| !ui_text.password)
+                    .and_then(|visible| {
+                        let (start, _) = ui_text.text.grapheme_indices(true).nth(visible)?;
+                        let start = skip_composition(to_display(start..full_len)).start;
+                        Some(
+                            display_text[..start]
+                                .chars()
+                                .filter(|c| !c.is_control())
+                                .count(),
+                        )
+                    });
 
                 let section = VariedSection {
-                    // Needs a recenter because we are using [-0.5,0.5] for the mesh
-                    // instead of the expected [0,1]
-                    screen_position: (
-                        transform.pixel_x + transform.pixel_width * ui_text.align.norm_offset().0,
-                        // invert y because layout calculates it in reverse
-                        -(transform.pixel_y
-                            + transform.pixel_height * ui_text.align.norm_offset().1),
-                    ),
-                    bounds: (transform.pixel_width, transform.pixel_height),
-                    // There is no other way to inject some glyph metadata than using Z.
-                    // Fortunately depth is not required, so this slot is instead used to
-                    // distinguish computed glyphs indented to be used for various entities.
-                    z: unsafe { std::mem::transmute(entity.id()) },
-                    layout: Default::default(), // overriden on queue
+                    screen_position: section_position(transform, &ui_text.align),
+                    bounds: match (&ui_text.overflow, &ui_text.line_mode) {
+                        (Overflow::Show, LineMode::Single) => {
+                            (std::f32::INFINITY, std::f32::INFINITY)
+                        }
+                        (Overflow::Show, LineMode::Wrap) => {
+                            (transform.pixel_width, std::f32::INFINITY)
+                        }
+                        _ => (transform.pixel_width, transform.pixel_height),
+                    },
                     text,
+                    ..VariedSection::default()
+                };
+                let geometry = SectionGeometry {
+                    screen_position: section.screen_position,
+                    bounds: section.bounds,
                 };
 
-                ui_text.cached_glyphs.extend(
-                    glyph_brush_ref
-                        .glyphs_custom_layout(&section, &layout)
-                        .map(|g| {
-                            let pos = g.position();
-                            let advance_width = g.unpositioned().h_metrics().advance_width;
-                            CachedGlyph {
-                                x: pos.x,
-                                y: -pos.y,
-                                advance_width,
-                            }
-                        }),
+                // Static labels keep the glyph positions of the last frame.
+                let key = layout_key(&section, &layout, font_asset.map(|(_, version)| *version));
+                let fonts = &*fonts_ref;
+                let laid_out = layout_cache.update(entity, key, || {
+                    layout
+                        .calculate_glyphs(fonts, &geometry, &section.text)
+                        .into_iter()
+                        .map(|(glyph, _, font_id)| (glyph, font_id))
+                        .collect()
+                });
+                let laid_out_glyphs = layout_cache.glyphs(entity);
+                if laid_out || ui_text.cached_glyphs.is_empty() {
+                    ui_text.cached_glyphs = section_glyphs(&section.text, laid_out_glyphs);
+                }
+
+                let (cached, not_cached) = cache_glyphs(
+                    glyph_cache,
+                    drawn_glyphs(laid_out_glyphs, &section.text, visible_glyphs),
+                    layout.pixel_snap,
+                    raster_mode,
                 );
+                missing += not_cached;
+                let bounds = layout.bounds_rect(&geometry);
+                let rotation = quad_rotation(transform);
 
-                glyph_brush_ref.queue_custom_layout(section, &layout);
+                entity.id().hash(&mut drawn_state);
+                key.hash(&mut drawn_state);
+                transform.rotation.to_bits().hash(&mut drawn_state);
+                for (_, _, color) in &cached {
+                    hash_color(color, &mut drawn_state);
+                }
+                if let Some(editing) = editing {
+                    editing.cursor_position.hash(&mut drawn_state);
+                    editing.highlight_vector.hash(&mut drawn_state);
+                    editing.composition.hash(&mut drawn_state);
+                    hash_color(&editing.selected_background_color, &mut drawn_state);
+                    hash_color(&tint_color, &mut drawn_state);
+                    hash_color(&ui_text.color, &mut drawn_state);
+                    selecteds.contains(entity).hash(&mut drawn_state);
+                    transform.pixel_x().to_bits().hash(&mut drawn_state);
+                    transform.pixel_y().to_bits().hash(&mut drawn_state);
+                }
+                drawn.push(DrawnText {
+                    entity,
+                    glyphs: cached,
+                    bounds,
+                    rotation,
+                });
+            } else {
+                ui_text.cached_glyphs.clear();
+                ui_text.truncated = false;
+                layout_cache.remove(entity);
             }
         }
 
-        loop {
-            let action = glyph_brush_ref.process_queued(
-                |rect, data| unsafe {
-                    factory
-                        .upload_image(
-                            tex.image().clone(),
-                            rect.width(),
-                            rect.height(),
-                            hal::image::SubresourceLayers {
-                                aspects: hal::format::Aspects::COLOR,
-                                level: 0,
-                                layers: 0..1,
-                            },
-                            hal::image::Offset {
-                                x: rect.min.x as _,
-                                y: rect.min.y as _,
-                                z: 0,
-                            },
-                            hal::image::Extent {
-                                width: rect.width(),
-                                height: rect.height(),
-                                depth: 1,
-                            },
-                            data,
-                            ImageState {
-                                queue: *queue,
-                                stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
-                                access: hal::image::Access::SHADER_READ,
-                                layout: hal::image::Layout::General,
-                            },
-                            ImageState {
-                                queue: *queue,
-                                stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
-                                access: hal::image::Access::SHADER_READ,
-                                layout: hal::image::Layout::General,
-                            },
+        let texture_changed =
+            update_glyph_texture(factory, *queue, &mut tex_storage, glyph_tex, glyph_cache);
+
+        // The glyphs are only drawn again when some text changed or the glyphs moved.
+        let drawn_state = drawn_state.finish();
+        if drawn_state == self.drawn_state && !texture_changed {
+            return;
+        }
+        self.drawn_state = drawn_state;
+        if let Some(ui_dirty) = ui_dirty.as_mut() {
+            ui_dirty.mark();
+        }
+        if missing > 0 {
+            error!(
+                "{} glyphs of this frame don't fit in the glyph cache of {:?} pixels holding \
+                 {} glyphs, they are not drawn.",
+                missing,
+                glyph_cache.size(),
+                glyph_cache.len()
+            );
+        }
+
+        let mut vertices = glyph_quads(drawn, glyph_cache);
+
+        // make sure to erase all glyphs, even if not drawn this frame
+        for glyph_data in (&mut glyphs).join() {
+            glyph_data.vertices.clear();
+            glyph_data.sel_vertices.clear();
+        }
+
+        for (entity, ui_text, editing, tint, transform, _, _) in (
+            &entities,
+            &texts,
+            text_editings.maybe(),
+            tints.maybe(),
+            &transforms,
+            !&hiddens,
+            !&hidden_propagates,
+        )
+            .join()
+        {
+            let vertices = vertices.remove(&entity).unwrap_or_default();
+            let glyph_data = match editing {
+                Some(editing) => {
+                    let font = font_storage
+                        .get(&ui_text.font)
+                        .expect("Font with rendered glyphs must be loaded");
+                    let tint_color = tint_components(tint);
+                    let bg_color = editing.selected_background_color;
+                    let bg_color = if selecteds.contains(entity) {
+                        bg_color
+                    } else {
+                        mul_blend(&bg_color, &[0.5, 0.5, 0.5, 0.5])
+                    };
+                    UiGlyphs {
+                        vertices,
+                        ..editing_glyphs(
+                            ui_text,
+                            editing,
+                            transform,
+                            &font.0,
+                            ui_text.font_size * ui_scale,
+                            mul_blend(&tint_color, &bg_color),
+                            mul_blend(&ui_text.color, &tint_color),
                         )
-                        .unwrap();
-                },
-                move |glyph| {
-                    // The glyph's Z parameter smuggles entity id, so glyphs can be associated
-                    // for rendering as part of specific components.
-                    let entity_id: u32 = unsafe { std::mem::transmute(glyph.z) };
-
-                    let mut uv = glyph.tex_coords;
-                    let bounds_max_x = glyph.bounds.max.x as f32;
-                    let bounds_max_y = glyph.bounds.max.y as f32;
-                    let bounds_min_x = glyph.bounds.min.x as f32;
-                    let bounds_min_y = glyph.bounds.min.y as f32;
-                    let mut coords_max_x = glyph.pixel_coords.max.x as f32;
-                    let mut coords_max_y = glyph.pixel_coords.max.y as f32;
-                    let mut coords_min_x = glyph.pixel_coords.min.x as f32;
-                    let mut coords_min_y = glyph.pixel_coords.min.y as f32;
-
-                    // Glyph out of bounds, trim the quad
-                    if coords_max_x > bounds_max_x {
-                        let old_width = coords_max_x - coords_min_x;
-                        coords_max_x = bounds_max_x;
-                        uv.max.x = uv.min.x
-                            + (uv.max.x - uv.min.x) * (coords_max_x - coords_min_x) / old_width;
-                    }
-                    if coords_min_x < bounds_min_x {
-                        let old_width = coords_max_x - coords_min_x;
-                        coords_min_x = bounds_min_x;
-                        uv.min.x = uv.max.x
-                            - (uv.max.x - uv.min.x) * (coords_max_x - coords_min_x) / old_width;
-                    }
-                    if coords_max_y > bounds_max_y {
-                        let old_height = coords_max_y - coords_min_y;
-                        coords_max_y = bounds_max_y;
-                        uv.max.y = uv.min.y
-                            + (uv.max.y - uv.min.y) * (coords_max_y - coords_min_y) / old_height;
-                    }
-                    if coords_min_y < bounds_min_y {
-                        let old_height = coords_max_y - coords_min_y;
-                        coords_min_y = bounds_min_y;
-                        uv.min.y = uv.max.y
-                            - (uv.max.y - uv.min.y) * (coords_max_y - coords_min_y) / old_height;
                     }
+                }
+                None => UiGlyphs {
+                    vertices,
+                    sel_vertices: vec![],
+                    cursor_pos: (0., 0.),
+                    height: 0.,
+                    space_width: 0.,
+                },
+            };
+            glyphs.insert(entity, glyph_data).unwrap();
+        }
+    }
 
-                    let coords = [
-                        (coords_max_x + coords_min_x) * 0.5,
-                        -(coords_max_y + coords_min_y) * 0.5,
-                    ];
-                    let dims = [(coords_max_x - coords_min_x), (coords_max_y - coords_min_y)];
-                    let tex_coord_bounds = [uv.min.x, uv.min.y, uv.max.x, uv.max.y];
-                    (
-                        entity_id,
-                        UiArgs {
-                            coords: coords.into(),
-                            dimensions: dims.into(),
-                            tex_coord_bounds: tex_coord_bounds.into(),
-                            color: glyph.color.into(),
-                        },
-                    )
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        res.insert(UiGlyphsResource { glyph_tex: None });
+        res.insert(UiRenderStats::default());
+    }
+}
+
+/// Returns the id of the font in `fonts`, adding it if it wasn't added yet.
+///
+/// The font gets a new FontId when it changes, so sections laid out and glyphs
+/// cached with the old font are not reused and fall out of the caches.
+fn cached_font_id(
+    fonts_map: &mut HashMap<(u32, RasterMode), FontState>,
+    fonts: &mut Vec<Font<'static>>,
+    font_storage: &AssetStorage<FontAsset>,
+    handle: &FontHandle,
+    raster_mode: RasterMode,
+) -> Option<FontId> {
+    let font_asset = font_storage.get_with_version(handle);
+    let font_lookup = fonts_map
+        .entry((handle.id(), raster_mode))
+        .or_insert(FontState::NotFound);
+    if !font_lookup.is_current(font_asset.map(|(_, version)| *version)) {
+        *font_lookup = match font_asset {
+            Some((font, version)) => {
+                fonts.push(font.0.clone());
+                FontState::Ready(FontId(fonts.len() - 1), *version)
+            }
+            None => FontState::NotFound,
+        };
+    }
+    font_lookup.id()
+}
+
+/// Cuts glyph coverage to fully opaque or fully transparent, for `RasterMode::Sharp`.
+fn sharpen(coverage: &mut [u8]) {
+    for alpha in coverage {
+        *alpha = if *alpha >= SHARP_THRESHOLD { 255 } else { 0 };
+    }
+}
+
+/// Rasterizes the glyph with its origin `subpixel` pixels from the top left of a pixel.
+fn rasterize(glyph: &PositionedGlyph<'_>, subpixel: (f32, f32), raster_mode: RasterMode) -> Raster {
+    let glyph = glyph
+        .unpositioned()
+        .clone()
+        .positioned(point(subpixel.0, subpixel.1));
+    let bounds = match glyph.pixel_bounding_box() {
+        Some(bounds) => bounds,
+        None => {
+            return Raster {
+                offset: (0, 0),
+                width: 0,
+                height: 0,
+                coverage: Vec::new(),
+            }
+        }
+    };
+    let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+    let mut coverage = vec![0; (width * height) as usize];
+    glyph.draw(|x, y, v| coverage[(y * width + x) as usize] = (v * 255.0) as u8);
+    if raster_mode == RasterMode::Sharp {
+        sharpen(&mut coverage);
+    }
+    Raster {
+        offset: (bounds.min.x, bounds.min.y),
+        width,
+        height,
+        coverage,
+    }
+}
+
+/// The quad drawing a cached glyph, with the top left of the pixel holding its origin at
+/// `origin`, trimmed to the `bounds` of its text and turned by the `rotation` of its transform.
+fn glyph_quad(
+    raster: CachedRaster,
+    origin: Point<f32>,
+    texture_size: (u32, u32),
+    bounds: Rect<f32>,
+    color: [f32; 4],
+    rotation: [f32; 2],
+) -> UiArgs {
+    let left = origin.x + raster.offset.0 as f32;
+    let top = origin.y + raster.offset.1 as f32;
+    let rect = raster.tex_rect;
+    let mut uv = Rect {
+        min: point(
+            rect.min.x as f32 / texture_size.0 as f32,
+            rect.min.y as f32 / texture_size.1 as f32,
+        ),
+        max: point(
+            rect.max.x as f32 / texture_size.0 as f32,
+            rect.max.y as f32 / texture_size.1 as f32,
+        ),
+    };
+    let mut coords_min_x = left;
+    let mut coords_min_y = top;
+    let mut coords_max_x = left + rect.width() as f32;
+    let mut coords_max_y = top + rect.height() as f32;
+
+    // Glyph out of bounds, trim the quad
+    if coords_max_x > bounds.max.x {
+        let old_width = coords_max_x - coords_min_x;
+        coords_max_x = bounds.max.x;
+        uv.max.x = uv.min.x + (uv.max.x - uv.min.x) * (coords_max_x - coords_min_x) / old_width;
+    }
+    if coords_min_x < bounds.min.x {
+        let old_width = coords_max_x - coords_min_x;
+        coords_min_x = bounds.min.x;
+        uv.min.x = uv.max.x - (uv.max.x - uv.min.x) * (coords_max_x - coords_min_x) / old_width;
+    }
+    if coords_max_y > bounds.max.y {
+        let old_height = coords_max_y - coords_min_y;
+        coords_max_y = bounds.max.y;
+        uv.max.y = uv.min.y + (uv.max.y - uv.min.y) * (coords_max_y - coords_min_y) / old_height;
+    }
+    if coords_min_y < bounds.min.y {
+        let old_height = coords_max_y - coords_min_y;
+        coords_min_y = bounds.min.y;
+        uv.min.y = uv.max.y - (uv.max.y - uv.min.y) * (coords_max_y - coords_min_y) / old_height;
+    }
+
+    let coords = [
+        (coords_max_x + coords_min_x) * 0.5,
+        -(coords_max_y + coords_min_y) * 0.5,
+    ];
+    let dims = [(coords_max_x - coords_min_x), (coords_max_y - coords_min_y)];
+    let tex_coord_bounds = [uv.min.x, uv.min.y, uv.max.x, uv.max.y];
+    UiArgs {
+        coords: coords.into(),
+        dimensions: dims.into(),
+        tex_coord_bounds: tex_coord_bounds.into(),
+        color: color.into(),
+        rotation: rotation.into(),
+    }
+}
+
+/// Where a glyph laid out at `position` is drawn: the subpixel offset it is rasterized at, in
+/// `1 / SUBPIXEL_STEPS` pixels, and the point the top left of the pixel holding its origin is
+/// drawn at.
+///
+/// Snapped glyphs are rasterized and drawn on whole pixels. The others keep their fractional
+/// position, rasterized at the nearest subpixel offset so rasters are shared between glyphs
+/// laid out close to the same offset.
+fn subpixel_position(position: Point<f32>, pixel_snap: bool) -> ((u8, u8), Point<f32>) {
+    if pixel_snap {
+        return ((0, 0), point(position.x.round(), position.y.round()));
+    }
+    let steps = f32::from(SUBPIXEL_STEPS);
+    let subpixel = |v: f32| ((v - v.floor()) * steps).round() as u8 % SUBPIXEL_STEPS;
+    let (x, y) = (subpixel(position.x), subpixel(position.y));
+    let origin = point(
+        position.x - f32::from(x) / steps,
+        position.y - f32::from(y) / steps,
+    );
+    ((x, y), origin)
+}
+
+/// The `UiArgs::rotation` of the quads drawn on the transform, the cosine and sine of its
+/// rotation.
+fn quad_rotation(transform: &UiTransform) -> [f32; 2] {
+    let (sin, cos) = transform.rotation.sin_cos();
+    [cos, sin]
+}
+
+/// The laid out `glyphs` of `sections` that are drawn, with their font, character and color.
+/// Only the first `visible` glyphs are drawn, the others are not revealed yet.
+fn drawn_glyphs<'a>(
+    glyphs: &'a [(PositionedGlyph<'static>, FontId)],
+    sections: &'a [SectionText<'a>],
+    visible: Option<usize>,
+) -> impl Iterator<Item = (&'a PositionedGlyph<'static>, FontId, char, [f32; 4])> + 'a {
+    let chars = sections.iter().flat_map(|section| {
+        section
+            .text
+            .chars()
+            .filter(|c| !c.is_control())
+            .map(move |c| (c, section.color))
+    });
+    glyphs
+        .iter()
+        .zip(chars)
+        .take(visible.unwrap_or(std::usize::MAX))
+        .map(|((glyph, font_id), (c, color))| (glyph, *font_id, c, color))
+}
+
+/// The glyphs of a text drawn this frame.
+struct DrawnText {
+    entity: Entity,
+    /// The key of each glyph in the glyph cache, with where it is drawn and its color.
+    glyphs: Vec<(GlyphKey, Point<f32>, [f32; 4])>,
+    /// The bounds the quads of the glyphs are trimmed to.
+    bounds: Rect<f32>,
+    rotation: [f32; 2],
+}
+
+/// Adds the rasters of the drawn `glyphs` to the glyph cache. Returns the key of each glyph
+/// in the cache, with where it is drawn and its color, and how many glyphs didn't fit.
+fn cache_glyphs<'a>(
+    glyph_cache: &mut GlyphCache,
+    glyphs: impl Iterator<Item = (&'a PositionedGlyph<'static>, FontId, char, [f32; 4])>,
+    pixel_snap: bool,
+    raster_mode: RasterMode,
+) -> (Vec<(GlyphKey, Point<f32>, [f32; 4])>, usize) {
+    let mut missing = 0;
+    let cached = glyphs
+        // Whitespace has nothing to draw.
+        .filter(|(glyph, ..)| glyph.pixel_bounding_box().is_some())
+        .filter_map(|(glyph, font_id, c, color)| {
+            let (subpixel, origin) = subpixel_position(glyph.position(), pixel_snap);
+            let glyph_key = GlyphKey::new(font_id, c, glyph.scale(), subpixel);
+            let raster = || rasterize(glyph, glyph_key.subpixel_offset(), raster_mode);
+            if glyph_cache.insert(glyph_key, raster) {
+                Some((glyph_key, origin, color))
+            } else {
+                missing += 1;
+                None
+            }
+        })
+        .collect();
+    (cached, missing)
+}
+
+/// The quads of the glyphs of every drawn text, by entity.
+fn glyph_quads(drawn: Vec<DrawnText>, glyph_cache: &GlyphCache) -> HashMap<Entity, Vec<UiArgs>> {
+    let texture_size = glyph_cache.size();
+    drawn
+        .into_iter()
+        .map(|text| {
+            let quads = text
+                .glyphs
+                .into_iter()
+                .filter_map(|(key, origin, color)| {
+                    let raster = glyph_cache.get(&key)?;
+                    Some(glyph_quad(
+                        raster,
+                        origin,
+                        texture_size,
+                        text.bounds,
+                        color,
+                        text.rotation,
+                    ))
+                })
+                .collect();
+            (text.entity, quads)
+        })
+        .collect()
+}
+
+/// Uploads the glyphs added to the glyph cache since the last frame to the glyph texture, or
+/// replaces the texture when the cache was packed again. Returns whether the texture changed.
+fn update_glyph_texture<B: Backend>(
+    factory: &mut Factory<B>,
+    queue: QueueId,
+    tex_storage: &mut AssetStorage<Texture>,
+    glyph_tex: &Handle<Texture>,
+    glyph_cache: &mut GlyphCache,
+) -> bool {
+    let uploads = match glyph_cache.take_update() {
+        Some(TextureUpdate::All) => {
+            // Replace texture in asset storage. No handles have to be updated.
+            let (w, h) = glyph_cache.size();
+            let texture = create_glyph_texture(factory, queue, w, h, &glyph_cache.image());
+            tex_storage.replace(glyph_tex, texture);
+            return true;
+        }
+        Some(TextureUpdate::Glyphs(uploads)) => uploads,
+        None => return false,
+    };
+    let tex = tex_storage
+        .get(glyph_tex)
+        .and_then(B::unwrap_texture)
+        .expect("Glyph texture is created synchronously");
+    for (rect, data) in uploads {
+        unsafe {
+            factory
+                .upload_image(
+                    tex.image().clone(),
+                    rect.width(),
+                    rect.height(),
+                    hal::image::SubresourceLayers {
+                        aspects: hal::format::Aspects::COLOR,
+                        level: 0,
+                        layers: 0..1,
+                    },
+                    hal::image::Offset {
+                        x: rect.min.x as _,
+                        y: rect.min.y as _,
+                        z: 0,
+                    },
+                    hal::image::Extent {
+                        width: rect.width(),
+                        height: rect.height(),
+                        depth: 1,
+                    },
+                    &data,
+                    ImageState {
+                        queue,
+                        stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
+                        access: hal::image::Access::SHADER_READ,
+                        layout: hal::image::Layout::General,
+                    },
+                    ImageState {
+                        queue,
+                        stage: hal::pso::PipelineStage::FRAGMENT_SHADER,
+                        access: hal::image::Access::SHADER_READ,
+                        layout: hal::image::Layout::General,
+                    },
+                )
+                .unwrap();
+        }
+    }
+    true
+}
+
+/// `text`, the text of `ui_text`, shortened to fit its transform by its overflow and
+/// `max_lines`.
+fn shortened_text<'a>(
+    text: &'a str,
+    ui_text: &UiText,
+    transform: &UiTransform,
+    font: Option<&FontAsset>,
+    fonts: &impl FontMap<'static>,
+    font_id: FontId,
+    font_size: f32,
+) -> Cow<'a, str> {
+    let width_of = |font: &FontAsset, text: &str| font.measure(text, font_size).width;
+    let display_text = match (&ui_text.overflow, &ui_text.line_mode, font) {
+        (Overflow::Ellipsis, LineMode::Single, Some(font)) => {
+            ellipsize(text, transform.pixel_width, |text| width_of(font, text))
+        }
+        (Overflow::MiddleEllipsis { head, tail }, LineMode::Single, Some(font)) => {
+            middle_ellipsize(text, transform.pixel_width, *head, *tail, |text| {
+                width_of(font, text)
+            })
+        }
+        _ => Cow::Borrowed(text),
+    };
+    match (ui_text.max_lines, &ui_text.line_mode, font) {
+        (Some(0), _, _) => Cow::Borrowed(""),
+        (Some(max_lines), LineMode::Wrap, Some(font)) => {
+            let starts = {
+                let geometry = SectionGeometry {
+                    screen_position: (0.0, 0.0),
+                    bounds: (transform.pixel_width, std::f32::INFINITY),
+                };
+                let text = [SectionText {
+                    text: &display_text,
+                    scale: Scale::uniform(font_size),
+                    font_id,
+                    ..SectionText::default()
+                }];
+                let layout = text_layout(&LineMode::Wrap, &Anchor::TopLeft);
+                line_starts(
+                    &display_text,
+                    layout
+                        .calculate_glyphs(fonts, &geometry, &text)
+                        .iter()
+                        .map(|(glyph, _, _)| glyph.position().y),
+                )
+            };
+            match limit_lines(
+                &display_text,
+                &starts,
+                max_lines,
+                transform.pixel_width,
+                |text| width_of(font, text),
+            ) {
+                Some(limited) => Cow::Owned(limited),
+                None => display_text,
+            }
+        }
+        _ => display_text,
+    }
+}
+
+/// The sections of `text` colored by `spans`, moved off the baseline by `baselines` and
+/// split where the glyphs are taken from another of `fonts`, with how far each section is
+/// raised above the baseline.
+fn span_section_texts<'a>(
+    text: &'a str,
+    base_color: [f32; 4],
+    spans: &[(Range<usize>, [f32; 4])],
+    baselines: &[(Range<usize>, TextBaseline)],
+    fonts: &[(FontId, &Font<'_>)],
+    default_font: FontId,
+    font_size: f32,
+) -> (Vec<SectionText<'a>>, Vec<f32>) {
+    let baseline_runs = color_runs(text, TextBaseline::Normal, baselines);
+    color_runs(text, base_color, spans)
+        .into_iter()
+        .flat_map(|(range, color)| {
+            // Color runs are split further where the baseline changes.
+            baseline_runs.iter().filter_map(move |(run, baseline)| {
+                let start = range.start.max(run.start);
+                let end = range.end.min(run.end);
+                if start < end {
+                    Some((start..end, color, *baseline))
+                } else {
+                    None
+                }
+            })
+        })
+        .flat_map(|(range, color, baseline)| {
+            let run = &text[range];
+            font_runs(run, fonts.len(), |i, c| has_glyph(fonts[i].1, c))
+                .into_iter()
+                .map(move |(range, font)| (&run[range], color, font, baseline))
+        })
+        .map(|(text, color, font, baseline)| {
+            let (scale, shift) = baseline_metrics(baseline, font_size);
+            let section = SectionText {
+                text,
+                scale,
+                color,
+                font_id: fonts.get(font).map_or(default_font, |(id, _)| *id),
+            };
+            (section, shift)
+        })
+        .unzip()
+}
+
+/// The sections of a password, a dot for every grapheme, with the selection of `editing` in
+/// its selected text color.
+fn password_section_texts(
+    ui_text: &UiText,
+    editing: Option<&TextEditing>,
+    scale: Scale,
+    font_id: FontId,
+    tint_color: [f32; 4],
+) -> Vec<SectionText<'static>> {
+    let base_color = mul_blend(&ui_text.color, &tint_color);
+    let string_len = ui_text.text.graphemes(true).count();
+    let runs = match editing {
+        Some(sel) => {
+            let pos = sel.cursor_position;
+            let pos_highlight = sel.cursor_position + sel.highlight_vector;
+            let start = pos.min(pos_highlight) as usize;
+            let to_end = pos.max(pos_highlight) as usize - start;
+            let rest = string_len - start - to_end;
+            vec![
+                (start, base_color),
+                (to_end, mul_blend(&sel.selected_text_color, &tint_color)),
+                (rest, base_color),
+            ]
+        }
+        None => vec![(string_len, base_color)],
+    };
+    runs.into_iter()
+        .flat_map(|(subsection_len, color)| {
+            password_sections(subsection_len).map(move |text| SectionText {
+                text,
+                scale,
+                color,
+                font_id,
+            })
+        })
+        .collect()
+}
+
+/// The cached glyph of every grapheme of the text of `sections`, from its laid out `glyphs`.
+fn section_glyphs(
+    sections: &[SectionText<'_>],
+    glyphs: &[(PositionedGlyph<'static>, FontId)],
+) -> Vec<CachedGlyph> {
+    let text = sections
+        .iter()
+        .map(|section| section.text)
+        .collect::<String>();
+    grapheme_glyphs(
+        &text,
+        glyphs.iter().map(|(g, _)| {
+            let pos = g.position();
+            let advance_width = g.unpositioned().h_metrics().advance_width;
+            CachedGlyph {
+                x: pos.x,
+                y: -pos.y,
+                advance_width,
+            }
+        }),
+    )
+}
+
+/// The selection highlight, the underline of the input method composition and the cursor of
+/// edited text, placed by its cached glyphs. The glyph quads are left empty.
+fn editing_glyphs(
+    ui_text: &UiText,
+    editing: &TextEditing,
+    transform: &UiTransform,
+    font: &Font<'_>,
+    font_size: f32,
+    bg_color: [f32; 4],
+    text_color: [f32; 4],
+) -> UiGlyphs {
+    let scale = Scale::uniform(font_size);
+    let v_metrics = font.v_metrics(scale);
+    let height = v_metrics.ascent - v_metrics.descent;
+    let offset = (v_metrics.ascent + v_metrics.descent) * 0.5;
+    let total_len = ui_text.cached_glyphs.len();
+    // Glyphs of the input method composition are laid out at the cursor.
+    let composed = if ui_text.password {
+        0
+    } else {
+        editing.composition.graphemes(true).count()
+    };
+    let pos = editing.cursor_position as usize;
+    let pos_highlight = (editing.cursor_position + editing.highlight_vector) as usize;
+    let (start, end) = if editing.highlight_vector > 0 {
+        (pos + composed, pos_highlight + composed)
+    } else {
+        (pos_highlight, pos)
+    };
+    let start = start.min(total_len);
+    let end = end.min(total_len);
+
+    let mut sel_vertices =
+        selection_quads(&ui_text.cached_glyphs[start..end], offset, height, bg_color);
+    let thickness = (font_size / 16.0).max(1.0);
+    let underline = ui_text
+        .cached_glyphs
+        .iter()
+        .skip(pos)
+        .take(composed)
+        .map(|g| UiArgs {
+            coords: [g.x + g.advance_width * 0.5, g.y + v_metrics.descent * 0.5].into(),
+            dimensions: [g.advance_width, thickness].into(),
+            tex_coord_bounds: [0., 0., 1., 1.].into(),
+            color: text_color.into(),
+            rotation: [1., 0.].into(),
+        });
+    sel_vertices.extend(underline);
+    let rtl = ui_text.direction.is_rtl(&ui_text.text);
+    let cursor_pos = match caret_position(&ui_text.cached_glyphs, pos + composed, rtl) {
+        Some((x, y)) => (x, y + offset),
+        None => (
+            transform.pixel_x() + transform.pixel_width * ui_text.align.norm_offset().0,
+            transform.pixel_y(),
+        ),
+    };
+    UiGlyphs {
+        vertices: vec![],
+        sel_vertices,
+        cursor_pos,
+        height,
+        space_width: font.glyph(' ').scaled(scale).h_metrics().advance_width,
+    }
+}
+
+/// The color of the tint, white without one.
+fn tint_components(tint: Option<&Tint>) -> [f32; 4] {
+    tint.map_or([1., 1., 1., 1.], |t| {
+        let (r, g, b, a) = t.0.into_components();
+        [r, g, b, a]
+    })
+}
+
+/// Hashes the bits of every component of the color.
+fn hash_color(color: &[f32; 4], state: &mut impl Hasher) {
+    for component in color {
+        component.to_bits().hash(state);
+    }
+}
+
+/// Returns one highlight rectangle per line for the selected glyphs, spanning from the
+/// start of the first glyph to the end of the last one on that line.
+fn selection_quads(
+    glyphs: &[CachedGlyph],
+    offset: f32,
+    height: f32,
+    color: [f32; 4],
+) -> Vec<UiArgs> {
+    let mut lines: Vec<(f32, f32, f32)> = Vec::new();
+    for glyph in glyphs {
+        let (left, right) = (glyph.x, glyph.x + glyph.advance_width);
+        match lines.last_mut() {
+            Some((y, min_x, max_x)) if *y == glyph.y => {
+                *min_x = min_x.min(left);
+                *max_x = max_x.max(right);
+            }
+            _ => lines.push((glyph.y, left, right)),
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(y, min_x, max_x)| UiArgs {
+            coords: [(min_x + max_x) * 0.5, y + offset].into(),
+            dimensions: [max_x - min_x, height].into(),
+            tex_coord_bounds: [0., 0., 1., 1.].into(),
+            color: color.into(),
+            rotation: [1., 0.].into(),
+        })
+        .collect()
+}
+
+/// Returns the position of the text cursor in front of the glyph at `index`, on the
+/// baseline. The cursor is on the side of the glyph the text starts from. Past the last
+/// glyph it is behind the last glyph, and `None` when there are no glyphs.
+fn caret_position(glyphs: &[CachedGlyph], index: usize, rtl: bool) -> Option<(f32, f32)> {
+    if let Some(glyph) = glyphs.get(index) {
+        Some(if rtl {
+            (glyph.x + glyph.advance_width, glyph.y)
+        } else {
+            (glyph.x, glyph.y)
+        })
+    } else {
+        glyphs.last().map(|glyph| {
+            if rtl {
+                (glyph.x, glyph.y)
+            } else {
+                (glyph.x + glyph.advance_width, glyph.y)
+            }
+        })
+    }
+}
+
+/// Whether the font has a glyph for the character, rather than the missing glyph box.
+fn has_glyph(font: &Font<'_>, c: char) -> bool {
+    font.glyph(c).id() != GlyphId(0)
+}
+
+/// Splits `text` into byte ranges rendered with the same font, out of `fonts` fonts ordered
+/// by preference. Each character uses the first font that has a glyph for it, or the first
+/// font if none has. Whitespace stays with the font of the preceding character.
+fn font_runs(
+    text: &str,
+    fonts: usize,
+    has_glyph: impl Fn(usize, char) -> bool,
+) -> Vec<(Range<usize>, usize)> {
+    let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+    for (start, c) in text.char_indices() {
+        let end = start + c.len_utf8();
+        let font = match runs.last() {
+            Some((_, font)) if c.is_whitespace() => *font,
+            _ => (0..fonts).find(|&i| has_glyph(i, c)).unwrap_or(0),
+        };
+        match runs.last_mut() {
+            Some((range, last)) if *last == font => range.end = end,
+            _ => runs.push((start..end, font)),
+        }
+    }
+    runs
+}
+
+/// The scale of the glyphs on `baseline` in text of `font_size`, and how far they are
+/// raised above the baseline in pixels.
+fn baseline_metrics(baseline: TextBaseline, font_size: f32) -> (Scale, f32) {
+    match baseline {
+        TextBaseline::Normal => (Scale::uniform(font_size), 0.0),
+        TextBaseline::Superscript => (
+            Scale::uniform(font_size * SCRIPT_SCALE),
+            font_size * SUPERSCRIPT_RISE,
+        ),
+        TextBaseline::Subscript => (
+            Scale::uniform(font_size * SCRIPT_SCALE),
+            -font_size * SUBSCRIPT_DROP,
+        ),
+    }
+}
+
+/// Position of the text section within its transform, in the inverted y
+/// coordinates that the glyph layout works in.
+fn section_position(transform: &UiTransform, align: &Anchor) -> (f32, f32) {
+    // Needs a recenter because we are using [-0.5,0.5] for the mesh
+    // instead of the expected [0,1]
+    let (offset_x, offset_y) = align.norm_offset();
+    (
+        transform.pixel_x + transform.pixel_width * offset_x,
+        // invert y because layout calculates it in reverse
+        -(transform.pixel_y + transform.pixel_height * offset_y),
+    )
+}
+
+fn text_layout(line_mode: &LineMode, align: &Anchor) -> Layout<CustomLineBreaker> {
+    match line_mode {
+        LineMode::Single => Layout::SingleLine {
+            line_breaker: CustomLineBreaker::None,
+            h_align: align.horizontal_align(),
+            v_align: align.vertical_align(),
+        },
+        LineMode::Wrap => Layout::Wrap {
+            line_breaker: CustomLineBreaker::BuiltIn(BuiltInLineBreaker::UnicodeLineBreaker),
+            h_align: align.horizontal_align(),
+            v_align: align.vertical_align(),
+        },
+    }
+}
+
+/// Merges the glyphs of every grapheme of `text` into one, so cached glyphs are indexed
+/// like the cursor of `TextEditing`. Flags and letters with combining marks are laid out as
+/// several glyphs, one per char.
+fn grapheme_glyphs(text: &str, glyphs: impl IntoIterator<Item = CachedGlyph>) -> Vec<CachedGlyph> {
+    let mut glyphs = glyphs.into_iter();
+    text.graphemes(true)
+        .filter_map(|grapheme| {
+            let mut cluster = glyphs.by_ref().take(grapheme.chars().count());
+            let first = cluster.next()?;
+            let (left, right) = cluster.fold(
+                (first.x, first.x + first.advance_width),
+                |(left, right), glyph| {
+                    (left.min(glyph.x), right.max(glyph.x + glyph.advance_width))
                 },
             );
+            Some(CachedGlyph {
+                x: left,
+                y: first.y,
+                advance_width: right - left,
+            })
+        })
+        .collect()
+}
 
-            match action {
-                Ok(BrushAction::Draw(vertices)) => {
-                    // entity ids are guaranteed to be in the same order as queued
-                    let mut glyph_ctr = 0;
+/// The longest start of `text` that fits in `max_width` with "…" appended, or `text` itself
+/// when it fits whole. Nothing is left when not even the ellipsis fits.
+fn ellipsize<'a>(text: &'a str, max_width: f32, width_of: impl Fn(&str) -> f32) -> Cow<'a, str> {
+    if width_of(text) <= max_width {
+        return Cow::Borrowed(text);
+    }
+    if width_of(ELLIPSIS) > max_width {
+        return Cow::Borrowed("");
+    }
+    let boundaries = text.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+    // The number of leading chars kept, found with a binary search as longer starts of the
+    // text are wider.
+    let (mut kept, mut too_many) = (0, boundaries.len());
+    while too_many - kept > 1 {
+        let middle = (kept + too_many) / 2;
+        let candidate = format!("{}{}", &text[..boundaries[middle]], ELLIPSIS);
+        if width_of(&candidate) <= max_width {
+            kept = middle;
+        } else {
+            too_many = middle;
+        }
+    }
+    Cow::Owned(format!("{}{}", &text[..boundaries[kept]], ELLIPSIS))
+}
 
-                    // make sure to erase all glyphs, even if not queued this frame
-                    for glyph_data in (&mut glyphs).join() {
-                        glyph_data.vertices.clear();
-                        glyph_data.sel_vertices.clear();
-                    }
+/// The byte index every line of `text` starts at, given the baselines of its glyphs in
+/// order. Lines start after hard line breaks and where the baseline of the glyphs changes.
+fn line_starts(text: &str, baselines: impl IntoIterator<Item = f32>) -> Vec<usize> {
+    let mut baselines = baselines.into_iter();
+    let mut starts = vec![0];
+    let mut line = None;
+    for (index, c) in text.char_indices() {
+        match c {
+            '\n' => {
+                starts.push(index + 1);
+                line = None;
+            }
+            // Control characters are laid out without a glyph.
+            c if c.is_control() => {}
+            _ => {
+                let baseline = match baselines.next() {
+                    Some(baseline) => baseline,
+                    None => break,
+                };
+                if line.map_or(false, |line| line != baseline) {
+                    starts.push(index);
+                }
+                line = Some(baseline);
+            }
+        }
+    }
+    starts
+}
 
-                    for (entity, ui_text, editing, tint, transform, _, _) in (
-                        &entities,
-                        &texts,
-                        text_editings.maybe(),
-                        tints.maybe(),
-                        &transforms,
-                        !&hiddens,
-                        !&hidden_propagates,
-                    )
-                        .join()
-                    {
-                        let e_id = entity.id();
-                        let len = vertices[glyph_ctr..]
-                            .iter()
-                            .take_while(|(id, _)| *id == e_id)
-                            .count();
-                        let entity_verts = vertices[glyph_ctr..glyph_ctr + len].iter().map(|v| v.1);
-                        glyph_ctr += len;
-
-                        if let Some(glyph_data) = glyphs.get_mut(entity) {
-                            glyph_data.vertices.extend(entity_verts);
-                        } else {
-                            glyphs
-                                .insert(
-                                    entity,
-                                    UiGlyphs {
-                                        vertices: entity_verts.collect(),
-                                        sel_vertices: vec![],
-                                        cursor_pos: (0., 0.),
-                                        height: 0.,
-                                        space_width: 0.,
-                                    },
-                                )
-                                .unwrap();
-                        }
+/// `text` cut after its first `max_lines` lines starting at `starts`, with "…" ending the
+/// last line, or `None` when no more text follows them. Chars at the end of the last line
+/// are dropped until the ellipsis fits in `max_width`.
+fn limit_lines(
+    text: &str,
+    starts: &[usize],
+    max_lines: usize,
+    max_width: f32,
+    width_of: impl Fn(&str) -> f32,
+) -> Option<String> {
+    match starts.get(max_lines) {
+        Some(&cut) if !text[cut..].trim().is_empty() => {}
+        _ => return None,
+    }
+    if max_lines == 0 {
+        return Some(String::new());
+    }
+    let last_start = starts[max_lines - 1];
+    let last_line = text[last_start..starts[max_lines]].trim_end();
+    // Shortening the line with the ellipsis appended keeps it whole when it fits, and
+    // otherwise only drops chars of the line itself.
+    let last_line = format!("{}{}", last_line, ELLIPSIS);
+    let last_line = ellipsize(&last_line, max_width, width_of);
+    Some(format!("{}{}", &text[..last_start], last_line))
+}
 
-                        if let Some(editing) = editing {
-                            let font = font_storage
-                                .get(&ui_text.font)
-                                .expect("Font with rendered glyphs must be loaded");
-                            let scale = Scale::uniform(ui_text.font_size);
-                            let v_metrics = font.0.v_metrics(scale);
-                            let height = v_metrics.ascent - v_metrics.descent;
-                            let offset = (v_metrics.ascent + v_metrics.descent) * 0.5;
-                            let total_len = ui_text.cached_glyphs.len();
-                            let pos = editing.cursor_position;
-                            let pos_highlight = editing.cursor_position + editing.highlight_vector;
-                            let start = (pos.min(pos_highlight) as usize).min(total_len);
-                            let end = (pos.max(pos_highlight) as usize).min(total_len);
-
-                            let tint_color = tint.map_or([1., 1., 1., 1.], |t| {
-                                let (r, g, b, a) = t.0.into_components();
-                                [r, g, b, a]
-                            });
-                            let bg_color = editing.selected_background_color;
-                            let bg_color = if selecteds.contains(entity) {
-                                bg_color
-                            } else {
-                                mul_blend(&bg_color, &[0.5, 0.5, 0.5, 0.5])
-                            };
-                            let bg_color = mul_blend(&tint_color, &bg_color);
-
-                            let iter = ui_text.cached_glyphs[start..end].iter().map(|g| UiArgs {
-                                coords: [g.x + g.advance_width * 0.5, g.y + offset].into(),
-                                dimensions: [g.advance_width, height].into(),
-                                tex_coord_bounds: [0., 0., 1., 1.].into(),
-                                color: bg_color.clone().into(),
-                            });
-                            let mut glyph_data = glyphs.get_mut(entity).unwrap();
-                            glyph_data.sel_vertices.extend(iter);
-                            glyph_data.height = height;
-                            glyph_data.space_width =
-                                font.0.glyph(' ').scaled(scale).h_metrics().advance_width;
-                            glyph_data.cursor_pos =
-                                if let Some(glyph) = ui_text.cached_glyphs.get(pos as usize) {
-                                    (glyph.x, glyph.y + offset)
-                                } else if let Some(glyph) = ui_text.cached_glyphs.last() {
-                                    (glyph.x + glyph.advance_width, glyph.y + offset)
-                                } else {
-                                    (
-                                        transform.pixel_x()
-                                            + transform.pixel_width * ui_text.align.norm_offset().0,
-                                        transform.pixel_y(),
-                                    )
-                                };
-                        }
-                    }
-                    break;
+/// The first `head` and last `tail` chars of `text` with "…" between them, or `text` itself
+/// when it fits whole. Leading chars are dropped until the result fits in `max_width`, then
+/// trailing ones. Nothing is left when not even the ellipsis fits.
+fn middle_ellipsize<'a>(
+    text: &'a str,
+    max_width: f32,
+    head: usize,
+    tail: usize,
+    width_of: impl Fn(&str) -> f32,
+) -> Cow<'a, str> {
+    if width_of(text) <= max_width {
+        return Cow::Borrowed(text);
+    }
+    if width_of(ELLIPSIS) > max_width {
+        return Cow::Borrowed("");
+    }
+    let boundaries = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(Some(text.len()))
+        .collect::<Vec<_>>();
+    let chars = boundaries.len() - 1;
+    let tail = tail.min(chars);
+    let head = head.min(chars - tail);
+    let shortened = |head: usize, tail: usize| {
+        format!(
+            "{}{}{}",
+            &text[..boundaries[head]],
+            ELLIPSIS,
+            &text[boundaries[chars - tail]..]
+        )
+    };
+    // The most chars kept on one side while the other keeps `other`, found with a binary
+    // search as keeping more chars only makes the text wider.
+    let most_kept = |limit: usize, keep: &dyn Fn(usize) -> String| {
+        let (mut kept, mut too_many) = (0, limit + 1);
+        while too_many - kept > 1 {
+            let middle = (kept + too_many) / 2;
+            if width_of(&keep(middle)) <= max_width {
+                kept = middle;
+            } else {
+                too_many = middle;
+            }
+        }
+        kept
+    };
+    if width_of(&shortened(0, tail)) <= max_width {
+        let head = most_kept(head, &|head| shortened(head, tail));
+        Cow::Owned(shortened(head, tail))
+    } else {
+        let tail = most_kept(tail, &|tail| shortened(0, tail));
+        Cow::Owned(shortened(0, tail))
+    }
+}
+
+/// Where text was shortened for display: the bytes of the original text from `head` to
+/// `tail` were replaced by `ellipsis` bytes, which are zero when no ellipsis was added.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Truncation {
+    head: usize,
+    ellipsis: usize,
+    tail: usize,
+}
+
+impl Truncation {
+    /// How `text` was cut to give `display`, or `None` when it is shown whole. The ellipsis
+    /// may be followed by the end of the text, as with `middle_ellipsize`.
+    fn of(text: &str, display: &str) -> Option<Self> {
+        if text == display {
+            return None;
+        }
+        // The text itself may hold ellipses, so any of them could be the one added.
+        let cut = display.rmatch_indices(ELLIPSIS).find_map(|(head, _)| {
+            let shown_tail = &display[head + ELLIPSIS.len()..];
+            let tail = text.len().checked_sub(shown_tail.len())?;
+            if tail >= head && text.starts_with(&display[..head]) && text.ends_with(shown_tail) {
+                Some(Truncation {
+                    head,
+                    ellipsis: ELLIPSIS.len(),
+                    tail,
+                })
+            } else {
+                None
+            }
+        });
+        cut.or_else(|| {
+            if text.starts_with(display) {
+                Some(Truncation {
+                    head: display.len(),
+                    ellipsis: 0,
+                    tail: text.len(),
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Moves a byte range of the original text to the displayed text. The parts of the range
+    /// which were cut are dropped, the ellipsis is only included when the whole cut is.
+    fn map(&self, range: Range<usize>) -> Range<usize> {
+        let shown_tail = self.head + self.ellipsis;
+        let start = if range.start <= self.head {
+            range.start
+        } else if range.start >= self.tail {
+            range.start - self.tail + shown_tail
+        } else {
+            shown_tail
+        };
+        let end = if range.end <= self.head {
+            range.end
+        } else if range.end >= self.tail {
+            range.end - self.tail + shown_tail
+        } else {
+            self.head
+        };
+        start..end.max(start)
+    }
+}
+
+/// Lays text out like the wrapped `Layout`, then moves the glyphs after tabs to the tab
+/// stops, moves the lines apart by `line_scale`, justifies them and mirrors each line for
+/// right to left text, so the first glyph of a line ends at its right edge. Superscripts and
+/// subscripts are moved off the baseline last.
+#[derive(Debug, Clone)]
+struct TextLayout {
+    layout: Layout<CustomLineBreaker>,
+    rtl: bool,
+    /// How much the distance between baselines is scaled, `None` keeps the font's spacing.
+    line_scale: Option<f32>,
+    /// Whether glyph origins are rounded to whole pixels.
+    pixel_snap: bool,
+    /// Whether the kerning between pairs of glyphs is kept.
+    kerning: bool,
+    /// Whether spaces are widened so lines fill the width of the bounds.
+    justify: bool,
+    /// The distance between tab stops in pixels, tabs are ignored when it isn't positive.
+    tab_width: f32,
+    /// How far the glyphs of each section are raised above the baseline in pixels. Sections
+    /// past the end are not moved.
+    baseline_shifts: Vec<f32>,
+}
+
+impl TextLayout {
+    /// The layout of `ui_text` on its transform, with the sections moved off the baseline by
+    /// `baseline_shifts`.
+    fn new(
+        ui_text: &UiText,
+        transform: &UiTransform,
+        font: Option<&FontAsset>,
+        font_size: f32,
+        ui_scale: f32,
+        baseline_shifts: Vec<f32>,
+    ) -> Self {
+        let scale = Scale::uniform(font_size);
+        TextLayout {
+            layout: text_layout(&ui_text.line_mode, &ui_text.align),
+            rtl: ui_text.direction.is_rtl(&ui_text.text),
+            line_scale: match (ui_text.line_height, font) {
+                (Some(line_height), Some(font)) => Some(line_scale(&font.0, scale, line_height)),
+                _ => None,
+            },
+            pixel_snap: snaps_to_pixels(ui_text, transform, ui_scale),
+            kerning: ui_text.kerning,
+            justify: ui_text.justify == Justify::Full,
+            tab_width: match (ui_text.tab_width, font) {
+                (TabWidth::Pixels(width), _) => width * ui_scale,
+                (TabWidth::Spaces(count), Some(font)) => {
+                    count * font.0.glyph(' ').scaled(scale).h_metrics().advance_width
+                }
+                (TabWidth::Spaces(_), None) => 0.0,
+            },
+            baseline_shifts,
+        }
+    }
+}
+
+impl Hash for TextLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.layout.hash(state);
+        self.rtl.hash(state);
+        self.line_scale.map(f32::to_bits).hash(state);
+        self.pixel_snap.hash(state);
+        self.kerning.hash(state);
+        self.justify.hash(state);
+        self.tab_width.to_bits().hash(state);
+        for shift in &self.baseline_shifts {
+            shift.to_bits().hash(state);
+        }
+    }
+}
+
+impl GlyphPositioner for TextLayout {
+    fn calculate_glyphs<'font, F: FontMap<'font>>(
+        &self,
+        fonts: &F,
+        geometry: &SectionGeometry,
+        sections: &[SectionText<'_>],
+    ) -> Vec<(PositionedGlyph<'font>, [f32; 4], FontId)> {
+        let mut glyphs = self.layout.calculate_glyphs(fonts, geometry, sections);
+        let (h_align, v_align) = match self.layout {
+            Layout::SingleLine {
+                h_align, v_align, ..
+            }
+            | Layout::Wrap {
+                h_align, v_align, ..
+            } => (h_align, v_align),
+        };
+        if !self.kerning {
+            unkern_lines(
+                &mut glyphs,
+                |first, second, font_id| {
+                    fonts
+                        .font(font_id)
+                        .pair_kerning(second.scale(), first.id(), second.id())
+                },
+                h_align,
+            );
+        }
+        if self.tab_width > 0.0 && sections.iter().any(|section| section.text.contains('\t')) {
+            expand_tabs(
+                &mut glyphs,
+                sections.iter().flat_map(|section| section.text.chars()),
+                bounds_left(h_align, geometry),
+                self.tab_width,
+            );
+        }
+        if let Some(line_scale) = self.line_scale {
+            space_lines(&mut glyphs, line_scale, v_align);
+        }
+        let width = geometry.bounds.0;
+        if self.justify && width.is_finite() {
+            justify_lines(
+                &mut glyphs,
+                |glyph, font_id| glyph.id() == fonts.font(font_id).glyph(' ').id(),
+                bounds_left(h_align, geometry),
+                width,
+            );
+        }
+        if self.rtl {
+            mirror_lines(&mut glyphs);
+        }
+        // Lines are told apart by their baseline, so glyphs only leave it once the lines are
+        // laid out.
+        if self.baseline_shifts.iter().any(|shift| *shift != 0.0) {
+            let shifts = &self.baseline_shifts;
+            shift_baselines(
+                &mut glyphs,
+                sections.iter().enumerate().flat_map(|(i, section)| {
+                    let shift = shifts.get(i).cloned().unwrap_or(0.0);
+                    section
+                        .text
+                        .chars()
+                        .filter(|c| !c.is_control())
+                        .map(move |_| shift)
+                }),
+                |glyph, font_id| fonts.font(font_id).v_metrics(glyph.scale()),
+                v_align,
+            );
+        }
+        if self.pixel_snap {
+            snap_to_pixels(&mut glyphs);
+        }
+        glyphs
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> Rect<f32> {
+        self.layout.bounds_rect(geometry)
+    }
+}
+
+/// The glyphs of every `UiText` laid out on the last frame, with the layout inputs they were
+/// laid out from, so unchanged text isn't laid out again.
+#[derive(Debug, Default)]
+struct LayoutCache {
+    layouts: HashMap<Entity, (u64, Vec<(PositionedGlyph<'static>, FontId)>)>,
+}
+
+impl LayoutCache {
+    /// Lays out the text of the entity with `layout`, unless the layout key is the same as the
+    /// last one. Returns true when it was laid out again.
+    fn update(
+        &mut self,
+        entity: Entity,
+        key: u64,
+        layout: impl FnOnce() -> Vec<(PositionedGlyph<'static>, FontId)>,
+    ) -> bool {
+        match self.layouts.get(&entity) {
+            Some((last, _)) if *last == key => false,
+            _ => {
+                self.layouts.insert(entity, (key, layout()));
+                true
+            }
+        }
+    }
+
+    /// The glyphs the entity was last laid out to.
+    fn glyphs(&self, entity: Entity) -> &[(PositionedGlyph<'static>, FontId)] {
+        self.layouts.get(&entity).map_or(&[], |(_, glyphs)| glyphs)
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        self.layouts.remove(&entity);
+    }
+
+    fn retain(&mut self, mut alive: impl FnMut(Entity) -> bool) {
+        self.layouts.retain(|entity, _| alive(*entity));
+    }
+}
+
+/// Hashes everything the glyph positions of a section depend on. Colors are left out, so
+/// selecting or revealing text doesn't lay it out again.
+fn layout_key(section: &VariedSection<'_>, layout: &TextLayout, font_version: Option<u32>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    section.screen_position.0.to_bits().hash(&mut hasher);
+    section.screen_position.1.to_bits().hash(&mut hasher);
+    section.bounds.0.to_bits().hash(&mut hasher);
+    section.bounds.1.to_bits().hash(&mut hasher);
+    for text in &section.text {
+        text.text.hash(&mut hasher);
+        text.scale.x.to_bits().hash(&mut hasher);
+        text.scale.y.to_bits().hash(&mut hasher);
+        text.font_id.0.hash(&mut hasher);
+    }
+    layout.hash(&mut hasher);
+    font_version.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The factor to scale the font's distance between baselines by, so lines are
+/// `line_height` times the font's ascent plus descent apart.
+fn line_scale(font: &Font<'_>, scale: Scale, line_height: f32) -> f32 {
+    let v_metrics = font.v_metrics(scale);
+    let height = v_metrics.ascent - v_metrics.descent;
+    let default_spacing = height + v_metrics.line_gap;
+    if default_spacing > 0.0 {
+        line_height.max(MIN_LINE_HEIGHT) * height / default_spacing
+    } else {
+        1.0
+    }
+}
+
+/// The left edge of the bounds of a section. Unbounded sections start at their position.
+fn bounds_left(h_align: HorizontalAlign, geometry: &SectionGeometry) -> f32 {
+    let width = geometry.bounds.0;
+    match h_align {
+        _ if !width.is_finite() => geometry.screen_position.0,
+        HorizontalAlign::Left => geometry.screen_position.0,
+        HorizontalAlign::Center => geometry.screen_position.0 - width * 0.5,
+        HorizontalAlign::Right => geometry.screen_position.0 - width,
+    }
+}
+
+/// Undoes the kerning between neighbouring glyphs of each line with the same font and
+/// scale, moving the rest of the line along. Lines keep their `h_align`.
+fn unkern_lines<'font>(
+    glyphs: &mut [(PositionedGlyph<'font>, [f32; 4], FontId)],
+    kerning: impl Fn(&PositionedGlyph<'font>, &PositionedGlyph<'font>, FontId) -> f32,
+    h_align: HorizontalAlign,
+) {
+    let mut start = 0;
+    while start < glyphs.len() {
+        let baseline = glyphs[start].0.position().y;
+        let end = start
+            + glyphs[start..]
+                .iter()
+                .take_while(|(glyph, _, _)| glyph.position().y == baseline)
+                .count();
+        let line = &mut glyphs[start..end];
+        // How far each glyph moves, the sum of the kerning before it on the line.
+        let mut offset = 0.0;
+        let mut offsets = vec![0.0];
+        for pair in line.windows(2) {
+            let ((first, _, first_font), (second, _, second_font)) = (&pair[0], &pair[1]);
+            if first_font == second_font && first.scale() == second.scale() {
+                offset -= kerning(first, second, *second_font);
+            }
+            offsets.push(offset);
+        }
+        let shift = match h_align {
+            HorizontalAlign::Left => 0.0,
+            HorizontalAlign::Center => -offset * 0.5,
+            HorizontalAlign::Right => -offset,
+        };
+        for ((glyph, _, _), offset) in line.iter_mut().zip(offsets) {
+            let position = glyph.position();
+            *glyph = glyph
+                .unpositioned()
+                .clone()
+                .positioned(point(position.x + offset + shift, position.y));
+        }
+        start = end;
+    }
+}
+
+/// Moves the glyphs following every tab of `text` on its line to the next multiple of
+/// `tab_width` from `left`.
+///
+/// Tabs, like the other control characters, are laid out without a glyph, so glyphs are
+/// matched to the other characters of `text` in order.
+fn expand_tabs(
+    glyphs: &mut [(PositionedGlyph<'_>, [f32; 4], FontId)],
+    text: impl Iterator<Item = char>,
+    left: f32,
+    tab_width: f32,
+) {
+    let mut next = 0;
+    // The end of the last glyph or tab stop on the current line, and its baseline.
+    let mut pen: Option<(f32, f32)> = None;
+    for c in text {
+        match c {
+            '\t' => {
+                let (x, baseline) = match (pen, glyphs.get(next)) {
+                    (Some(pen), _) => pen,
+                    (None, Some((glyph, _, _))) => (left, glyph.position().y),
+                    (None, None) => break,
+                };
+                let stop = left + (((x - left) / tab_width).floor() + 1.0) * tab_width;
+                for (glyph, _, _) in glyphs[next..]
+                    .iter_mut()
+                    .take_while(|(glyph, _, _)| glyph.position().y == baseline)
+                {
+                    let pos = glyph.position();
+                    *glyph = glyph
+                        .unpositioned()
+                        .clone()
+                        .positioned(point(pos.x + stop - x, pos.y));
                 }
-                Ok(BrushAction::ReDraw) => {
-                    break;
+                pen = Some((stop, baseline));
+            }
+            '\n' => pen = None,
+            c if c.is_control() => {}
+            _ => {
+                if let Some((glyph, _, _)) = glyphs.get(next) {
+                    let pos = glyph.position();
+                    let advance_width = glyph.unpositioned().h_metrics().advance_width;
+                    pen = Some((pos.x + advance_width, pos.y));
                 }
-                Err(BrushError::TextureTooSmall { suggested: (w, h) }) => {
-                    // Replace texture in asset storage. No handles have to be updated.
-                    tex_storage.replace(glyph_tex, create_glyph_texture(factory, *queue, w, h));
-                    tex = tex_storage
-                        .get(glyph_tex)
-                        .and_then(B::unwrap_texture)
-                        .unwrap();
-                    glyph_brush_ref.resize_texture(w, h);
+                next += 1;
+            }
+        }
+    }
+}
+
+/// Scales the distance of every baseline to the first one by `line_scale`, then moves the
+/// lines back in place for their vertical alignment.
+fn space_lines(
+    glyphs: &mut [(PositionedGlyph<'_>, [f32; 4], FontId)],
+    line_scale: f32,
+    v_align: VerticalAlign,
+) {
+    let (first, last) = match (glyphs.first(), glyphs.last()) {
+        (Some(first), Some(last)) => (first.0.position().y, last.0.position().y),
+        _ => return,
+    };
+    let growth = (last - first) * (line_scale - 1.0);
+    let shift = match v_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Center => -growth * 0.5,
+        VerticalAlign::Bottom => -growth,
+    };
+    for (glyph, _, _) in glyphs.iter_mut() {
+        let pos = glyph.position();
+        let y = first + (pos.y - first) * line_scale + shift;
+        *glyph = glyph.unpositioned().clone().positioned(point(pos.x, y));
+    }
+}
+
+/// Moves every line to `left` and widens the spaces between its words, so the line ends at
+/// `left + width`. The last line and lines without spaces keep their width.
+fn justify_lines<'font>(
+    glyphs: &mut [(PositionedGlyph<'font>, [f32; 4], FontId)],
+    is_space: impl Fn(&PositionedGlyph<'font>, FontId) -> bool,
+    left: f32,
+    width: f32,
+) {
+    let mut start = 0;
+    while start < glyphs.len() {
+        let baseline = glyphs[start].0.position().y;
+        let end = start
+            + glyphs[start..]
+                .iter()
+                .take_while(|(glyph, _, _)| glyph.position().y == baseline)
+                .count();
+        let last_line = end == glyphs.len();
+        let line = &mut glyphs[start..end];
+        // Spaces at the end of a wrapped line are not part of its width.
+        let words_end = line
+            .iter()
+            .rposition(|(glyph, _, font_id)| !is_space(glyph, *font_id))
+            .map_or(0, |last| last + 1);
+        if words_end > 0 {
+            let line_left = line[0].0.position().x;
+            let last = &line[words_end - 1].0;
+            let line_right = last.position().x + last.unpositioned().h_metrics().advance_width;
+            let spaces = line[..words_end]
+                .iter()
+                .filter(|(glyph, _, font_id)| is_space(glyph, *font_id))
+                .count();
+            let extra = if last_line || spaces == 0 {
+                0.0
+            } else {
+                (width - (line_right - line_left)).max(0.0) / spaces as f32
+            };
+            let mut offset = left - line_left;
+            for (index, (glyph, _, font_id)) in line.iter_mut().enumerate() {
+                let widened = index < words_end && is_space(glyph, *font_id);
+                let pos = glyph.position();
+                *glyph = glyph
+                    .unpositioned()
+                    .clone()
+                    .positioned(point(pos.x + offset, pos.y));
+                if widened {
+                    offset += extra;
                 }
             }
         }
+        start = end;
     }
+}
 
-    fn setup(&mut self, res: &mut Resources) {
-        Self::SystemData::setup(res);
-        res.insert(UiGlyphsResource { glyph_tex: None });
+/// Whether the glyphs of `ui_text` are rounded to whole pixels. Rotated and scaled text
+/// isn't, as rounding would make it jitter while the rotation or scale changes.
+fn snaps_to_pixels(ui_text: &UiText, transform: &UiTransform, ui_scale: f32) -> bool {
+    ui_text.pixel_snap && transform.rotation == 0.0 && ui_scale == 1.0
+}
+
+/// Rounds the origin of every glyph to whole pixels.
+fn snap_to_pixels(glyphs: &mut [(PositionedGlyph<'_>, [f32; 4], FontId)]) {
+    for (glyph, _, _) in glyphs.iter_mut() {
+        let pos = glyph.position();
+        *glyph = glyph
+            .unpositioned()
+            .clone()
+            .positioned(point(pos.x.round(), pos.y.round()));
+    }
+}
+
+/// Raises every glyph by its shift, and moves the lines apart where the raised and lowered
+/// glyphs reach past the ascent and descent of the rest of their line. The lines are then
+/// moved back in place for their vertical alignment.
+///
+/// Control characters are laid out without a glyph, so `shifts` holds one value for each of
+/// the other characters, in the order of the glyphs.
+fn shift_baselines<'font>(
+    glyphs: &mut [(PositionedGlyph<'font>, [f32; 4], FontId)],
+    shifts: impl Iterator<Item = f32>,
+    v_metrics: impl Fn(&PositionedGlyph<'font>, FontId) -> VMetrics,
+    v_align: VerticalAlign,
+) {
+    let shifts = shifts.collect::<Vec<_>>();
+    let shift_of = |i: usize| shifts.get(i).cloned().unwrap_or(0.0);
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < glyphs.len() {
+        let baseline = glyphs[start].0.position().y;
+        let end = start
+            + glyphs[start..]
+                .iter()
+                .take_while(|(glyph, _, _)| glyph.position().y == baseline)
+                .count();
+        lines.push(start..end);
+        start = end;
+    }
+
+    // How far each line is moved down, and all of them together.
+    let mut offsets = Vec::with_capacity(lines.len());
+    let mut growth = 0.0;
+    for line in &lines {
+        let metrics = line
+            .clone()
+            .map(|i| {
+                let (glyph, _, font_id) = &glyphs[i];
+                (v_metrics(glyph, *font_id), shift_of(i))
+            })
+            .collect::<Vec<_>>();
+        // Lines holding only shifted glyphs keep the spacing of their tallest glyph.
+        let unshifted = metrics.iter().any(|(_, shift)| *shift == 0.0);
+        let (ascent, descent) = metrics
+            .iter()
+            .filter(|(_, shift)| !unshifted || *shift == 0.0)
+            .fold((0.0f32, 0.0f32), |(ascent, descent), (m, _)| {
+                (ascent.max(m.ascent), descent.min(m.descent))
+            });
+        let (top, bottom) = metrics
+            .iter()
+            .fold((ascent, descent), |(top, bottom), (m, shift)| {
+                (top.max(m.ascent + shift), bottom.min(m.descent + shift))
+            });
+        growth += top - ascent;
+        offsets.push(growth);
+        growth += descent - bottom;
+    }
+
+    let align = match v_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Center => -growth * 0.5,
+        VerticalAlign::Bottom => -growth,
+    };
+    for (line, offset) in lines.into_iter().zip(offsets) {
+        for i in line {
+            let glyph = &mut glyphs[i].0;
+            let pos = glyph.position();
+            let y = pos.y + offset + align - shift_of(i);
+            *glyph = glyph.unpositioned().clone().positioned(point(pos.x, y));
+        }
+    }
+}
+
+/// Reverses the glyph order of every line, keeping each line where it was laid out.
+fn mirror_lines(glyphs: &mut [(PositionedGlyph<'_>, [f32; 4], FontId)]) {
+    let mut start = 0;
+    while start < glyphs.len() {
+        let baseline = glyphs[start].0.position().y;
+        let end = start
+            + glyphs[start..]
+                .iter()
+                .take_while(|(glyph, _, _)| glyph.position().y == baseline)
+                .count();
+        let line = &mut glyphs[start..end];
+        let (left, right) = line.iter().fold(
+            (std::f32::INFINITY, std::f32::NEG_INFINITY),
+            |(left, right), (glyph, _, _)| {
+                let x = glyph.position().x;
+                let advance = glyph.unpositioned().h_metrics().advance_width;
+                (left.min(x), right.max(x + advance))
+            },
+        );
+        for (glyph, _, _) in line.iter_mut() {
+            let x = glyph.position().x;
+            let advance = glyph.unpositioned().h_metrics().advance_width;
+            *glyph = glyph
+                .unpositioned()
+                .clone()
+                .positioned(point(left + right - x - advance, baseline));
+        }
+        start = end;
     }
 }
 
@@ -543,6 +2006,7 @@ fn create_glyph_texture<B: Backend>(
     queue: QueueId,
     w: u32,
     h: u32,
+    data: &[u8],
 ) -> Texture {
     use hal::format::{Component as C, Swizzle};
     TextureBuilder::new()
@@ -550,7 +2014,11 @@ fn create_glyph_texture<B: Backend>(
         .with_view_kind(hal::image::ViewKind::D2)
         .with_data_width(w)
         .with_data_height(h)
-        .with_data(vec![R8Srgb { repr: [0] }; (w * h) as _])
+        .with_data(
+            data.iter()
+                .map(|&repr| R8Srgb { repr: [repr] })
+                .collect::<Vec<_>>(),
+        )
         // TODO: This will not work properly on metal :(
         // need to add extra uniform and mask in shader for metal
         .with_swizzle(Swizzle(C::One, C::One, C::One, C::R))
@@ -567,6 +2035,21 @@ fn create_glyph_texture<B: Backend>(
         .expect("Failed to create glyph texture")
 }
 
+/// `string` with the input method composition of `editing` inserted at the cursor, and the
+/// byte range of the composition in it. `None` when nothing is being composed.
+fn with_composition(string: &str, editing: &TextEditing) -> Option<(String, Range<usize>)> {
+    if editing.composition.is_empty() {
+        return None;
+    }
+    let index = string
+        .grapheme_indices(true)
+        .nth(editing.cursor_position as usize)
+        .map_or(string.len(), |i| i.0);
+    let mut composed = string.to_string();
+    composed.insert_str(index, &editing.composition);
+    Some((composed, index..index + editing.composition.len()))
+}
+
 fn selection_span(editing: &TextEditing, string: &str) -> Option<(usize, usize)> {
     if editing.highlight_vector == 0 {
         return None;
@@ -589,16 +2072,1012 @@ fn selection_span(editing: &TextEditing, string: &str) -> Option<(usize, usize)>
     }
 }
 
+/// Splits `text` into contiguous byte ranges of the same color, or of any other style set by
+/// spans. Later spans override earlier ones, spans not on char boundaries are skipped.
+fn color_runs<T: Copy>(
+    text: &str,
+    base_color: T,
+    spans: &[(Range<usize>, T)],
+) -> Vec<(Range<usize>, T)> {
+    let valid_spans = spans
+        .iter()
+        .filter(|(range, _)| {
+            range.start < range.end
+                && range.end <= text.len()
+                && text.is_char_boundary(range.start)
+                && text.is_char_boundary(range.end)
+        })
+        .collect::<Vec<_>>();
+
+    let mut bounds = vec![0, text.len()];
+    for (range, _) in &valid_spans {
+        bounds.push(range.start);
+        bounds.push(range.end);
+    }
+    bounds.sort();
+    bounds.dedup();
+
+    bounds
+        .windows(2)
+        .map(|run| {
+            let color = valid_spans
+                .iter()
+                .rev()
+                .find(|(range, _)| range.start <= run[0] && run[1] <= range.end)
+                .map_or(base_color, |(_, color)| *color);
+            (run[0]..run[1], color)
+        })
+        .collect()
+}
+
 fn mul_blend(a: &[f32; 4], b: &[f32; 4]) -> [f32; 4] {
     [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
 }
 
 const PASSWORD_STR: &str = "••••••••••••••••";
 const PASSWORD_STR_GRAPHEMES: usize = 16; // 3 bytes per grapheme
-fn password_sections(len: usize) -> impl Iterator<Item = &'static str> {
+pub(crate) fn password_sections(len: usize) -> impl Iterator<Item = &'static str> {
     let full_chunks = len / PASSWORD_STR_GRAPHEMES;
     let last_len = len % PASSWORD_STR_GRAPHEMES;
     std::iter::repeat(PASSWORD_STR)
         .take(full_chunks)
         .chain(Some(&PASSWORD_STR[0..last_len * 3]))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextDirection;
+    use amethyst_core::ecs::{Builder, World};
+    use glyph_brush::{GlyphBrush, GlyphBrushBuilder, GlyphCruncher};
+
+    const FONT_SIZE: f32 = 16.0;
+
+    fn test_brush() -> (GlyphBrush<'static, (u32, UiArgs)>, Font<'static>) {
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let brush = GlyphBrushBuilder::using_font(font.clone()).build();
+        (brush, font)
+    }
+
+    fn text_width(font: &Font<'static>, text: &str) -> f32 {
+        let scale = Scale::uniform(FONT_SIZE);
+        text.chars()
+            .map(|c| font.glyph(c).scaled(scale).h_metrics().advance_width)
+            .sum()
+    }
+
+    fn line_count(
+        brush: &mut GlyphBrush<'static, (u32, UiArgs)>,
+        text: &str,
+        line_mode: LineMode,
+        width: f32,
+    ) -> usize {
+        let section = VariedSection {
+            bounds: (width, std::f32::INFINITY),
+            text: vec![SectionText {
+                text,
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+
+        let mut lines = brush
+            .glyphs_custom_layout(&section, &text_layout(&line_mode, &Anchor::TopLeft))
+            .map(|g| g.position().y)
+            .collect::<Vec<_>>();
+        lines.dedup();
+        lines.len()
+    }
+
+    #[test]
+    fn color_runs_apply_spans() {
+        const BASE: [f32; 4] = [1., 1., 1., 1.];
+        const RED: [f32; 4] = [1., 0., 0., 1.];
+        const BLUE: [f32; 4] = [0., 0., 1., 1.];
+
+        assert_eq!(
+            color_runs("hello world", BASE, &[(0..5, RED), (6..11, BLUE)]),
+            vec![(0..5, RED), (5..6, BASE), (6..11, BLUE)]
+        );
+        // Overlapping spans resolve to the last one.
+        assert_eq!(
+            color_runs("hello world", BASE, &[(0..8, RED), (4..11, BLUE)]),
+            vec![(0..4, RED), (4..8, BLUE), (8..11, BLUE)]
+        );
+        // "é" is two bytes long, so a span ending at byte 1 is invalid.
+        assert_eq!(
+            color_runs("éa", BASE, &[(0..1, RED), (2..3, BLUE)]),
+            vec![(0..2, BASE), (2..3, BLUE)]
+        );
+        assert_eq!(color_runs("", BASE, &[(0..1, RED)]), vec![]);
+    }
+
+    #[test]
+    fn missing_glyphs_fall_back_to_next_font() {
+        let (_, square) = test_brush();
+        assert!(has_glyph(&square, 'a'));
+        assert!(!has_glyph(&square, '漢'));
+
+        // The fallback stands in for a CJK font, which has every glyph of this text.
+        let primary_or_fallback = |font: usize, c: char| font == 1 || has_glyph(&square, c);
+        let text = "ab 漢字 c";
+        assert_eq!(
+            font_runs(text, 2, primary_or_fallback),
+            vec![(0..3, 0), (3..10, 1), (10..11, 0)]
+        );
+        assert_eq!(&text[3..10], "漢字 ");
+        // Without a fallback the primary font renders the missing glyph box.
+        assert_eq!(font_runs(text, 1, primary_or_fallback), vec![(0..11, 0)]);
+    }
+
+    #[test]
+    fn caret_follows_measured_width() {
+        let (mut brush, font) = test_brush();
+        let section = VariedSection {
+            screen_position: (10.0, 0.0),
+            text: vec![SectionText {
+                text: "Wave",
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let glyphs = brush
+            .glyphs_custom_layout(&section, &text_layout(&LineMode::Single, &Anchor::TopLeft))
+            .map(|g| CachedGlyph {
+                x: g.position().x,
+                y: -g.position().y,
+                advance_width: g.unpositioned().h_metrics().advance_width,
+            })
+            .collect::<Vec<_>>();
+
+        for index in 0..=4 {
+            let (x, _) = caret_position(&glyphs, index, false).unwrap();
+            let expected = 10.0 + text_width(&font, &"Wave"[..index]);
+            assert!((x - expected).abs() < 0.5);
+        }
+        assert_eq!(caret_position(&[], 0, false), None);
+    }
+
+    #[test]
+    fn selection_highlight_spans_selected_text() {
+        let (mut brush, font) = test_brush();
+        let section = VariedSection {
+            bounds: (text_width(&font, "select me") + 1.0, std::f32::INFINITY),
+            text: vec![SectionText {
+                text: "select me please",
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let glyphs = brush
+            .glyphs_custom_layout(&section, &text_layout(&LineMode::Wrap, &Anchor::TopLeft))
+            .map(|g| CachedGlyph {
+                x: g.position().x,
+                y: -g.position().y,
+                advance_width: g.unpositioned().h_metrics().advance_width,
+            })
+            .collect::<Vec<_>>();
+        let color = [0.0, 0.0, 1.0, 0.5];
+
+        // "lect" on the first line.
+        let quads = selection_quads(&glyphs[2..6], 0.0, 10.0, color);
+        assert_eq!(quads.len(), 1);
+        let [width, height]: [f32; 2] = quads[0].dimensions.into();
+        assert!((width - text_width(&font, "lect")).abs() < 0.5);
+        assert_eq!(height, 10.0);
+        let [x, _]: [f32; 2] = quads[0].coords.into();
+        assert!((x - (text_width(&font, "se") + width * 0.5)).abs() < 0.5);
+
+        // "me please" wraps, so it is highlighted on two lines.
+        let quads = selection_quads(&glyphs[7..], 0.0, 10.0, color);
+        assert_eq!(quads.len(), 2);
+        let [width, _]: [f32; 2] = quads[1].dimensions.into();
+        assert!((width - text_width(&font, "please")).abs() < 0.5);
+        assert_ne!(quads[0].coords, quads[1].coords);
+    }
+
+    #[test]
+    fn password_sections_mask_every_grapheme() {
+        for &len in &[0, 1, PASSWORD_STR_GRAPHEMES, PASSWORD_STR_GRAPHEMES * 2 + 3] {
+            let masked = password_sections(len).collect::<String>();
+            assert_eq!(masked.chars().count(), len);
+            assert!(masked.chars().all(|c| c == '•'));
+        }
+
+        let real = "pässwörd";
+        let masked = password_sections(real.graphemes(true).count()).collect::<String>();
+        assert_eq!(masked, "••••••••");
+    }
+
+    #[test]
+    fn section_position_follows_alignment() {
+        let transform = UiTransform::new(
+            "text".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            100.0,
+            50.0,
+            0.0,
+            40.0,
+            20.0,
+        );
+        let cases = [
+            (Anchor::TopLeft, (80.0, -60.0)),
+            (Anchor::TopMiddle, (100.0, -60.0)),
+            (Anchor::TopRight, (120.0, -60.0)),
+            (Anchor::MiddleLeft, (80.0, -50.0)),
+            (Anchor::Middle, (100.0, -50.0)),
+            (Anchor::MiddleRight, (120.0, -50.0)),
+            (Anchor::BottomLeft, (80.0, -40.0)),
+            (Anchor::BottomMiddle, (100.0, -40.0)),
+            (Anchor::BottomRight, (120.0, -40.0)),
+        ];
+        for (align, expected) in cases.iter() {
+            assert_eq!(
+                section_position(&transform, align),
+                *expected,
+                "{:?}",
+                align
+            );
+        }
+    }
+
+    #[test]
+    fn wrap_breaks_on_whitespace() {
+        let (mut brush, font) = test_brush();
+        let word = text_width(&font, "abc");
+
+        let text = "abc abc abc abc";
+        assert_eq!(line_count(&mut brush, text, LineMode::Wrap, 1.5 * word), 4);
+        assert_eq!(line_count(&mut brush, text, LineMode::Wrap, 2.5 * word), 2);
+        assert_eq!(
+            line_count(&mut brush, text, LineMode::Wrap, std::f32::INFINITY),
+            1
+        );
+        assert_eq!(line_count(&mut brush, text, LineMode::Single, word), 1);
+    }
+
+    #[test]
+    fn wrap_respects_hard_line_breaks() {
+        let (mut brush, _) = test_brush();
+        assert_eq!(
+            line_count(&mut brush, "ab\ncd", LineMode::Wrap, std::f32::INFINITY),
+            2
+        );
+    }
+
+    #[test]
+    fn reloaded_font_is_added_again() {
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let mut storage = AssetStorage::<FontAsset>::new();
+        let handle = storage.insert(FontAsset(font.clone()));
+        let version = storage.get_version(&handle);
+        let state = FontState::Ready(FontId(1), version.unwrap());
+        assert!(state.is_current(version));
+        assert!(!FontState::NotFound.is_current(version));
+        assert!(FontState::NotFound.is_current(None));
+
+        // Swap the backing font, the way hot reloading replaces the asset.
+        let reloaded = FontAsset::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        storage.replace(&handle, reloaded);
+        assert!(!state.is_current(storage.get_version(&handle)));
+        assert!(storage.get(&handle).is_some());
+    }
+
+    #[test]
+    fn rtl_text_starts_at_right_edge() {
+        let (mut brush, font) = test_brush();
+        let section = VariedSection {
+            screen_position: (200.0, 0.0),
+            bounds: (200.0, std::f32::INFINITY),
+            text: vec![SectionText {
+                text: "abc",
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let glyphs = |brush: &mut GlyphBrush<'static, (u32, UiArgs)>, rtl| {
+            let layout = TextLayout {
+                layout: text_layout(&LineMode::Single, &Anchor::MiddleRight),
+                rtl,
+                line_scale: None,
+                pixel_snap: false,
+                kerning: true,
+                justify: false,
+                tab_width: 0.0,
+                baseline_shifts: Vec::new(),
+            };
+            brush
+                .glyphs_custom_layout(&section, &layout)
+                .map(|g| g.position().x)
+                .collect::<Vec<_>>()
+        };
+
+        let width = text_width(&font, "a");
+        let rtl = glyphs(&mut brush, true);
+        assert!((rtl[0] + width - 200.0).abs() < 0.01);
+        assert!(rtl[0] > rtl[1] && rtl[1] > rtl[2]);
+        let ltr = glyphs(&mut brush, false);
+        assert!(ltr[0] < ltr[1] && ltr[1] < ltr[2]);
+        assert!((ltr[0] - rtl[2]).abs() < 0.01);
+    }
+
+    #[test]
+    fn pixel_snap_rounds_glyph_origins() {
+        let (mut brush, _) = test_brush();
+        let section = VariedSection {
+            screen_position: (10.3, 20.6),
+            bounds: (200.0, std::f32::INFINITY),
+            text: vec![SectionText {
+                text: "abc",
+                scale: Scale::uniform(FONT_SIZE + 0.5),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let mut origins = |pixel_snap| {
+            let layout = TextLayout {
+                layout: text_layout(&LineMode::Single, &Anchor::TopLeft),
+                rtl: false,
+                line_scale: None,
+                pixel_snap,
+                kerning: true,
+                justify: false,
+                tab_width: 0.0,
+                baseline_shifts: Vec::new(),
+            };
+            brush
+                .glyphs_custom_layout(&section, &layout)
+                .map(|g| g.position())
+                .collect::<Vec<_>>()
+        };
+
+        let snapped = origins(true);
+        assert_eq!(snapped.len(), 3);
+        assert!(snapped
+            .iter()
+            .all(|pos| pos.x.fract() == 0.0 && pos.y.fract() == 0.0));
+        let unsnapped = origins(false);
+        // The section starts between pixels.
+        assert_eq!(unsnapped[0].x, 10.3);
+    }
+
+    #[test]
+    fn pixel_snap_skips_rotated_and_scaled_text() {
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(test_brush().1));
+        let text = UiText::new(font, "abc".to_string(), [1.0; 4], FONT_SIZE);
+        let mut transform = UiTransform::new(
+            "label".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            100.0,
+            20.0,
+        );
+        assert!(snaps_to_pixels(&text, &transform, 1.0));
+        assert!(!snaps_to_pixels(&text, &transform, 1.5));
+        assert!(!snaps_to_pixels(&text, &transform, 2.0));
+        transform.rotation = 0.5;
+        assert!(!snaps_to_pixels(&text, &transform, 1.0));
+    }
+
+    #[test]
+    fn glyphs_of_rotated_text_are_rotated() {
+        let mut transform = UiTransform::new(
+            "label".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            100.0,
+            20.0,
+        );
+        let raster = CachedRaster {
+            offset: (0, -8),
+            tex_rect: Rect {
+                min: point(0, 0),
+                max: point(8, 8),
+            },
+        };
+        let bounds = Rect {
+            min: point(-50.0, -10.0),
+            max: point(50.0, 10.0),
+        };
+        let quad = |transform: &UiTransform| {
+            let quad = glyph_quad(
+                raster,
+                point(0.0, 0.0),
+                (64, 64),
+                bounds,
+                [1.0; 4],
+                quad_rotation(transform),
+            );
+            let rotation: [f32; 2] = quad.rotation.into();
+            rotation
+        };
+
+        assert_eq!(quad(&transform), [1.0, 0.0]);
+        transform.rotation = std::f32::consts::FRAC_PI_2;
+        let [cos, sin] = quad(&transform);
+        assert!(cos.abs() < 1e-6);
+        assert!((sin - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unsnapped_glyphs_keep_their_fractional_position() {
+        let (_, font) = test_brush();
+        let glyph = font
+            .glyph('A')
+            .scaled(Scale::uniform(FONT_SIZE))
+            .positioned(point(10.37, 20.0));
+        let (width, height) = (64, 64);
+        let quad_left = |pixel_snap| {
+            let (subpixel, origin) = subpixel_position(glyph.position(), pixel_snap);
+            let key = GlyphKey::new(FontId(0), 'A', glyph.scale(), subpixel);
+            let raster = rasterize(&glyph, key.subpixel_offset(), RasterMode::Smooth);
+            let cached = CachedRaster {
+                offset: raster.offset,
+                tex_rect: Rect {
+                    min: point(0, 0),
+                    max: point(raster.width, raster.height),
+                },
+            };
+            let bounds = Rect {
+                min: point(0.0, 0.0),
+                max: point(100.0, 100.0),
+            };
+            let quad = glyph_quad(
+                cached,
+                origin,
+                (width, height),
+                bounds,
+                [1.0; 4],
+                [1.0, 0.0],
+            );
+            let [x, _]: [f32; 2] = quad.coords.into();
+            let [w, _]: [f32; 2] = quad.dimensions.into();
+            (subpixel, x - w * 0.5)
+        };
+
+        let (subpixel, left) = quad_left(false);
+        assert_eq!(subpixel, (4, 0));
+        // Rasterized 0.4 pixels into its pixel, which starts 0.4 pixels before the origin.
+        assert!((left - left.floor() - 0.97).abs() < 1e-4);
+
+        let (subpixel, left) = quad_left(true);
+        assert_eq!(subpixel, (0, 0));
+        assert_eq!(left, left.round());
+
+        // Glyphs laid out close to the same subpixel offset share their raster.
+        assert_eq!(
+            subpixel_position(point(3.41, 0.0), false).0,
+            subpixel_position(point(10.37, 0.0), false).0
+        );
+        assert_eq!(subpixel_position(point(2.98, 0.5), false).0, (0, 5));
+    }
+
+    #[test]
+    fn full_justify_stretches_all_but_last_line() {
+        let (mut brush, font) = test_brush();
+        let width = text_width(&font, "aa bb cc") + text_width(&font, "d");
+        let section = VariedSection {
+            bounds: (width, std::f32::INFINITY),
+            text: vec![SectionText {
+                text: "aa bb cc dd ee",
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let layout = TextLayout {
+            layout: text_layout(&LineMode::Wrap, &Anchor::TopLeft),
+            rtl: false,
+            line_scale: None,
+            pixel_snap: false,
+            kerning: true,
+            justify: true,
+            tab_width: 0.0,
+            baseline_shifts: Vec::new(),
+        };
+        let space = font.glyph(' ').id();
+        let advance = |c| text_width(&font, c);
+        let words = brush
+            .glyphs_custom_layout(&section, &layout)
+            .filter(|glyph| glyph.id() != space)
+            .map(|glyph| glyph.position())
+            .collect::<Vec<_>>();
+        assert_eq!(words.len(), 10);
+
+        // "aa bb cc" fills the line, the widened spaces take the width of the missing "d".
+        let first_line = &words[..6];
+        assert!(first_line.iter().all(|pos| pos.y == words[0].y));
+        assert!((first_line[0].x).abs() < 0.01);
+        assert!((first_line[5].x + advance("c") - width).abs() < 0.01);
+        let gap = first_line[2].x - (first_line[1].x + advance("a"));
+        assert!((gap - advance(" ") - advance("d") / 2.0).abs() < 0.01);
+
+        // The last line keeps its spaces.
+        let last_line = &words[6..];
+        assert!(last_line.iter().all(|pos| pos.y > words[0].y));
+        assert!((last_line[0].x).abs() < 0.01);
+        assert!((last_line[2].x - advance("dd ")).abs() < 0.01);
+    }
+
+    #[test]
+    fn unchanged_label_is_not_laid_out_again() {
+        let entity = World::new().create_entity().build();
+        let layout = TextLayout {
+            layout: text_layout(&LineMode::Single, &Anchor::Middle),
+            rtl: false,
+            line_scale: None,
+            pixel_snap: true,
+            kerning: true,
+            justify: false,
+            tab_width: 0.0,
+            baseline_shifts: Vec::new(),
+        };
+        let label = |text, width, color| VariedSection {
+            bounds: (width, 20.0),
+            text: vec![SectionText {
+                text,
+                scale: Scale::uniform(FONT_SIZE),
+                color,
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let mut cache = LayoutCache::default();
+        let mut frame = |section: VariedSection<'_>| {
+            cache.update(entity, layout_key(&section, &layout, Some(0)), Vec::new)
+        };
+
+        assert!(frame(label("Score: 10", 100.0, [1.0; 4])));
+        assert!(!frame(label("Score: 10", 100.0, [1.0; 4])));
+        // Recoloring keeps the glyph positions.
+        assert!(!frame(label("Score: 10", 100.0, [0.5; 4])));
+        assert!(frame(label("Score: 11", 100.0, [0.5; 4])));
+        // Resizing the transform changes the bounds.
+        assert!(frame(label("Score: 11", 80.0, [0.5; 4])));
+        assert!(!frame(label("Score: 11", 80.0, [0.5; 4])));
+    }
+
+    #[test]
+    fn auto_direction_uses_first_strong_character() {
+        assert!(TextDirection::Auto.is_rtl("12 שלום world"));
+        assert!(TextDirection::Auto.is_rtl("مرحبا"));
+        assert!(!TextDirection::Auto.is_rtl("- hello שלום"));
+        assert!(!TextDirection::Auto.is_rtl("42"));
+        assert!(TextDirection::Rtl.is_rtl("hello"));
+    }
+
+    #[test]
+    fn line_height_spaces_baselines() {
+        let (mut brush, font) = test_brush();
+        let section = VariedSection {
+            bounds: (200.0, std::f32::INFINITY),
+            text: vec![SectionText {
+                text: "ab\ncd",
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let mut baselines = |line_height: Option<f32>| {
+            let layout = TextLayout {
+                layout: text_layout(&LineMode::Wrap, &Anchor::TopLeft),
+                rtl: false,
+                line_scale: line_height
+                    .map(|line_height| line_scale(&font, Scale::uniform(FONT_SIZE), line_height)),
+                pixel_snap: false,
+                kerning: true,
+                justify: false,
+                tab_width: 0.0,
+                baseline_shifts: Vec::new(),
+            };
+            let mut lines = brush
+                .glyphs_custom_layout(&section, &layout)
+                .map(|g| g.position().y)
+                .collect::<Vec<_>>();
+            lines.dedup();
+            lines
+        };
+
+        let v_metrics = font.v_metrics(Scale::uniform(FONT_SIZE));
+        let height = v_metrics.ascent - v_metrics.descent;
+        let default = baselines(None);
+        assert_eq!(default.len(), 2);
+        assert!((default[1] - default[0] - height - v_metrics.line_gap).abs() < 0.01);
+
+        let spaced = baselines(Some(1.5));
+        assert_eq!(spaced[0], default[0]);
+        assert!((spaced[1] - spaced[0] - 1.5 * height).abs() < 0.01);
+
+        // Zero and negative line heights are clamped instead of stacking the lines.
+        let clamped = baselines(Some(-1.0));
+        assert!((clamped[1] - clamped[0] - MIN_LINE_HEIGHT * height).abs() < 0.01);
+    }
+
+    #[test]
+    fn grapheme_glyphs_merge_clusters() {
+        let glyph = |x| CachedGlyph {
+            x,
+            y: 5.0,
+            advance_width: 10.0,
+        };
+        // "a", a flag of two regional indicators, then "e" with a combining acute accent.
+        let text = "a\u{1F1EB}\u{1F1F7}e\u{301}";
+        let glyphs = (0..5).map(|i| glyph(i as f32 * 10.0));
+        let merged = grapheme_glyphs(text, glyphs)
+            .into_iter()
+            .map(|g| (g.x, g.advance_width))
+            .collect::<Vec<_>>();
+        assert_eq!(merged, vec![(0.0, 10.0), (10.0, 20.0), (30.0, 20.0)]);
+    }
+
+    #[test]
+    fn ellipsis_fits_in_width() {
+        let font = FontAsset::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let width_of = |text: &str| font.measure(text, FONT_SIZE).width;
+        let text = "A label much too long for its box";
+        let max_width = width_of("A label much");
+
+        let shortened = ellipsize(text, max_width, width_of);
+        assert!(shortened.ends_with(ELLIPSIS));
+        assert!(text.starts_with(shortened.trim_end_matches(ELLIPSIS)));
+        assert!(width_of(&shortened) <= max_width);
+        // One more char wouldn't have fit.
+        let kept = shortened.trim_end_matches(ELLIPSIS).chars().count();
+        let longer = text.chars().take(kept + 1).collect::<String>() + ELLIPSIS;
+        assert!(width_of(&longer) > max_width);
+
+        assert_eq!(ellipsize("short", max_width, width_of), "short");
+        assert_eq!(
+            ellipsize("héllo wörld", width_of("hé") + width_of(ELLIPSIS), width_of),
+            "hé\u{2026}"
+        );
+        assert_eq!(ellipsize(text, 0.0, width_of), "");
+    }
+
+    #[test]
+    fn spans_are_cut_with_the_text() {
+        let (red, blue, white) = ([1., 0., 0., 1.], [0., 0., 1., 1.], [1.; 4]);
+        let text = "hello world";
+        let display = format!("hello wo{}", ELLIPSIS);
+        let cut = Truncation::of(text, &display).unwrap();
+        // "wor" crosses the cut and "ld" was cut whole, so the ellipsis keeps the base color.
+        let spans = vec![(0..2, red), (7..10, blue), (9..11, red)]
+            .into_iter()
+            .map(|(range, color)| (cut.map(range), color))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            color_runs(&display, white, &spans),
+            vec![(0..2, red), (2..7, white), (7..8, blue), (8..11, white)]
+        );
+        // A span over everything that was cut colors the ellipsis standing for it.
+        assert_eq!(cut.map(6..11), 6..display.len());
+        assert_eq!(Truncation::of(text, text), None);
+        assert_eq!(Truncation::of(text, "").unwrap().map(0..11), 0..0);
+    }
+
+    #[test]
+    fn middle_ellipsis_keeps_head_and_tail() {
+        let font = FontAsset::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let width_of = |text: &str| font.measure(text, FONT_SIZE).width;
+        let path = "C:\\Users\\jöel\\Documents\\projects\\amethyst\\file.txt";
+        let max_width = width_of("C:\\Users\\jöel\\Documents");
+
+        let shortened = middle_ellipsize(path, max_width, 3, 9, width_of);
+        assert_eq!(shortened, "C:\\\u{2026}\\file.txt");
+        assert!(width_of(&shortened) <= max_width);
+
+        // Leading chars are dropped first when head and tail don't fit, respecting the
+        // multi-byte "ö".
+        let shortened = middle_ellipsize(path, width_of("C:\\\u{2026}\\file.txt"), 13, 9, width_of);
+        assert_eq!(shortened, "C:\\\u{2026}\\file.txt");
+        let shortened = middle_ellipsize(path, width_of("\u{2026}file.txt"), 3, 9, width_of);
+        assert_eq!(shortened, "\u{2026}file.txt");
+
+        assert_eq!(
+            middle_ellipsize("short", max_width, 3, 9, width_of),
+            "short"
+        );
+        assert_eq!(middle_ellipsize(path, 0.0, 3, 9, width_of), "");
+    }
+
+    #[test]
+    fn spans_are_cut_with_the_middle_of_the_text() {
+        let (red, blue, white) = ([1., 0., 0., 1.], [0., 0., 1., 1.], [1.; 4]);
+        let text = "abcdefxyz";
+        let display = format!("ab{}yz", ELLIPSIS);
+        let cut = Truncation::of(text, &display).unwrap();
+        assert_eq!(
+            cut,
+            Truncation {
+                head: 2,
+                ellipsis: ELLIPSIS.len(),
+                tail: 7
+            }
+        );
+        // "bcd" and "fxy" cross the cut on either side, "de" was cut whole.
+        let spans = vec![(1..4, red), (5..8, blue), (3..5, red)]
+            .into_iter()
+            .map(|(range, color)| (cut.map(range), color))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            color_runs(&display, white, &spans),
+            vec![
+                (0..1, white),
+                (1..2, red),
+                (2..5, white),
+                (5..6, blue),
+                (6..7, white)
+            ]
+        );
+        // A span over the whole cut colors the ellipsis too.
+        assert_eq!(cut.map(1..8), 1..6);
+        // Ellipses of the text itself aren't taken for the one added.
+        let cut = Truncation::of("a\u{2026}bcd\u{2026}e", "a\u{2026}b\u{2026}e").unwrap();
+        assert_eq!((cut.head, cut.tail), (5, 10));
+    }
+
+    #[test]
+    fn sharp_glyphs_have_hard_edges() {
+        let (_, font) = test_brush();
+        // Off the pixel grid, so the edges of the glyph only partly cover their pixels.
+        let glyph = font
+            .glyph('A')
+            .scaled(Scale::uniform(FONT_SIZE))
+            .positioned(point(0.5, 0.5));
+        let bounds = glyph.pixel_bounding_box().unwrap();
+        let mut smooth = vec![0; (bounds.width() * bounds.height()) as usize];
+        glyph.draw(|x, y, v| {
+            smooth[(y * bounds.width() as u32 + x) as usize] = (v * 255.0) as u8;
+        });
+        let mut sharp = smooth.clone();
+        sharpen(&mut sharp);
+
+        assert!(smooth.iter().any(|&alpha| alpha > 0 && alpha < 255));
+        assert!(sharp.iter().all(|&alpha| alpha == 0 || alpha == 255));
+        assert_ne!(smooth, sharp);
+        assert!(sharp.iter().any(|&alpha| alpha == 255));
+    }
+
+    #[test]
+    fn tab_moves_pen_to_next_stop() {
+        let (mut brush, font) = test_brush();
+        let space = text_width(&font, " ");
+        let layout = TextLayout {
+            layout: text_layout(&LineMode::Wrap, &Anchor::TopLeft),
+            rtl: false,
+            line_scale: None,
+            pixel_snap: false,
+            kerning: true,
+            justify: false,
+            tab_width: 4.0 * space,
+            baseline_shifts: Vec::new(),
+        };
+        let mut origins = |text| {
+            let section = VariedSection {
+                screen_position: (10.0, 0.0),
+                bounds: (400.0, std::f32::INFINITY),
+                text: vec![SectionText {
+                    text,
+                    scale: Scale::uniform(FONT_SIZE),
+                    ..SectionText::default()
+                }],
+                ..VariedSection::default()
+            };
+            brush
+                .glyphs_custom_layout(&section, &layout)
+                .map(|g| g.position().x - 10.0)
+                .collect::<Vec<_>>()
+        };
+
+        let tab = 4.0 * space;
+        let next_stop = |text| ((text_width(&font, text) / tab).floor() + 1.0) * tab;
+        assert!((origins("a\tb")[1] - next_stop("a")).abs() < 0.01);
+        assert!((origins("\t\tb")[0] - 2.0 * tab).abs() < 0.01);
+        assert!((origins("abcdefgh\tb")[8] - next_stop("abcdefgh")).abs() < 0.01);
+        // Lines start over at the left edge, and leading spaces are kept.
+        assert!((origins("abcdef\n\tb")[6] - tab).abs() < 0.01);
+        assert!((origins("  b")[2] - 2.0 * space).abs() < 0.01);
+    }
+
+    #[test]
+    fn superscript_is_smaller_and_raised() {
+        let (mut brush, font) = test_brush();
+        let (script_scale, rise) = baseline_metrics(TextBaseline::Superscript, FONT_SIZE);
+        let section = VariedSection {
+            bounds: (400.0, std::f32::INFINITY),
+            text: vec![
+                SectionText {
+                    text: "x",
+                    scale: Scale::uniform(FONT_SIZE),
+                    ..SectionText::default()
+                },
+                SectionText {
+                    text: "2",
+                    scale: script_scale,
+                    ..SectionText::default()
+                },
+            ],
+            ..VariedSection::default()
+        };
+        let mut glyphs = |baseline_shifts| {
+            let layout = TextLayout {
+                layout: text_layout(&LineMode::Wrap, &Anchor::TopLeft),
+                rtl: false,
+                line_scale: None,
+                pixel_snap: false,
+                kerning: true,
+                justify: false,
+                tab_width: 0.0,
+                baseline_shifts,
+            };
+            brush
+                .glyphs_custom_layout(&section, &layout)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let on_baseline = glyphs(Vec::new());
+        let raised = glyphs(vec![0.0, rise]);
+
+        let (x, two) = (&raised[0], &raised[1]);
+        assert!(two.scale().y < x.scale().y);
+        assert!((x.position().y - two.position().y - rise).abs() < 0.01);
+        // The line moves down so the top of the "2" stays inside the bounds.
+        let top = two.position().y - font.v_metrics(two.scale()).ascent;
+        assert!(top >= -0.01);
+        assert!(x.position().y > on_baseline[0].position().y);
+        assert_eq!(x.position().x, on_baseline[0].position().x);
+    }
+
+    #[test]
+    fn kerning_can_be_turned_off() {
+        // A copy of square.ttf with "AV" and "VA" added to its kerning table.
+        let font = Font::from_bytes(include_bytes!("./font/kerned.ttf").to_vec())
+            .expect("Unable to parse kerned.ttf");
+        let mut brush = GlyphBrushBuilder::using_font(font.clone()).build();
+        let section = VariedSection {
+            screen_position: (200.0, 0.0),
+            text: vec![SectionText {
+                text: "AV",
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let mut edges = |align, kerning| {
+            let layout = TextLayout {
+                layout: text_layout(&LineMode::Single, &align),
+                rtl: false,
+                line_scale: None,
+                pixel_snap: false,
+                kerning,
+                justify: false,
+                tab_width: 0.0,
+                baseline_shifts: Vec::new(),
+            };
+            let glyphs = brush
+                .glyphs_custom_layout(&section, &layout)
+                .cloned()
+                .collect::<Vec<_>>();
+            let last = glyphs.last().unwrap();
+            (
+                glyphs[0].position().x,
+                last.position().x + last.unpositioned().h_metrics().advance_width,
+            )
+        };
+        let width = |(left, right): (f32, f32)| right - left;
+        let kerned = edges(Anchor::TopLeft, true);
+        let unkerned = edges(Anchor::TopLeft, false);
+        let kerning = font.pair_kerning(
+            Scale::uniform(FONT_SIZE),
+            font.glyph('A').id(),
+            font.glyph('V').id(),
+        );
+        assert!(kerning < 0.0);
+        assert!(width(unkerned) > width(kerned));
+        assert!((width(unkerned) - text_width(&font, "AV")).abs() < 0.01);
+        assert!((width(kerned) - text_width(&font, "AV") - kerning).abs() < 0.01);
+        assert_eq!(unkerned.0, kerned.0);
+
+        // Right aligned text grows to the left.
+        let kerned = edges(Anchor::TopRight, true);
+        let unkerned = edges(Anchor::TopRight, false);
+        assert!((unkerned.1 - kerned.1).abs() < 0.01);
+        assert!(unkerned.0 < kerned.0);
+    }
+
+    #[test]
+    fn unrevealed_glyphs_are_laid_out_but_not_drawn() {
+        let (mut brush, font) = test_brush();
+        let text = "the quick brown fox jumps";
+        let section = VariedSection {
+            bounds: (text_width(&font, "the quick brown"), std::f32::INFINITY),
+            text: vec![SectionText {
+                text,
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let layout = TextLayout {
+            layout: text_layout(&LineMode::Wrap, &Anchor::TopLeft),
+            rtl: false,
+            line_scale: None,
+            pixel_snap: false,
+            kerning: true,
+            justify: true,
+            tab_width: 0.0,
+            baseline_shifts: Vec::new(),
+        };
+        let laid_out = brush
+            .glyphs_custom_layout(&section, &layout)
+            .map(|glyph| (glyph.clone(), FontId(0)))
+            .collect::<Vec<_>>();
+        let drawn = |visible| {
+            drawn_glyphs(&laid_out, &section.text, visible)
+                .map(|(glyph, _, c, _)| (glyph.position(), c))
+                .collect::<Vec<_>>()
+        };
+        let all = drawn(None);
+        let revealed = drawn(Some(5));
+        assert_eq!(all.len(), text.chars().count());
+        // The revealed glyphs are where they are with the whole text, justified to its lines.
+        assert_eq!(revealed.len(), 5);
+        assert_eq!(revealed[..], all[..5]);
+        let revealed_text = revealed.iter().map(|(_, c)| c).collect::<String>();
+        assert_eq!(revealed_text, "the q");
+    }
+
+    #[test]
+    fn max_lines_ends_last_line_with_ellipsis() {
+        let (mut brush, font) = test_brush();
+        let text = "the quick brown fox jumps over the lazy dog again and again";
+        let width = text_width(&font, "the quick brown") + 1.0;
+        let section = VariedSection {
+            bounds: (width, std::f32::INFINITY),
+            text: vec![SectionText {
+                text,
+                scale: Scale::uniform(FONT_SIZE),
+                ..SectionText::default()
+            }],
+            ..VariedSection::default()
+        };
+        let starts = line_starts(
+            text,
+            brush
+                .glyphs_custom_layout(&section, &text_layout(&LineMode::Wrap, &Anchor::TopLeft))
+                .map(|glyph| glyph.position().y),
+        );
+        assert!(starts.len() > 2);
+        let width_of = |text: &str| text_width(&font, text);
+
+        let limited = limit_lines(text, &starts, 2, width, width_of).unwrap();
+        assert!(limited.ends_with(ELLIPSIS));
+        assert!(limited.starts_with("the quick brown"));
+        assert_eq!(line_count(&mut brush, &limited, LineMode::Wrap, width), 2);
+
+        assert_eq!(
+            limit_lines(text, &starts, 0, width, width_of),
+            Some(String::new())
+        );
+        assert_eq!(
+            limit_lines(text, &starts, starts.len(), width, width_of),
+            None
+        );
+    }
+}
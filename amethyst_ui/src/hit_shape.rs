@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use amethyst_core::ecs::prelude::{Component, DenseVecStorage};
+
+use crate::UiTransform;
+
+/// The alpha channel of an image, kept on the CPU for hit-testing with `UiHitShape::Mask`.
+///
+/// Textures only live on the GPU once loaded, so the mask has to be built from the image
+/// data separately, usually from the same file as the `UiImage` of the element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitMask {
+    width: usize,
+    height: usize,
+    alpha: Vec<u8>,
+}
+
+impl HitMask {
+    /// Creates a mask from the alpha values of an image, row by row from the top left.
+    ///
+    /// Panics if `alpha` doesn't hold `width * height` values.
+    pub fn new(width: usize, height: usize, alpha: Vec<u8>) -> Self {
+        assert_eq!(
+            alpha.len(),
+            width * height,
+            "A {}x{} mask needs {} alpha values",
+            width,
+            height,
+            width * height
+        );
+        HitMask {
+            width,
+            height,
+            alpha,
+        }
+    }
+
+    /// Creates a mask from the alpha channel of RGBA8 pixels, row by row from the top left.
+    pub fn from_rgba(width: usize, height: usize, pixels: &[u8]) -> Self {
+        HitMask::new(
+            width,
+            height,
+            pixels
+                .chunks(4)
+                .filter_map(|pixel| pixel.get(3).cloned())
+                .collect(),
+        )
+    }
+
+    /// The alpha, from 0 to 1, of the pixel at `u` and `v`, both 0 to 1 from the top left.
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+        let column = ((u * self.width as f32) as usize).min(self.width - 1);
+        let row = ((v * self.height as f32) as usize).min(self.height - 1);
+        f32::from(self.alpha[row * self.width + column]) / 255.0
+    }
+}
+
+/// Replaces the rectangle of a `UiTransform` when checking if the cursor is over the element,
+/// for round buttons and irregular icons.
+///
+/// Shapes only ever make the hit area smaller, positions outside of the transform never hit.
+/// Rotation of the transform is taken into account.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiHitShape {
+    /// The whole rectangle of the transform, the same as having no `UiHitShape`.
+    Rect,
+    /// A circle around the center of the transform.
+    Circle {
+        /// The radius of the circle, in screen pixels like the laid out size of the transform.
+        radius: f32,
+    },
+    /// The pixels of an image stretched over the transform, with an alpha of at least
+    /// `threshold`.
+    Mask {
+        /// The alpha of the image drawn on the element.
+        texture: Arc<HitMask>,
+        /// The lowest alpha, from 0 to 1, that registers a hit.
+        threshold: f32,
+    },
+}
+
+impl UiHitShape {
+    /// Checks if the global position is inside this shape, placed on `transform`.
+    pub fn contains(&self, transform: &UiTransform, x: f32, y: f32) -> bool {
+        if !transform.position_inside(x, y) {
+            return false;
+        }
+        let (x, y) = transform.unrotate(x, y);
+        match self {
+            UiHitShape::Rect => true,
+            UiHitShape::Circle { radius } => {
+                let (dx, dy) = (x - transform.pixel_x, y - transform.pixel_y);
+                dx * dx + dy * dy <= radius * radius
+            }
+            UiHitShape::Mask { texture, threshold } => {
+                let u = (x - transform.pixel_x) / transform.pixel_width + 0.5;
+                let v = (transform.pixel_y - y) / transform.pixel_height + 0.5;
+                texture.sample(u, v) >= *threshold
+            }
+        }
+    }
+}
+
+impl Component for UiHitShape {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Checks if the position is over the element, using its `UiHitShape` if it has one.
+pub(crate) fn hit(transform: &UiTransform, shape: Option<&UiHitShape>, (x, y): (f32, f32)) -> bool {
+    match shape {
+        Some(shape) => shape.contains(transform, x, y),
+        None => transform.position_inside(x, y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Anchor;
+
+    fn button() -> UiTransform {
+        UiTransform::new(
+            "button".to_string(),
+            Anchor::BottomLeft,
+            Anchor::Middle,
+            50.0,
+            50.0,
+            0.0,
+            40.0,
+            40.0,
+        )
+    }
+
+    #[test]
+    fn mask_rejects_transparent_pixels() {
+        // Opaque on the left half, transparent on the right half.
+        let mask = HitMask::new(2, 2, vec![255, 0, 255, 0]);
+        let shape = UiHitShape::Mask {
+            texture: Arc::new(mask),
+            threshold: 0.5,
+        };
+        let transform = button();
+        assert!(shape.contains(&transform, 40.0, 60.0));
+        assert!(shape.contains(&transform, 35.0, 35.0));
+        assert!(!shape.contains(&transform, 60.0, 60.0));
+        assert!(!shape.contains(&transform, 65.0, 35.0));
+        assert!(!shape.contains(&transform, 20.0, 50.0));
+    }
+
+    #[test]
+    fn mask_from_rgba_keeps_alpha() {
+        let mask = HitMask::from_rgba(2, 1, &[255, 255, 255, 0, 0, 0, 0, 128]);
+        assert_eq!(mask.sample(0.0, 0.0), 0.0);
+        assert_eq!(mask.sample(0.9, 0.5), 128.0 / 255.0);
+    }
+
+    #[test]
+    fn circle_hits_inside_radius_only() {
+        let shape = UiHitShape::Circle { radius: 20.0 };
+        let transform = button();
+        assert!(shape.contains(&transform, 50.0, 50.0));
+        assert!(shape.contains(&transform, 65.0, 60.0));
+        // Inside the rectangle, but in its corner.
+        assert!(!shape.contains(&transform, 68.0, 68.0));
+        assert!(hit(&transform, None, (68.0, 68.0)));
+    }
+}
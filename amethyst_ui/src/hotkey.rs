@@ -0,0 +1,240 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use amethyst_core::{
+    ecs::prelude::{Join, Read, ReadStorage, Resources, System, SystemData, Write},
+    shrev::{EventChannel, ReaderId},
+};
+use winit::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent};
+
+use crate::{Selected, TextEditing};
+
+/// A key with the modifiers that have to be held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    /// The key to press.
+    pub key: VirtualKeyCode,
+    /// The modifiers to hold. Only matches when exactly these modifiers are held.
+    pub modifiers: ModifiersState,
+}
+
+impl Hotkey {
+    /// Creates a hotkey for the key without any modifiers.
+    pub fn new(key: VirtualKeyCode) -> Self {
+        Hotkey {
+            key,
+            modifiers: ModifiersState::default(),
+        }
+    }
+
+    /// Sets the modifiers that have to be held with the key.
+    pub fn with_modifiers(mut self, modifiers: ModifiersState) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+}
+
+impl From<VirtualKeyCode> for Hotkey {
+    fn from(key: VirtualKeyCode) -> Self {
+        Hotkey::new(key)
+    }
+}
+
+/// Resource binding hotkeys to user defined UI actions, like closing a menu on Escape.
+///
+/// Bindings can be changed at any time. The `HotkeySystem` writes the action of each pressed
+/// hotkey to the `EventChannel<A>` resource.
+#[derive(Debug, Clone)]
+pub struct UiHotkeys<A> {
+    bindings: HashMap<Hotkey, A>,
+}
+
+impl<A> Default for UiHotkeys<A> {
+    fn default() -> Self {
+        UiHotkeys {
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+impl<A> UiHotkeys<A> {
+    /// Binds the hotkey to the action, returning the action it was bound to before.
+    pub fn bind<K: Into<Hotkey>>(&mut self, hotkey: K, action: A) -> Option<A> {
+        self.bindings.insert(hotkey.into(), action)
+    }
+
+    /// Removes the binding of the hotkey, returning its action.
+    pub fn unbind<K: Into<Hotkey>>(&mut self, hotkey: K) -> Option<A> {
+        self.bindings.remove(&hotkey.into())
+    }
+
+    /// Returns the action bound to the hotkey.
+    pub fn action<K: Into<Hotkey>>(&self, hotkey: K) -> Option<&A> {
+        self.bindings.get(&hotkey.into())
+    }
+
+    /// Returns the hotkeys bound to the action.
+    pub fn hotkeys<'a>(&'a self, action: &'a A) -> impl Iterator<Item = Hotkey> + 'a
+    where
+        A: PartialEq,
+    {
+        self.bindings
+            .iter()
+            .filter(move |(_, bound)| *bound == action)
+            .map(|(hotkey, _)| *hotkey)
+    }
+}
+
+/// System writing the actions of pressed `UiHotkeys` to the `EventChannel<A>` resource.
+///
+/// Hotkeys are ignored while a text field is focused, so typing into it can't trigger them.
+/// This system isn't part of the `UiBundle`, add it once for each action type.
+pub struct HotkeySystem<A> {
+    reader: Option<ReaderId<Event>>,
+    _marker: PhantomData<A>,
+}
+
+impl<A> HotkeySystem<A> {
+    /// Creates a new instance of this system
+    pub fn new() -> Self {
+        HotkeySystem {
+            reader: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A> Default for HotkeySystem<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, A> System<'a> for HotkeySystem<A>
+where
+    A: Clone + Send + Sync + 'static,
+{
+    type SystemData = (
+        Read<'a, EventChannel<Event>>,
+        Read<'a, UiHotkeys<A>>,
+        ReadStorage<'a, Selected>,
+        ReadStorage<'a, TextEditing>,
+        Write<'a, EventChannel<A>>,
+    );
+
+    fn run(&mut self, (events, hotkeys, selecteds, editables, mut actions): Self::SystemData) {
+        let events = events.read(
+            self.reader
+                .as_mut()
+                .expect("`HotkeySystem::setup` was not called before `HotkeySystem::run`"),
+        );
+        if (&selecteds, &editables).join().next().is_some() {
+            return;
+        }
+
+        for event in events {
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                modifiers,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = *event
+            {
+                let hotkey = Hotkey::new(key).with_modifiers(modifiers);
+                if let Some(action) = hotkeys.action(hotkey) {
+                    actions.single_write(action.clone());
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use winit::{DeviceId, WindowId};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum MenuAction {
+        Close,
+        Submit,
+    }
+
+    fn press(world: &World, key: VirtualKeyCode) {
+        world
+            .write_resource::<EventChannel<Event>>()
+            .single_write(Event::WindowEvent {
+                window_id: unsafe { WindowId::dummy() },
+                event: WindowEvent::KeyboardInput {
+                    device_id: unsafe { DeviceId::dummy() },
+                    input: KeyboardInput {
+                        scancode: 0,
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        modifiers: ModifiersState::default(),
+                    },
+                },
+            });
+    }
+
+    #[test]
+    fn escape_fires_bound_action() {
+        let mut world = World::new();
+        let mut system = HotkeySystem::<MenuAction>::new();
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<MenuAction>>()
+            .register_reader();
+        world
+            .write_resource::<UiHotkeys<MenuAction>>()
+            .bind(VirtualKeyCode::Escape, MenuAction::Close);
+
+        press(&world, VirtualKeyCode::Escape);
+        press(&world, VirtualKeyCode::Return);
+        system.run_now(&world.res);
+        let fired = |world: &World, reader: &mut ReaderId<MenuAction>| {
+            world
+                .read_resource::<EventChannel<MenuAction>>()
+                .read(reader)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fired(&world, &mut reader), vec![MenuAction::Close]);
+
+        // Rebinding at runtime.
+        world
+            .write_resource::<UiHotkeys<MenuAction>>()
+            .bind(VirtualKeyCode::Return, MenuAction::Submit);
+        press(&world, VirtualKeyCode::Return);
+        system.run_now(&world.res);
+        assert_eq!(fired(&world, &mut reader), vec![MenuAction::Submit]);
+
+        // A focused text field swallows the keys.
+        world
+            .create_entity()
+            .with(TextEditing::new(
+                10,
+                [0., 0., 0., 1.],
+                [1., 1., 1., 1.],
+                false,
+            ))
+            .with(Selected)
+            .build();
+        press(&world, VirtualKeyCode::Escape);
+        system.run_now(&world.res);
+        assert!(fired(&world, &mut reader).is_empty());
+    }
+}
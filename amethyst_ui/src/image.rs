@@ -1,16 +1,41 @@
 use amethyst_assets::Handle;
-use amethyst_core::ecs::{Component, DenseVecStorage};
+use amethyst_core::ecs::{Component, DenseVecStorage, FlaggedStorage};
 use amethyst_rendy::Texture;
 
 /// Image used UI widgets, often as background.
 #[derive(Debug, Clone, PartialEq)]
 pub enum UiImage {
     /// An image backed by texture handle
-    Texture(Handle<Texture>),
+    Texture {
+        /// The drawn texture
+        texture: Handle<Texture>,
+        /// Color multiplied with every texel, so one white texture can be drawn in many
+        /// colors. `[1.0; 4]` draws the texture unchanged.
+        tint: [f32; 4],
+    },
     /// An image entirely covered by single solid color
     SolidColor([f32; 4]),
+    /// A texture sliced into nine parts. The corners keep their size, the edges stretch
+    /// along one axis and the center stretches along both.
+    NinePatch {
+        /// The sliced texture
+        texture: Handle<Texture>,
+        /// Size of the borders in texture pixels, in order left, right, top, bottom.
+        /// Borders are shrunk when they don't fit into the `UiTransform`.
+        border: [f32; 4],
+        /// Size of the texture in pixels, used to map the borders to texture coordinates.
+        texture_size: [u32; 2],
+    },
+    /// A texture repeated to cover the `UiTransform`, starting at its top left corner.
+    /// Tiles at the right and bottom edges are cut off.
+    Tiled {
+        /// The repeated texture
+        texture: Handle<Texture>,
+        /// Size of one repetition of the texture in pixels.
+        tile_size: [f32; 2],
+    },
 }
 
 impl Component for UiImage {
-    type Storage = DenseVecStorage<Self>;
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
@@ -6,14 +6,14 @@ use thread_profiler::profile_scope;
 
 use amethyst_core::{
     ecs::prelude::{
-        BitSet, ComponentEvent, Join, ReadExpect, ReadStorage, ReaderId, Resources, System,
-        WriteStorage,
+        BitSet, ComponentEvent, Join, Read, ReadExpect, ReadStorage, ReaderId, Resources, System,
+        Write, WriteStorage,
     },
     HierarchyEvent, Parent, ParentHierarchy,
 };
 use amethyst_window::ScreenDimensions;
 
-use super::UiTransform;
+use super::{UiDirty, UiLayout, UiScale, UiScrollContainer, UiTint, UiTransform};
 
 /// Indicates if the position and margins should be calculated in pixel or
 /// relative to their parent size.
@@ -127,6 +127,8 @@ pub enum Stretch {
 /// Manages the `Parent` component on entities having `UiTransform`
 /// It does almost the same as the `TransformSystem`, but with some differences,
 /// like `UiTransform` alignment and stretching.
+///
+/// Marks the `UiDirty` resource, if there is one, when transforms change.
 #[derive(Default)]
 pub struct UiTransformSystem {
     transform_modified: BitSet,
@@ -136,23 +138,39 @@ pub struct UiTransformSystem {
     parent_events_id: Option<ReaderId<HierarchyEvent>>,
 
     screen_size: (f32, f32),
+
+    scale: f32,
 }
 
 impl<'a> System<'a> for UiTransformSystem {
     type SystemData = (
         WriteStorage<'a, UiTransform>,
         ReadStorage<'a, Parent>,
+        ReadStorage<'a, UiScrollContainer>,
+        ReadStorage<'a, UiLayout>,
         ReadExpect<'a, ScreenDimensions>,
         ReadExpect<'a, ParentHierarchy>,
+        Read<'a, UiScale>,
+        Option<Write<'a, UiDirty>>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (mut transforms, parents, screen_dim, hierarchy) = data;
+        let (
+            mut transforms,
+            parents,
+            scroll_containers,
+            layouts,
+            screen_dim,
+            hierarchy,
+            ui_scale,
+            ui_dirty,
+        ) = data;
         #[cfg(feature = "profiler")]
         profile_scope!("ui_parent_system");
 
         self.transform_modified.clear();
 
         let self_transform_modified = &mut self.transform_modified;
+        let mut removed = false;
 
         let self_transform_events_id = &mut self
             .transform_events_id
@@ -166,7 +184,7 @@ impl<'a> System<'a> for UiTransformSystem {
                 ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
                     self_transform_modified.add(*id);
                 }
-                ComponentEvent::Removed(_id) => {}
+                ComponentEvent::Removed(_id) => removed = true,
             });
 
         for event in
@@ -182,12 +200,16 @@ impl<'a> System<'a> for UiTransformSystem {
         }
 
         let current_screen_size = (screen_dim.width(), screen_dim.height());
-        let screen_resized = current_screen_size != self.screen_size;
+        let current_scale = ui_scale.effective(&screen_dim);
+        // A new scale moves anchored elements just like a new screen size.
+        let screen_resized = current_screen_size != self.screen_size || current_scale != self.scale;
         self.screen_size = current_screen_size;
+        self.scale = current_scale;
         if screen_resized {
             process_root_iter(
                 (&mut transforms, !&parents).join().map(|i| i.0),
                 &*screen_dim,
+                current_scale,
             );
         } else {
             // Immutable borrow
@@ -197,6 +219,7 @@ impl<'a> System<'a> for UiTransformSystem {
                     .join()
                     .map(|i| i.0),
                 &*screen_dim,
+                current_scale,
             );
         }
 
@@ -235,63 +258,77 @@ impl<'a> System<'a> for UiTransformSystem {
                     transform.pixel_y =
                         parent_transform_copy.pixel_y + parent_transform_copy.pixel_height * norm.1;
                     transform.global_z = parent_transform_copy.global_z + transform.local_z;
+                    transform.laid_out = true;
 
+                    let scale = pixel_scale(transform, current_scale);
+                    let parent_width = parent_transform_copy.pixel_width / scale;
+                    let parent_height = parent_transform_copy.pixel_height / scale;
                     let new_size = match transform.stretch {
                         Stretch::NoStretch => (transform.width, transform.height),
-                        Stretch::X { x_margin } => (
-                            parent_transform_copy.pixel_width - x_margin * 2.0,
-                            transform.height,
-                        ),
-                        Stretch::Y { y_margin } => (
-                            transform.width,
-                            parent_transform_copy.pixel_height - y_margin * 2.0,
-                        ),
+                        Stretch::X { x_margin } => {
+                            (parent_width - x_margin * 2.0, transform.height)
+                        }
+                        Stretch::Y { y_margin } => {
+                            (transform.width, parent_height - y_margin * 2.0)
+                        }
                         Stretch::XY {
                             keep_aspect_ratio: false,
                             x_margin,
                             y_margin,
                         } => (
-                            parent_transform_copy.pixel_width - x_margin * 2.0,
-                            parent_transform_copy.pixel_height - y_margin * 2.0,
+                            parent_width - x_margin * 2.0,
+                            parent_height - y_margin * 2.0,
                         ),
                         Stretch::XY {
                             keep_aspect_ratio: true,
                             x_margin,
                             y_margin,
                         } => {
-                            let scale = f32::min(
-                                (parent_transform_copy.pixel_width - x_margin * 2.0)
-                                    / transform.width,
-                                (parent_transform_copy.pixel_height - y_margin * 2.0)
-                                    / transform.height,
+                            let fit = f32::min(
+                                (parent_width - x_margin * 2.0) / transform.width,
+                                (parent_height - y_margin * 2.0) / transform.height,
                             );
 
-                            (transform.width * scale, transform.height * scale)
+                            (transform.width * fit, transform.height * fit)
                         }
                     };
                     transform.width = new_size.0;
                     transform.height = new_size.1;
                     match transform.scale_mode {
                         ScaleMode::Pixel => {
-                            transform.pixel_x += transform.local_x;
-                            transform.pixel_y += transform.local_y;
-                            transform.pixel_width = transform.width;
-                            transform.pixel_height = transform.height;
+                            transform.pixel_x += transform.local_x * scale;
+                            transform.pixel_y += transform.local_y * scale;
+                            transform.pixel_width = transform.width * scale;
+                            transform.pixel_height = transform.height * scale;
                         }
                         ScaleMode::Percent => {
                             transform.pixel_x +=
                                 transform.local_x * parent_transform_copy.pixel_width;
                             transform.pixel_y +=
                                 transform.local_y * parent_transform_copy.pixel_height;
-                            transform.pixel_width =
-                                transform.width * parent_transform_copy.pixel_width;
-                            transform.pixel_height =
-                                transform.height * parent_transform_copy.pixel_height;
+                            // Children of a layout are sized against its content box.
+                            let (parent_width, parent_height) = layouts.get(parent_entity).map_or(
+                                (
+                                    parent_transform_copy.pixel_width,
+                                    parent_transform_copy.pixel_height,
+                                ),
+                                |layout| {
+                                    layout.content_size(
+                                        parent_transform_copy.pixel_width,
+                                        parent_transform_copy.pixel_height,
+                                    )
+                                },
+                            );
+                            transform.pixel_width = transform.width * parent_width;
+                            transform.pixel_height = transform.height * parent_height;
                         }
                     }
                     let pivot_norm = transform.pivot.norm_offset();
                     transform.pixel_x += transform.pixel_width * -pivot_norm.0;
                     transform.pixel_y += transform.pixel_height * -pivot_norm.1;
+                    if let Some(scroll) = scroll_containers.get(parent_entity) {
+                        transform.pixel_y += scroll.scroll_offset;
+                    }
                 }
             }
             // Populate the modifications we just did.
@@ -315,18 +352,35 @@ impl<'a> System<'a> for UiTransformSystem {
                 }
                 ComponentEvent::Removed(_id) => {}
             });
+
+        if let Some(mut ui_dirty) = ui_dirty {
+            if screen_resized || removed || (&*self_transform_modified).join().next().is_some() {
+                ui_dirty.mark();
+            }
+        }
     }
 
     fn setup(&mut self, res: &mut Resources) {
         use amethyst_core::ecs::prelude::SystemData;
         Self::SystemData::setup(res);
+        // Only read by the `DrawUi` pass, which can't register storages itself.
+        ReadStorage::<UiTint>::setup(res);
         self.parent_events_id = Some(res.fetch_mut::<ParentHierarchy>().track());
         let mut transforms = WriteStorage::<UiTransform>::fetch(res);
         self.transform_events_id = Some(transforms.register_reader());
     }
 }
 
-fn process_root_iter<'a, I>(iter: I, screen_dim: &ScreenDimensions)
+/// Returns the factor the pixel sizes of the transform are multiplied by. Percent sizes are
+/// relative to the parent, which is already scaled.
+fn pixel_scale(transform: &UiTransform, scale: f32) -> f32 {
+    match transform.scale_mode {
+        ScaleMode::Pixel => scale,
+        ScaleMode::Percent => 1.0,
+    }
+}
+
+pub(crate) fn process_root_iter<'a, I>(iter: I, screen_dim: &ScreenDimensions, ui_scale: f32)
 where
     I: Iterator<Item = &'a mut UiTransform>,
 {
@@ -335,40 +389,44 @@ where
         transform.pixel_x = screen_dim.width() / 2.0 + screen_dim.width() * norm.0;
         transform.pixel_y = screen_dim.height() / 2.0 + screen_dim.height() * norm.1;
         transform.global_z = transform.local_z;
+        transform.laid_out = true;
 
+        let scale = pixel_scale(transform, ui_scale);
+        let screen_width = screen_dim.width() / scale;
+        let screen_height = screen_dim.height() / scale;
         let new_size = match transform.stretch {
             Stretch::NoStretch => (transform.width, transform.height),
-            Stretch::X { x_margin } => (screen_dim.width() - x_margin * 2.0, transform.height),
-            Stretch::Y { y_margin } => (transform.width, screen_dim.height() - y_margin * 2.0),
+            Stretch::X { x_margin } => (screen_width - x_margin * 2.0, transform.height),
+            Stretch::Y { y_margin } => (transform.width, screen_height - y_margin * 2.0),
             Stretch::XY {
                 keep_aspect_ratio: false,
                 x_margin,
                 y_margin,
             } => (
-                screen_dim.width() - x_margin * 2.0,
-                screen_dim.height() - y_margin * 2.0,
+                screen_width - x_margin * 2.0,
+                screen_height - y_margin * 2.0,
             ),
             Stretch::XY {
                 keep_aspect_ratio: true,
                 x_margin,
                 y_margin,
             } => {
-                let scale = f32::min(
-                    (screen_dim.width() - x_margin * 2.0) / transform.width,
-                    (screen_dim.height() - y_margin * 2.0) / transform.height,
+                let fit = f32::min(
+                    (screen_width - x_margin * 2.0) / transform.width,
+                    (screen_height - y_margin * 2.0) / transform.height,
                 );
 
-                (transform.width * scale, transform.height * scale)
+                (transform.width * fit, transform.height * fit)
             }
         };
         transform.width = new_size.0;
         transform.height = new_size.1;
         match transform.scale_mode {
             ScaleMode::Pixel => {
-                transform.pixel_x += transform.local_x;
-                transform.pixel_y += transform.local_y;
-                transform.pixel_width = transform.width;
-                transform.pixel_height = transform.height;
+                transform.pixel_x += transform.local_x * scale;
+                transform.pixel_y += transform.local_y * scale;
+                transform.pixel_width = transform.width * scale;
+                transform.pixel_height = transform.height * scale;
             }
             ScaleMode::Percent => {
                 transform.pixel_x += transform.local_x * screen_dim.width();
@@ -382,3 +440,94 @@ where
         transform.pixel_y += transform.pixel_height * -pivot_norm.1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchored(anchor: Anchor, x: f32, y: f32) -> UiTransform {
+        UiTransform::new(
+            "anchored".to_string(),
+            anchor.clone(),
+            anchor,
+            x,
+            y,
+            0.0,
+            100.0,
+            50.0,
+        )
+    }
+
+    fn layout_root(transform: &mut UiTransform, width: u32, height: u32) -> (f32, f32) {
+        let screen_dim = ScreenDimensions::new(width, height, 1.0);
+        process_root_iter(std::iter::once(&mut *transform), &screen_dim, 1.0);
+        (transform.pixel_x(), transform.pixel_y())
+    }
+
+    #[test]
+    fn root_anchors_follow_screen_size() {
+        let mut top_left = anchored(Anchor::TopLeft, 10.0, -10.0);
+        let mut middle = anchored(Anchor::Middle, 0.0, 0.0);
+        let mut bottom_right = anchored(Anchor::BottomRight, -10.0, 10.0);
+
+        assert_eq!(layout_root(&mut top_left, 800, 600), (60.0, 565.0));
+        assert_eq!(layout_root(&mut middle, 800, 600), (400.0, 300.0));
+        assert_eq!(layout_root(&mut bottom_right, 800, 600), (740.0, 35.0));
+
+        assert_eq!(layout_root(&mut top_left, 1024, 768), (60.0, 733.0));
+        assert_eq!(layout_root(&mut middle, 1024, 768), (512.0, 384.0));
+        assert_eq!(layout_root(&mut bottom_right, 1024, 768), (964.0, 35.0));
+    }
+
+    #[test]
+    fn percent_size_follows_screen_size() {
+        let mut half = UiTransform::new(
+            "half".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            0.5,
+            0.25,
+        )
+        .as_percent();
+
+        layout_root(&mut half, 800, 600);
+        assert_eq!((half.pixel_width, half.pixel_height), (400.0, 150.0));
+
+        layout_root(&mut half, 1920, 1080);
+        assert_eq!((half.pixel_width, half.pixel_height), (960.0, 270.0));
+    }
+
+    #[test]
+    fn ui_scale_multiplies_pixel_sizes() {
+        let screen_dim = ScreenDimensions::new(800, 600, 1.0);
+        let scale = UiScale::new(2.0).effective(&screen_dim);
+        let mut top_left = anchored(Anchor::TopLeft, 10.0, -10.0);
+        let mut bar =
+            anchored(Anchor::BottomMiddle, 0.0, 0.0).with_stretch(Stretch::X { x_margin: 10.0 });
+        let mut half = anchored(Anchor::Middle, 0.0, 0.0).as_percent();
+        half.width = 0.5;
+        half.height = 0.5;
+        process_root_iter(
+            vec![&mut top_left, &mut bar, &mut half].into_iter(),
+            &screen_dim,
+            scale,
+        );
+
+        assert_eq!((top_left.pixel_x(), top_left.pixel_y()), (120.0, 530.0));
+        assert_eq!(
+            (top_left.pixel_width, top_left.pixel_height),
+            (200.0, 100.0)
+        );
+        // Margins are scaled, the stretched width still fills the screen.
+        assert_eq!((bar.pixel_width, bar.pixel_height), (760.0, 100.0));
+        assert_eq!((half.pixel_width, half.pixel_height), (400.0, 300.0));
+
+        // The window's hidpi factor is applied once, on top of the user's factor.
+        let hidpi = ScreenDimensions::new(800, 600, 2.0);
+        assert_eq!(UiScale::default().effective(&hidpi), 2.0);
+        assert_eq!(UiScale::new(1.5).effective(&hidpi), 3.0);
+    }
+}
@@ -3,59 +3,93 @@
 #![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
 
 pub use self::{
+    auto_size::{AutoSize, AutoSizeSystem},
     blink::BlinkSystem,
     bundle::UiBundle,
     button::{
         UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionRetriggerSystem,
         UiButtonActionType, UiButtonBuilder, UiButtonBuilderResources, UiButtonSystem,
     },
-    event::{targeted, Interactable, UiEvent, UiEventType, UiMouseSystem},
+    cursor::{UiCursor, UiCursorSystem},
+    drag::{DragSystem, Draggable},
+    dropdown::{DropdownSystem, UiDropdown},
+    event::{targeted, Interactable, UiEvent, UiEventType, UiHovered, UiMouseSystem},
     event_retrigger::{EventReceiver, EventRetriggerSystem},
+    flex::{JustifyContent, LayoutDirection, LayoutSystem, UiLayout},
     font::{
         default::get_default_font,
         systemfont::{default_system_font, get_all_font_handles, list_system_font_families},
     },
-    format::{FontAsset, FontHandle, TtfFormat},
+    format::{FontAsset, FontHandle, TextMetrics, TtfFormat, UiFontLoader},
     glyphs::UiGlyphsSystem,
+    hit_shape::{HitMask, UiHitShape},
+    hotkey::{Hotkey, HotkeySystem, UiHotkeys},
     image::UiImage,
     label::{UiLabel, UiLabelBuilder, UiLabelBuilderResources},
     layout::{Anchor, ScaleMode, Stretch, UiTransformSystem},
-    pass::{DrawUi, DrawUiDesc},
+    pass::{DrawUi, DrawUiDesc, UiDebugLines, UiDirty, UiRenderStats},
     prefab::{
         NoCustomUi, ToNativeWidget, UiCreator, UiFormat, UiImagePrefab, UiLoader, UiLoaderSystem,
         UiPrefab, UiTextBuilder, UiTransformBuilder, UiWidget,
     },
+    progress_bar::{ProgressBarSystem, UiProgressBar},
     resize::{ResizeSystem, UiResize},
-    selection::{Selectable, Selected, SelectionKeyboardSystem, SelectionMouseSystem},
+    reveal::{RevealMode, TextRevealSystem, UiTextReveal},
+    scale::UiScale,
+    scroll::{ScrollSystem, UiScrollContainer},
+    selection::{FocusPolicy, Selectable, Selected, SelectionKeyboardSystem, SelectionMouseSystem},
     selection_order_cache::{CacheSelectionOrderSystem, CachedSelectionOrder},
+    slider::{SliderSystem, UiSlider},
     sound::{UiPlaySoundAction, UiSoundRetrigger, UiSoundRetriggerSystem, UiSoundSystem},
-    text::{LineMode, TextEditing, TextEditingMouseSystem, UiText},
-    text_editing::TextEditingInputSystem,
-    transform::{UiFinder, UiTransform},
+    text::{
+        Justify, LineMode, Overflow, RasterMode, TabWidth, TextBaseline, TextDirection,
+        TextEditing, TextEditingMouseSystem, TextOutline, TextShadow, TextSpan, UiText,
+    },
+    text_editing::{ImeEvent, TextEditingInputSystem},
+    tint::UiTint,
+    toggle::{ToggleSystem, UiToggle},
+    tooltip::{TooltipSystem, UiTooltip, UiTooltipDisplay},
+    transform::{screen_position, UiFinder, UiTransform},
     widgets::{Widget, WidgetId, Widgets},
 };
 
 pub(crate) use amethyst_core::ecs::prelude::Entity;
 pub(crate) use paste;
 
+mod auto_size;
 mod blink;
 mod bundle;
 mod button;
+mod cursor;
+mod drag;
+mod dropdown;
 mod event;
 mod event_retrigger;
+mod flex;
 mod font;
 mod format;
+mod glyph_cache;
 mod glyphs;
+mod hit_shape;
+mod hotkey;
 mod image;
 mod label;
 mod layout;
 mod pass;
 mod prefab;
+mod progress_bar;
 mod resize;
+mod reveal;
+mod scale;
+mod scroll;
 mod selection;
 mod selection_order_cache;
+mod slider;
 mod sound;
 mod text;
 mod text_editing;
+mod tint;
+mod toggle;
+mod tooltip;
 mod transform;
 mod widgets;
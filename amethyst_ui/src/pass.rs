@@ -1,11 +1,17 @@
 use crate::{
     glyphs::{UiGlyphs, UiGlyphsResource},
-    Selected, TextEditing, UiImage, UiTransform,
+    tint::inherited_alpha,
+    Selected, TextEditing, UiEvent, UiImage, UiScrollContainer, UiText, UiTint, UiTransform,
 };
 use amethyst_assets::{AssetStorage, Handle, Loader};
 use amethyst_core::{
-    ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, Resources, SystemData},
-    Hidden, HiddenPropagate,
+    ecs::{
+        storage::{ComponentEvent, Tracked},
+        Component, Entities, Entity, Join, Read, ReadExpect, ReadStorage, ReaderId, Resources,
+        SystemData, WriteStorage,
+    },
+    shrev::EventChannel,
+    Hidden, HiddenPropagate, Parent,
 };
 use amethyst_rendy::{
     batch::OrderedOneLevelBatch,
@@ -50,6 +56,8 @@ pub(crate) struct UiArgs {
     pub(crate) dimensions: vec2,
     pub(crate) tex_coord_bounds: vec4,
     pub(crate) color: vec4,
+    /// Cosine and sine of the angle the quad is rotated by around its center.
+    pub(crate) rotation: vec2,
 }
 
 impl AsVertex for UiArgs {
@@ -59,6 +67,7 @@ impl AsVertex for UiArgs {
             (Format::Rg32Sfloat, "dimensions"),
             (Format::Rgba32Sfloat, "tex_coord_bounds"),
             (Format::Rgba32Sfloat, "color"),
+            (Format::Rg32Sfloat, "rotation"),
         ))
     }
 }
@@ -68,6 +77,9 @@ struct UiViewArgs {
     inverse_window_size: vec2,
 }
 
+/// Height of the strips glyphs are cut into to draw a `UiText::gradient`, in pixels.
+const GRADIENT_STRIP_HEIGHT: f32 = 2.0;
+
 lazy_static::lazy_static! {
     static ref UI_VERTEX: SpirvShader = SpirvShader::new(
         include_bytes!("../compiled/ui.vert.spv").to_vec(),
@@ -82,6 +94,151 @@ lazy_static::lazy_static! {
     );
 }
 
+/// What the UI pass drew on the last frame, to keep track of what the UI costs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UiRenderStats {
+    /// The number of draw calls, one per batch of quads sharing a texture.
+    pub draw_calls: usize,
+    /// The number of quads drawn with the glyph texture, including shadows and outlines.
+    pub glyphs: usize,
+    /// The total number of quads drawn, glyphs included.
+    pub quads: usize,
+}
+
+impl UiRenderStats {
+    fn from_batches<K: PartialEq>(
+        batches: &OrderedOneLevelBatch<K, UiArgs>,
+        glyph_tex: &K,
+    ) -> Self {
+        let mut stats = UiRenderStats {
+            quads: batches.count(),
+            ..Default::default()
+        };
+        for (tex, range) in batches.iter() {
+            stats.draw_calls += 1;
+            if tex == glyph_tex {
+                stats.glyphs += range.len();
+            }
+        }
+        stats
+    }
+}
+
+/// Resource letting the `DrawUi` pass draw the batches of the last frame again, instead of
+/// batching the UI every frame.
+///
+/// Without this resource the UI is batched every frame. Once it is added to the world, the
+/// UI is only batched again when it was marked dirty, when UI events were sent, the screen
+/// was resized, elements were hidden or shown, a `UiImage` or `UiTint` was changed, the
+/// `UiDebugLines` are enabled, or an edited text is selected, as its cursor blinks. The
+/// `UiTransformSystem` and the `UiGlyphsSystem` mark it when they lay out elements or text.
+/// Systems changing how elements look in other ways, like swapping the `Tint` of an element,
+/// have to call `mark` themselves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UiDirty {
+    dirty: bool,
+}
+
+impl UiDirty {
+    /// Makes the UI be batched again on the next frame.
+    pub fn mark(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Whether the UI was marked dirty since the last frame.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Returns whether the UI was marked dirty, and clears the mark.
+    pub fn take(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+}
+
+/// Decides if the UI has to be batched again or the batches of the last frame still hold.
+#[derive(Debug, Default)]
+struct BatchReuse {
+    /// Whether the last batches are complete, they aren't before the first frame or while
+    /// textures of images are loading.
+    complete: bool,
+    screen_size: (f32, f32),
+    hidden: Vec<u32>,
+}
+
+impl BatchReuse {
+    /// Whether the UI has to be batched again. Remembers the screen size and the hidden
+    /// entities, to compare them on the next frame.
+    fn needs_batching(&mut self, dirty: bool, screen_size: (f32, f32), hidden: Vec<u32>) -> bool {
+        let needed =
+            dirty || !self.complete || self.screen_size != screen_size || self.hidden != hidden;
+        self.complete = true;
+        self.screen_size = screen_size;
+        self.hidden = hidden;
+        needed
+    }
+}
+
+/// Readers of the flagged storages which change how the UI looks without laying it out.
+#[derive(Debug, Default)]
+struct StorageChanges {
+    images: Option<ReaderId<ComponentEvent>>,
+    ui_tints: Option<ReaderId<ComponentEvent>>,
+}
+
+impl StorageChanges {
+    /// Whether any `UiImage` or `UiTint` was inserted, modified or removed since the last
+    /// call. Changes before the first call are missed, but the first frame is always batched.
+    fn any(&mut self, resources: &Resources) -> bool {
+        // Both are read, so neither keeps changes that were already batched.
+        let images = storage_changed::<UiImage>(&mut self.images, resources);
+        let ui_tints = storage_changed::<UiTint>(&mut self.ui_tints, resources);
+        images || ui_tints
+    }
+}
+
+fn storage_changed<T>(reader: &mut Option<ReaderId<ComponentEvent>>, resources: &Resources) -> bool
+where
+    T: Component,
+    T::Storage: Tracked,
+{
+    let mut storage = WriteStorage::<T>::fetch(resources);
+    let reader = reader.get_or_insert_with(|| storage.register_reader());
+    storage.channel().read(reader).count() > 0
+}
+
+/// Resource outlining every `UiTransform` over the UI, to debug layouts.
+///
+/// While enabled, the `DrawUi` pass draws the bounds of each visible element, as resolved by
+/// the `UiTransformSystem`, in a color picked from its entity. A small square marks the
+/// point in the parent, or the screen, the element is anchored to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiDebugLines {
+    /// Whether the outlines are drawn.
+    pub enabled: bool,
+    /// Width of the outlines in pixels.
+    pub thickness: f32,
+}
+
+impl Default for UiDebugLines {
+    fn default() -> Self {
+        UiDebugLines {
+            enabled: false,
+            thickness: 1.0,
+        }
+    }
+}
+
+/// Outline colors of the `UiDebugLines`, picked by entity id.
+const DEBUG_COLORS: [[f32; 4]; 6] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.3, 0.5, 1.0, 1.0],
+    [1.0, 1.0, 0.2, 1.0],
+    [1.0, 0.2, 1.0, 1.0],
+    [0.2, 1.0, 1.0, 1.0],
+];
+
 /// A UI drawing pass that draws UI elements and text in screen-space
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct DrawUiDesc;
@@ -135,6 +292,12 @@ impl<B: Backend> RenderGroupDesc<B, Resources> for DrawUiDesc {
             change: Default::default(),
             cached_draw_order: Default::default(),
             batches: Default::default(),
+            batch_reuse: Default::default(),
+            storage_changes: Default::default(),
+            debug_lines_batched: false,
+            ui_reader_id: resources
+                .try_fetch_mut::<EventChannel<UiEvent>>()
+                .map(|mut events| events.register_reader()),
             white_tex,
         }))
     }
@@ -148,9 +311,17 @@ pub struct DrawUi<B: Backend> {
     env: DynamicUniform<B, UiViewArgs>,
     textures: TextureSub<B>,
     vertex: DynamicVertex<B, UiArgs>,
+    // Consecutive quads sharing a texture, like icons from one atlas, are merged into one
+    // draw call. Batches are never reordered, as the UI is blended back to front.
     batches: OrderedOneLevelBatch<TextureId, UiArgs>,
     change: ChangeDetection,
     cached_draw_order: CachedDrawOrder,
+    batch_reuse: BatchReuse,
+    storage_changes: StorageChanges,
+    // Whether the last batches hold the outlines of the `UiDebugLines`.
+    debug_lines_batched: bool,
+    // Any UI event can change how the UI looks, so the UI is batched again after them.
+    ui_reader_id: Option<ReaderId<UiEvent>>,
     white_tex: Handle<Texture>,
 }
 
@@ -170,6 +341,8 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawUi<B> {
         _subpass: hal::pass::Subpass<'_, B>,
         resources: &Resources,
     ) -> PrepareResult {
+        // Read before the storages are borrowed for drawing.
+        let storages_changed = self.storage_changes.any(resources);
         let (
             entities,
             images,
@@ -179,7 +352,11 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawUi<B> {
             hidden_propagates,
             selected,
             tints,
+            ui_tints,
             glyphs,
+            texts,
+            parents,
+            scroll_containers,
             glyphs_res,
             screen_dimesnions,
         ) = <(
@@ -191,12 +368,15 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawUi<B> {
             ReadStorage<'_, HiddenPropagate>,
             ReadStorage<'_, Selected>,
             ReadStorage<'_, Tint>,
+            ReadStorage<'_, UiTint>,
             ReadStorage<'_, UiGlyphs>,
+            ReadStorage<'_, UiText>,
+            ReadStorage<'_, Parent>,
+            ReadStorage<'_, UiScrollContainer>,
             ReadExpect<'_, UiGlyphsResource>,
             ReadExpect<'_, ScreenDimensions>,
         ) as SystemData>::fetch(resources);
 
-        self.batches.swap_clear();
         let mut changed = false;
 
         let (white_tex_id, glyph_tex_id) = {
@@ -225,147 +405,247 @@ impl<B: Backend> RenderGroup<B, Resources> for DrawUi<B> {
             }
         };
 
-        // Populate and update the draw order cache.
-        let bitset = &mut self.cached_draw_order.cached;
-
-        self.cached_draw_order.cache.retain(|&(_z, entity)| {
-            let keep = transforms.contains(entity);
-            if !keep {
-                bitset.remove(entity.id());
+        let hidden = (hiddens.mask() | hidden_propagates.mask())
+            .join()
+            .collect::<Vec<_>>();
+        let events_sent = match (
+            self.ui_reader_id.as_mut(),
+            resources.try_fetch::<EventChannel<UiEvent>>(),
+        ) {
+            (Some(reader), Some(events)) => events.read(reader).count() > 0,
+            _ => false,
+        };
+        // Outlines are batched every frame while enabled, and once more to remove them.
+        let debug_lines = resources
+            .try_fetch::<UiDebugLines>()
+            .map_or(false, |lines| lines.enabled);
+        let debug_lines_shown = debug_lines || self.debug_lines_batched;
+        self.debug_lines_batched = debug_lines;
+        let dirty = match resources.try_fetch_mut::<UiDirty>() {
+            Some(mut dirty) => {
+                // Take the mark first, so it is cleared whatever else changed.
+                dirty.take()
+                    || changed
+                    || events_sent
+                    || storages_changed
+                    || (&text_editings, &selected).join().next().is_some()
+                    || debug_lines_shown
             }
-            keep
-        });
-
-        for &mut (ref mut z, entity) in &mut self.cached_draw_order.cache {
-            *z = transforms
-                .get(entity)
-                .expect("Unreachable: Enities are collected from a cache of prepopulate entities")
-                .global_z();
-        }
+            None => true,
+        };
+        let screen_size = (screen_dimesnions.width(), screen_dimesnions.height());
+        if self.batch_reuse.needs_batching(dirty, screen_size, hidden) {
+            self.batches.swap_clear();
 
-        // Attempt to insert the new entities in sorted position. Should reduce work during
-        // the sorting step.
-        let transform_set = transforms.mask().clone();
+            // Populate and update the draw order cache.
+            let bitset = &mut self.cached_draw_order.cached;
 
-        // Create a bitset containing only the new indices.
-        let new = (&transform_set ^ &self.cached_draw_order.cached) & &transform_set;
-        for (entity, transform, _new) in (&*entities, &transforms, &new).join() {
-            let pos = self
-                .cached_draw_order
-                .cache
-                .iter()
-                .position(|&(cached_z, _)| transform.global_z() >= cached_z);
+            self.cached_draw_order.cache.retain(|&(_z, entity)| {
+                let keep = transforms.contains(entity);
+                if !keep {
+                    bitset.remove(entity.id());
+                }
+                keep
+            });
 
-            match pos {
-                Some(pos) => self
-                    .cached_draw_order
-                    .cache
-                    .insert(pos, (transform.global_z(), entity)),
-                None => self
-                    .cached_draw_order
-                    .cache
-                    .push((transform.global_z(), entity)),
+            for &mut (ref mut z, entity) in &mut self.cached_draw_order.cache {
+                *z = transforms
+                    .get(entity)
+                    .expect(
+                        "Unreachable: Enities are collected from a cache of prepopulate entities",
+                    )
+                    .global_z();
             }
-        }
 
-        self.cached_draw_order.cached = transform_set;
+            // Attempt to insert the new entities in sorted position. Should reduce work during
+            // the sorting step.
+            let transform_set = transforms.mask().clone();
 
-        // Sort from largest z value to smallest z value.
-        // Most of the time this shouldn't do anything but you still need it
-        // for if the z values change.
-        self.cached_draw_order
-            .cache
-            .sort_unstable_by(|&(z1, _), &(z2, _)| z1.partial_cmp(&z2).unwrap_or(Ordering::Equal));
+            // Create a bitset containing only the new indices.
+            let new = (&transform_set ^ &self.cached_draw_order.cached) & &transform_set;
+            for (entity, transform, _new) in (&*entities, &transforms, &new).join() {
+                let pos = self
+                    .cached_draw_order
+                    .cache
+                    .iter()
+                    .position(|&(cached_z, _)| transform.global_z() >= cached_z);
 
-        for &(_z, entity) in &self.cached_draw_order.cache {
-            // Skip hidden entities
-            if hiddens.contains(entity) || hidden_propagates.contains(entity) {
-                continue;
+                match pos {
+                    Some(pos) => self
+                        .cached_draw_order
+                        .cache
+                        .insert(pos, (transform.global_z(), entity)),
+                    None => self
+                        .cached_draw_order
+                        .cache
+                        .push((transform.global_z(), entity)),
+                }
             }
 
-            let transform = transforms
-                .get(entity)
-                .expect("Unreachable: Entity is guaranteed to be present based on earlier actions");
+            self.cached_draw_order.cached = transform_set;
 
-            let tint = tints.get(entity).map(|t| {
-                let (r, g, b, a) = t.0.into_components();
-                [r, g, b, a]
-            });
+            // Most of the time this shouldn't do anything but you still need it
+            // for if the z values change.
+            sort_draw_order(&mut self.cached_draw_order.cache);
 
-            let image = images.get(entity);
-            if let Some(image) = image {
-                let this_changed = render_image(
-                    factory,
-                    resources,
-                    transform,
-                    image,
-                    &tint,
-                    white_tex_id,
-                    &mut self.textures,
-                    &mut self.batches,
+            for &(_z, entity) in &self.cached_draw_order.cache {
+                // Skip hidden entities
+                if hiddens.contains(entity) || hidden_propagates.contains(entity) {
+                    continue;
+                }
+
+                let transform = transforms.get(entity).expect(
+                    "Unreachable: Entity is guaranteed to be present based on earlier actions",
                 );
-                changed = changed || this_changed;
-            };
 
-            if let Some(glyph_data) = glyphs.get(entity) {
-                if glyph_data.sel_vertices.len() > 0 {
-                    self.batches
-                        .insert(white_tex_id, glyph_data.sel_vertices.iter().cloned());
-                }
+                let clip = scroll_clip_rect(entity, &parents, &transforms, &scroll_containers);
+                let place =
+                    |args: UiArgs| clip_quad(args, clip).map(|args| rotate_quad(args, transform));
 
-                // blinking cursor
-                if selected.contains(entity) {
-                    if let Some(editing) = text_editings.get(entity) {
-                        let blink_on = editing.cursor_blink_timer < 0.25;
-                        let (w, h) = match (blink_on, editing.use_block_cursor) {
-                            // use degenerate quad, but still insert so batches will not change
-                            (false, false) => (0., 0.),
-                            (true, false) => (2., glyph_data.height),
-                            (false, true) => {
-                                (glyph_data.space_width, 1.0f32.max(glyph_data.height * 0.1))
-                            }
-                            (true, true) => (glyph_data.space_width, glyph_data.height),
-                        };
-                        // align to baseline
-                        let base_x = glyph_data.cursor_pos.0 + w * 0.5;
-                        let base_y = glyph_data.cursor_pos.1 - (glyph_data.height - h) * 0.5;
-
-                        let min_x = transform.pixel_x + transform.pixel_width * -0.5;
-                        let max_x = transform.pixel_x + transform.pixel_width * 0.5;
-                        let min_y = transform.pixel_y + transform.pixel_height * -0.5;
-                        let max_y = transform.pixel_y + transform.pixel_height * 0.5;
-
-                        let left = (base_x - w * 0.5).max(min_x).min(max_x);
-                        let right = (base_x + w * 0.5).max(min_x).min(max_x);
-                        let top = (base_y - h * 0.5).max(min_y).min(max_y);
-                        let bottom = (base_y + h * 0.5).max(min_y).min(max_y);
-
-                        let x = (left + right) * 0.5;
-                        let y = (top + bottom) * 0.5;
-                        let w = right - left;
-                        let h = bottom - top;
+                let tint = tints.get(entity).map(|t| {
+                    let (r, g, b, a) = t.0.into_components();
+                    [r, g, b, a]
+                });
+                // Glyph colors already include the `Tint`, so only the inherited alpha is
+                // applied to them.
+                let alpha = inherited_alpha(entity, &parents, &ui_tints);
+                let tint = if alpha < 1.0 {
+                    let [r, g, b, a] = tint.unwrap_or([1., 1., 1., 1.]);
+                    Some([r, g, b, a * alpha])
+                } else {
+                    tint
+                };
 
+                let image = images.get(entity);
+                if let Some(image) = image {
+                    match render_image(
+                        factory,
+                        resources,
+                        transform,
+                        image,
+                        &tint,
+                        clip,
+                        white_tex_id,
+                        &mut self.textures,
+                        &mut self.batches,
+                    ) {
+                        Some(this_changed) => changed = changed || this_changed,
+                        // Batched again until the texture is loaded and the image drawn.
+                        None => self.batch_reuse.complete = false,
+                    }
+                };
+
+                if let Some(glyph_data) = glyphs.get(entity) {
+                    if glyph_data.sel_vertices.len() > 0 {
                         self.batches.insert(
                             white_tex_id,
-                            Some(UiArgs {
-                                coords: [x, y].into(),
-                                dimensions: [w, h].into(),
-                                tex_coord_bounds: [0., 0., 1., 1.].into(),
-                                color: tint.unwrap_or([1., 1., 1., 1.]).into(),
-                            }),
-                        )
+                            glyph_data
+                                .sel_vertices
+                                .iter()
+                                .filter_map(|args| place(fade(*args, alpha))),
+                        );
                     }
-                }
 
-                if glyph_data.vertices.len() > 0 {
-                    self.batches
-                        .insert(glyph_tex_id, glyph_data.vertices.iter().cloned());
+                    // blinking cursor
+                    if selected.contains(entity) {
+                        if let Some(editing) = text_editings.get(entity) {
+                            let blink_on =
+                                editing.cursor_blink_timer < editing.cursor_blink_period * 0.5;
+                            let (w, h) = match (blink_on, editing.use_block_cursor) {
+                                // use degenerate quad, but still insert so batches will not change
+                                (false, false) => (0., 0.),
+                                (true, false) => (2., glyph_data.height),
+                                (false, true) => {
+                                    (glyph_data.space_width, 1.0f32.max(glyph_data.height * 0.1))
+                                }
+                                (true, true) => (glyph_data.space_width, glyph_data.height),
+                            };
+                            // align to baseline
+                            let base_x = glyph_data.cursor_pos.0 + w * 0.5;
+                            let base_y = glyph_data.cursor_pos.1 - (glyph_data.height - h) * 0.5;
+
+                            let min_x = transform.pixel_x + transform.pixel_width * -0.5;
+                            let max_x = transform.pixel_x + transform.pixel_width * 0.5;
+                            let min_y = transform.pixel_y + transform.pixel_height * -0.5;
+                            let max_y = transform.pixel_y + transform.pixel_height * 0.5;
+
+                            let left = (base_x - w * 0.5).max(min_x).min(max_x);
+                            let right = (base_x + w * 0.5).max(min_x).min(max_x);
+                            let top = (base_y - h * 0.5).max(min_y).min(max_y);
+                            let bottom = (base_y + h * 0.5).max(min_y).min(max_y);
+
+                            let x = (left + right) * 0.5;
+                            let y = (top + bottom) * 0.5;
+                            let w = right - left;
+                            let h = bottom - top;
+
+                            self.batches.insert(
+                                white_tex_id,
+                                place(UiArgs {
+                                    coords: [x, y].into(),
+                                    dimensions: [w, h].into(),
+                                    tex_coord_bounds: [0., 0., 1., 1.].into(),
+                                    color: tint.unwrap_or([1., 1., 1., 1.]).into(),
+                                    rotation: [1., 0.].into(),
+                                }),
+                            )
+                        }
+                    }
+
+                    if glyph_data.vertices.len() > 0 {
+                        // Shadows and outlines use the glyph texture too, so they are drawn
+                        // in the same batch as the text.
+                        let text = texts.get(entity);
+                        let decorations = text.map_or_else(Vec::new, |text| {
+                            text_decoration_quads(text, &glyph_data.vertices, tint)
+                        });
+                        let gradient = text
+                            .and_then(|text| text.gradient)
+                            .map(|gradient| gradient_quads(&glyph_data.vertices, gradient));
+                        self.batches.insert(
+                            glyph_tex_id,
+                            decorations
+                                .iter()
+                                .cloned()
+                                .chain(
+                                    gradient
+                                        .as_ref()
+                                        .unwrap_or(&glyph_data.vertices)
+                                        .iter()
+                                        .map(|args| fade(*args, alpha)),
+                                )
+                                .filter_map(place),
+                        );
+                    }
                 }
             }
+
+            if let Some(lines) = resources.try_fetch::<UiDebugLines>() {
+                let screen_size = (screen_dimesnions.width(), screen_dimesnions.height());
+                let elements = (&*entities, &transforms, !&hiddens, !&hidden_propagates)
+                    .join()
+                    .map(|(entity, transform, _, _)| {
+                        let parent = parents
+                            .get(entity)
+                            .and_then(|parent| transforms.get(parent.entity));
+                        (
+                            entity,
+                            transform,
+                            anchor_position(transform, parent, screen_size),
+                        )
+                    });
+                self.batches
+                    .insert(white_tex_id, debug_line_quads(&lines, elements));
+            }
+
+            if let Some(mut stats) = resources.try_fetch_mut::<UiRenderStats>() {
+                *stats = UiRenderStats::from_batches(&self.batches, &glyph_tex_id);
+            }
+
+            changed = changed || self.batches.changed();
         }
 
         self.textures.maintain(factory, resources);
-        changed = changed || self.batches.changed();
         self.vertex.write(
             factory,
             index,
@@ -462,51 +742,909 @@ fn build_ui_pipeline<B: Backend>(
     }
 }
 
+/// Sorts entities from the smallest z value to the largest one, so that elements with a
+/// higher z are drawn on top. Elements with equal z are ordered by entity id, which keeps
+/// the draw order stable between frames.
+fn sort_draw_order(cache: &mut [(f32, Entity)]) {
+    cache.sort_unstable_by(|&(z1, e1), &(z2, e2)| {
+        z1.partial_cmp(&z2)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| e1.id().cmp(&e2.id()))
+    });
+}
+
 fn mul_blend(a: &[f32; 4], b: &[f32; 4]) -> [f32; 4] {
     [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
 }
 
+/// Multiplies the alpha of the quad with the inherited `UiTint` alpha.
+fn fade(args: UiArgs, alpha: f32) -> UiArgs {
+    let [r, g, b, a]: [f32; 4] = args.color.into();
+    UiArgs {
+        color: [r, g, b, a * alpha].into(),
+        ..args
+    }
+}
+
+/// The vertex color of the quads of an image, its own color multiplied with the `Tint`,
+/// which includes the inherited `UiTint` alpha.
+fn image_color(raw_image: &UiImage, tint: &Option<[f32; 4]>) -> [f32; 4] {
+    let color = match raw_image {
+        UiImage::SolidColor(color) | UiImage::Texture { tint: color, .. } => *color,
+        _ => [1., 1., 1., 1.],
+    };
+    match tint {
+        Some(tint) => mul_blend(&color, tint),
+        None => color,
+    }
+}
+
+/// Batches the quads of an image. Returns whether its texture changed, or `None` when the
+/// texture isn't loaded yet and nothing was batched.
 fn render_image<B: Backend>(
     factory: &Factory<B>,
     resources: &Resources,
     transform: &UiTransform,
     raw_image: &UiImage,
     tint: &Option<[f32; 4]>,
+    clip: Option<[f32; 4]>,
     white_tex_id: TextureId,
     textures: &mut TextureSub<B>,
     batches: &mut OrderedOneLevelBatch<TextureId, UiArgs>,
-) -> bool {
-    let color = match (raw_image, tint.as_ref()) {
-        (UiImage::SolidColor(color), Some(t)) => mul_blend(color, t),
-        (UiImage::SolidColor(color), None) => color.clone(),
-        (_, Some(t)) => t.clone(),
-        (_, None) => [1., 1., 1., 1.],
-    };
+) -> Option<bool> {
+    let color = image_color(raw_image, tint);
 
     let args = UiArgs {
         coords: [transform.pixel_x(), transform.pixel_y()].into(),
         dimensions: [transform.pixel_width, transform.pixel_height].into(),
         tex_coord_bounds: [0., 0., 1., 1.].into(),
         color: color.into(),
+        rotation: [1., 0.].into(),
     };
 
     match raw_image {
-        UiImage::Texture(tex) => {
+        UiImage::Texture { texture, .. } => {
+            if let Some((tex_id, this_changed)) = textures.insert(
+                factory,
+                resources,
+                texture,
+                hal::image::Layout::ShaderReadOnlyOptimal,
+            ) {
+                batches.insert(
+                    tex_id,
+                    clip_quad(args, clip).map(|args| rotate_quad(args, transform)),
+                );
+                Some(this_changed)
+            } else {
+                None
+            }
+        }
+        UiImage::NinePatch {
+            texture,
+            border,
+            texture_size,
+        } => {
             if let Some((tex_id, this_changed)) = textures.insert(
                 factory,
                 resources,
-                tex,
+                texture,
                 hal::image::Layout::ShaderReadOnlyOptimal,
             ) {
-                batches.insert(tex_id, Some(args));
-                this_changed
+                let quads = nine_patch_quads(transform, *border, *texture_size, color);
+                batches.insert(
+                    tex_id,
+                    quads
+                        .iter()
+                        .filter_map(|q| clip_quad(*q, clip))
+                        .map(|q| rotate_quad(q, transform)),
+                );
+                Some(this_changed)
             } else {
-                false
+                None
+            }
+        }
+        UiImage::Tiled { texture, tile_size } => {
+            if let Some((tex_id, this_changed)) = textures.insert(
+                factory,
+                resources,
+                texture,
+                hal::image::Layout::ShaderReadOnlyOptimal,
+            ) {
+                let quads = tile_quads(transform, *tile_size, color);
+                batches.insert(
+                    tex_id,
+                    quads
+                        .iter()
+                        .filter_map(|q| clip_quad(*q, clip))
+                        .map(|q| rotate_quad(q, transform)),
+                );
+                Some(this_changed)
+            } else {
+                None
+            }
+        }
+        UiImage::SolidColor(_) => {
+            batches.insert(
+                white_tex_id,
+                clip_quad(args, clip).map(|args| rotate_quad(args, transform)),
+            );
+            Some(false)
+        }
+    }
+}
+
+/// Copies of the glyph quads of `text` for its shadow, then its outline, to be drawn
+/// before the glyphs. Empty when the text has neither.
+fn text_decoration_quads(text: &UiText, glyphs: &[UiArgs], tint: Option<[f32; 4]>) -> Vec<UiArgs> {
+    const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    const DIRECTIONS: [[f32; 2]; 8] = [
+        [-DIAGONAL, DIAGONAL],
+        [0.0, 1.0],
+        [DIAGONAL, DIAGONAL],
+        [-1.0, 0.0],
+        [1.0, 0.0],
+        [-DIAGONAL, -DIAGONAL],
+        [0.0, -1.0],
+        [DIAGONAL, -DIAGONAL],
+    ];
+
+    let tint = tint.unwrap_or([1., 1., 1., 1.]);
+    let mut copies = Vec::new();
+    if let Some(shadow) = &text.shadow {
+        copies.push((shadow.offset, mul_blend(&shadow.color, &tint)));
+    }
+    if let Some(outline) = &text.outline {
+        let color = mul_blend(&outline.color, &tint);
+        copies.extend(
+            DIRECTIONS
+                .iter()
+                .map(|[x, y]| ([x * outline.width, y * outline.width], color)),
+        );
+    }
+
+    copies
+        .into_iter()
+        .flat_map(|([offset_x, offset_y], color)| {
+            glyphs.iter().map(move |glyph| {
+                let [x, y]: [f32; 2] = glyph.coords.into();
+                UiArgs {
+                    coords: [x + offset_x, y + offset_y].into(),
+                    color: color.into(),
+                    ..*glyph
+                }
+            })
+        })
+        .collect()
+}
+
+/// Cuts the glyph quads into strips at most `GRADIENT_STRIP_HEIGHT` pixels high, each
+/// colored with the `[top, bottom]` gradient where it is in the whole block of text.
+/// The gradient is multiplied with the color of the glyphs.
+fn gradient_quads(glyphs: &[UiArgs], gradient: [[f32; 4]; 2]) -> Vec<UiArgs> {
+    let (top, bottom) = glyphs.iter().fold(
+        (std::f32::NEG_INFINITY, std::f32::INFINITY),
+        |(top, bottom), glyph| {
+            let [_, y]: [f32; 2] = glyph.coords.into();
+            let [_, h]: [f32; 2] = glyph.dimensions.into();
+            (top.max(y + h * 0.5), bottom.min(y - h * 0.5))
+        },
+    );
+    let block_height = top - bottom;
+    let [top_color, bottom_color] = gradient;
+
+    glyphs
+        .iter()
+        .flat_map(|glyph| {
+            let [x, y]: [f32; 2] = glyph.coords.into();
+            let [w, h]: [f32; 2] = glyph.dimensions.into();
+            let [u_min, v_min, u_max, v_max]: [f32; 4] = glyph.tex_coord_bounds.into();
+            let color: [f32; 4] = glyph.color.into();
+            let strips = (h / GRADIENT_STRIP_HEIGHT).ceil().max(1.0) as usize;
+            let strip_height = h / strips as f32;
+            let glyph_top = y + h * 0.5;
+
+            (0..strips).map(move |strip| {
+                let center = glyph_top - strip_height * (strip as f32 + 0.5);
+                let t = if block_height > 0.0 {
+                    (top - center) / block_height
+                } else {
+                    0.0
+                };
+                let mix =
+                    |i: usize| (top_color[i] + (bottom_color[i] - top_color[i]) * t) * color[i];
+                // Texture coordinates start at the top left of the quad.
+                let v = |strip: f32| v_min + (v_max - v_min) * strip / strips as f32;
+                UiArgs {
+                    coords: [x, center].into(),
+                    dimensions: [w, strip_height].into(),
+                    tex_coord_bounds: [u_min, v(strip as f32), u_max, v(strip as f32 + 1.0)].into(),
+                    color: [mix(0), mix(1), mix(2), mix(3)].into(),
+                    rotation: glyph.rotation,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Returns the `[min_x, min_y, max_x, max_y]` area the entity is clipped to, which is the
+/// intersection of the bounds of every `UiScrollContainer` above it in the hierarchy.
+fn scroll_clip_rect(
+    entity: Entity,
+    parents: &ReadStorage<'_, Parent>,
+    transforms: &ReadStorage<'_, UiTransform>,
+    scroll_containers: &ReadStorage<'_, UiScrollContainer>,
+) -> Option<[f32; 4]> {
+    let mut clip: Option<[f32; 4]> = None;
+    let mut current = entity;
+    while let Some(parent) = parents.get(current) {
+        current = parent.entity;
+        if let (Some(_), Some(t)) = (scroll_containers.get(current), transforms.get(current)) {
+            let rect = [
+                t.pixel_x - t.pixel_width * 0.5,
+                t.pixel_y - t.pixel_height * 0.5,
+                t.pixel_x + t.pixel_width * 0.5,
+                t.pixel_y + t.pixel_height * 0.5,
+            ];
+            clip = Some(match clip {
+                Some(c) => [
+                    c[0].max(rect[0]),
+                    c[1].max(rect[1]),
+                    c[2].min(rect[2]),
+                    c[3].min(rect[3]),
+                ],
+                None => rect,
+            });
+        }
+    }
+    clip
+}
+
+/// Rotates the quad around the pivot of the transform by `UiTransform::rotation`.
+/// The quad is moved here, and turned around its own center by the vertex shader.
+fn rotate_quad(args: UiArgs, transform: &UiTransform) -> UiArgs {
+    if transform.rotation == 0.0 {
+        return args;
+    }
+    let (sin, cos) = transform.rotation.sin_cos();
+    let (pivot_x, pivot_y) = transform.pivot_position();
+    let [x, y]: [f32; 2] = args.coords.into();
+    let (dx, dy) = (x - pivot_x, y - pivot_y);
+    UiArgs {
+        coords: [pivot_x + dx * cos - dy * sin, pivot_y + dx * sin + dy * cos].into(),
+        rotation: [cos, sin].into(),
+        ..args
+    }
+}
+
+/// Returns the point in the parent, or on the screen for root elements, the transform is
+/// anchored to.
+fn anchor_position(
+    transform: &UiTransform,
+    parent: Option<&UiTransform>,
+    screen_size: (f32, f32),
+) -> (f32, f32) {
+    let (x, y) = transform.anchor.norm_offset();
+    match parent {
+        Some(parent) => (
+            parent.pixel_x + parent.pixel_width * x,
+            parent.pixel_y + parent.pixel_height * y,
+        ),
+        None => (screen_size.0 * (0.5 + x), screen_size.1 * (0.5 + y)),
+    }
+}
+
+/// Quads outlining each element and marking its anchor point, or none when the
+/// `UiDebugLines` are disabled.
+fn debug_line_quads<'a>(
+    lines: &UiDebugLines,
+    elements: impl IntoIterator<Item = (Entity, &'a UiTransform, (f32, f32))>,
+) -> Vec<UiArgs> {
+    if !lines.enabled {
+        return Vec::new();
+    }
+    let t = lines.thickness;
+    let quad = |x: f32, y: f32, w: f32, h: f32, color: [f32; 4]| UiArgs {
+        coords: [x, y].into(),
+        dimensions: [w, h].into(),
+        tex_coord_bounds: [0., 0., 1., 1.].into(),
+        color: color.into(),
+        rotation: [1., 0.].into(),
+    };
+    elements
+        .into_iter()
+        .flat_map(|(entity, transform, anchor)| {
+            let color = DEBUG_COLORS[entity.id() as usize % DEBUG_COLORS.len()];
+            let (x, y) = (transform.pixel_x, transform.pixel_y);
+            let (w, h) = (transform.pixel_width, transform.pixel_height);
+            let edges = vec![
+                quad(x - (w - t) * 0.5, y, t, h, color),
+                quad(x + (w - t) * 0.5, y, t, h, color),
+                quad(x, y + (h - t) * 0.5, w, t, color),
+                quad(x, y - (h - t) * 0.5, w, t, color),
+            ];
+            edges
+                .into_iter()
+                .map(move |edge| rotate_quad(edge, transform))
+                .chain(Some(quad(anchor.0, anchor.1, t * 4.0, t * 4.0, color)))
+        })
+        .collect()
+}
+
+/// Trims a quad to the `[min_x, min_y, max_x, max_y]` clip rect, shrinking its texture
+/// coordinates to match. Returns `None` when nothing of the quad is left.
+fn clip_quad(args: UiArgs, clip: Option<[f32; 4]>) -> Option<UiArgs> {
+    let clip = match clip {
+        Some(clip) => clip,
+        None => return Some(args),
+    };
+    let [x, y]: [f32; 2] = args.coords.into();
+    let [w, h]: [f32; 2] = args.dimensions.into();
+    let [u_min, v_min, u_max, v_max]: [f32; 4] = args.tex_coord_bounds.into();
+
+    let (left, right) = (x - w * 0.5, x + w * 0.5);
+    let (bottom, top) = (y - h * 0.5, y + h * 0.5);
+    let (new_left, new_right) = (left.max(clip[0]), right.min(clip[2]));
+    let (new_bottom, new_top) = (bottom.max(clip[1]), top.min(clip[3]));
+    if new_left >= new_right || new_bottom >= new_top {
+        return None;
+    }
+
+    // Texture coordinates start at the top left of the quad.
+    let u = |px: f32| u_min + (u_max - u_min) * (px - left) / w;
+    let v = |py: f32| v_min + (v_max - v_min) * (top - py) / h;
+    Some(UiArgs {
+        coords: [(new_left + new_right) * 0.5, (new_bottom + new_top) * 0.5].into(),
+        dimensions: [new_right - new_left, new_top - new_bottom].into(),
+        tex_coord_bounds: [u(new_left), v(new_top), u(new_right), v(new_bottom)].into(),
+        color: args.color,
+        rotation: args.rotation,
+    })
+}
+
+/// Splits the transform area into nine quads, from the top left to the bottom right
+/// row by row. Corners keep the border size, shrunk proportionally when two opposite
+/// borders don't fit into the transform.
+fn nine_patch_quads(
+    transform: &UiTransform,
+    border: [f32; 4],
+    texture_size: [u32; 2],
+    color: [f32; 4],
+) -> [UiArgs; 9] {
+    let [left, right, top, bottom] = border;
+    let width = transform.pixel_width;
+    let height = transform.pixel_height;
+    let scale_x = if left + right > width {
+        width / (left + right)
+    } else {
+        1.0
+    };
+    let scale_y = if top + bottom > height {
+        height / (top + bottom)
+    } else {
+        1.0
+    };
+
+    let min_x = transform.pixel_x() - width * 0.5;
+    let max_x = transform.pixel_x() + width * 0.5;
+    let min_y = transform.pixel_y() - height * 0.5;
+    let max_y = transform.pixel_y() + height * 0.5;
+    let xs = [
+        min_x,
+        min_x + left * scale_x,
+        max_x - right * scale_x,
+        max_x,
+    ];
+    // y grows upwards, texture coordinates downwards
+    let ys = [
+        max_y,
+        max_y - top * scale_y,
+        min_y + bottom * scale_y,
+        min_y,
+    ];
+
+    let tex_w = texture_size[0] as f32;
+    let tex_h = texture_size[1] as f32;
+    let us = [0.0, left / tex_w, 1.0 - right / tex_w, 1.0];
+    let vs = [0.0, top / tex_h, 1.0 - bottom / tex_h, 1.0];
+
+    let mut quads = [UiArgs {
+        coords: [0.0, 0.0].into(),
+        dimensions: [0.0, 0.0].into(),
+        tex_coord_bounds: [0.0, 0.0, 0.0, 0.0].into(),
+        color: color.into(),
+        rotation: [1., 0.].into(),
+    }; 9];
+    for row in 0..3 {
+        for col in 0..3 {
+            quads[row * 3 + col] = UiArgs {
+                coords: [(xs[col] + xs[col + 1]) * 0.5, (ys[row] + ys[row + 1]) * 0.5].into(),
+                dimensions: [xs[col + 1] - xs[col], ys[row] - ys[row + 1]].into(),
+                tex_coord_bounds: [us[col], vs[row], us[col + 1], vs[row + 1]].into(),
+                color: color.into(),
+                rotation: [1., 0.].into(),
+            };
+        }
+    }
+    quads
+}
+
+/// Covers the transform area with quads of `tile_size`, row by row from the top left.
+/// The last tile of each row and column is cut to the transform, together with its
+/// texture coordinates.
+fn tile_quads(transform: &UiTransform, tile_size: [f32; 2], color: [f32; 4]) -> Vec<UiArgs> {
+    let [tile_w, tile_h] = tile_size;
+    if tile_w <= 0.0 || tile_h <= 0.0 {
+        return Vec::new();
+    }
+    let min_x = transform.pixel_x() - transform.pixel_width * 0.5;
+    let max_x = transform.pixel_x() + transform.pixel_width * 0.5;
+    let min_y = transform.pixel_y() - transform.pixel_height * 0.5;
+    let max_y = transform.pixel_y() + transform.pixel_height * 0.5;
+    let columns = (transform.pixel_width / tile_w).ceil().max(0.0) as usize;
+    let rows = (transform.pixel_height / tile_h).ceil().max(0.0) as usize;
+
+    let mut quads = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        // y grows upwards, texture coordinates downwards
+        let top = max_y - tile_h * row as f32;
+        let bottom = (top - tile_h).max(min_y);
+        for col in 0..columns {
+            let left = min_x + tile_w * col as f32;
+            let right = (left + tile_w).min(max_x);
+            quads.push(UiArgs {
+                coords: [(left + right) * 0.5, (top + bottom) * 0.5].into(),
+                dimensions: [right - left, top - bottom].into(),
+                tex_coord_bounds: [0.0, 0.0, (right - left) / tile_w, (top - bottom) / tile_h]
+                    .into(),
+                color: color.into(),
+                rotation: [1., 0.].into(),
+            });
+        }
+    }
+    quads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Anchor;
+    use amethyst_core::ecs::{Builder, World};
+
+    fn quad(coords: [f32; 2], dimensions: [f32; 2], tex_coord_bounds: [f32; 4]) -> UiArgs {
+        UiArgs {
+            coords: coords.into(),
+            dimensions: dimensions.into(),
+            tex_coord_bounds: tex_coord_bounds.into(),
+            color: [1.0; 4].into(),
+            rotation: [1., 0.].into(),
+        }
+    }
+
+    #[test]
+    fn render_stats_count_batches() {
+        const WHITE: u32 = 0;
+        const GLYPHS: u32 = 1;
+        const ICON: u32 = 2;
+        let glyph = quad([0.0; 2], [8.0; 2], [0.0, 0.0, 0.1, 0.1]);
+        let image = quad([0.0; 2], [32.0; 2], [0.0, 0.0, 1.0, 1.0]);
+
+        // A button with a label, an icon, and a text field.
+        let mut batches = OrderedOneLevelBatch::<u32, UiArgs>::default();
+        batches.insert(WHITE, vec![image]);
+        batches.insert(GLYPHS, vec![glyph; 4]);
+        batches.insert(ICON, vec![image]);
+        batches.insert(WHITE, vec![image]);
+        batches.insert(GLYPHS, vec![glyph; 6]);
+
+        assert_eq!(
+            UiRenderStats::from_batches(&batches, &GLYPHS),
+            UiRenderStats {
+                draw_calls: 5,
+                glyphs: 10,
+                quads: 13,
+            }
+        );
+    }
+
+    #[test]
+    fn textured_image_color_is_its_tint() {
+        let pool = rayon::ThreadPoolBuilder::new().build().unwrap();
+        let loader = Loader::new(".", std::sync::Arc::new(pool));
+        let storage = AssetStorage::<Texture>::new();
+        let texture = loader.load_from_data(
+            load_from_srgba(palette::Srgba::new(1., 1., 1., 1.)).into(),
+            (),
+            &storage,
+        );
+        let faction = [0.8, 0.1, 0.1, 1.0];
+        let image = UiImage::Texture {
+            texture,
+            tint: faction,
+        };
+
+        assert_eq!(image_color(&image, &None), faction);
+        // The `Tint` passed in includes the alpha inherited from a `UiTint`.
+        assert_eq!(
+            image_color(&image, &Some([1.0, 1.0, 1.0, 0.5])),
+            [0.8, 0.1, 0.1, 0.5]
+        );
+    }
+
+    #[test]
+    fn same_texture_images_share_a_draw_call() {
+        const ATLAS: u32 = 3;
+        const OTHER: u32 = 4;
+        let image = quad([0.0; 2], [32.0; 2], [0.0, 0.0, 1.0, 1.0]);
+        let draw = |textures: &[u32]| {
+            let mut batches = OrderedOneLevelBatch::<u32, UiArgs>::default();
+            for tex in textures {
+                batches.insert(*tex, vec![image]);
+            }
+            let order = batches.iter().map(|(tex, _)| *tex).collect::<Vec<_>>();
+            (UiRenderStats::from_batches(&batches, &0).draw_calls, order)
+        };
+
+        assert_eq!(draw(&[ATLAS; 10]), (1, vec![ATLAS]));
+        assert_eq!(
+            draw(&[ATLAS, ATLAS, ATLAS, OTHER, OTHER]),
+            (2, vec![ATLAS, OTHER])
+        );
+        // Images sorted by z keep their order, even when it splits a texture's batch.
+        assert_eq!(draw(&[ATLAS, OTHER, ATLAS]), (3, vec![ATLAS, OTHER, ATLAS]));
+    }
+
+    #[test]
+    fn nine_patch_keeps_corner_size() {
+        let transform = UiTransform::new(
+            "panel".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            100.0,
+            100.0,
+            0.0,
+            200.0,
+            100.0,
+        );
+        let quads = nine_patch_quads(&transform, [10.0, 20.0, 5.0, 15.0], [64, 32], [1.0; 4]);
+        let (u1, u2) = (10.0 / 64.0, 1.0 - 20.0 / 64.0);
+        let (v1, v2) = (5.0 / 32.0, 1.0 - 15.0 / 32.0);
+
+        // top left
+        assert_eq!(
+            quads[0],
+            quad([5.0, 147.5], [10.0, 5.0], [0.0, 0.0, u1, v1])
+        );
+        // top right
+        assert_eq!(
+            quads[2],
+            quad([190.0, 147.5], [20.0, 5.0], [u2, 0.0, 1.0, v1])
+        );
+        // center
+        assert_eq!(
+            quads[4],
+            quad([95.0, 105.0], [170.0, 80.0], [u1, v1, u2, v2])
+        );
+        // bottom left
+        assert_eq!(
+            quads[6],
+            quad([5.0, 57.5], [10.0, 15.0], [0.0, v2, u1, 1.0])
+        );
+        // bottom right
+        assert_eq!(
+            quads[8],
+            quad([190.0, 57.5], [20.0, 15.0], [u2, v2, 1.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn nine_patch_clamps_borders() {
+        let transform = UiTransform::new(
+            "small".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            20.0,
+            20.0,
+        );
+        let quads = nine_patch_quads(&transform, [30.0, 10.0, 5.0, 5.0], [64, 64], [1.0; 4]);
+        let (u1, u2) = (30.0 / 64.0, 1.0 - 10.0 / 64.0);
+        let (v1, v2) = (5.0 / 64.0, 1.0 - 5.0 / 64.0);
+
+        // Left and right borders are scaled down by half to fit the width.
+        assert_eq!(quads[3], quad([-2.5, 0.0], [15.0, 10.0], [0.0, v1, u1, v2]));
+        assert_eq!(quads[4], quad([5.0, 0.0], [0.0, 10.0], [u1, v1, u2, v2]));
+        assert_eq!(quads[5], quad([7.5, 0.0], [5.0, 10.0], [u2, v1, 1.0, v2]));
+    }
+
+    #[test]
+    fn tiles_repeat_over_transform() {
+        let transform = UiTransform::new(
+            "floor".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            50.0,
+            20.0,
+            0.0,
+            100.0,
+            40.0,
+        );
+        let quads = tile_quads(&transform, [32.0, 16.0], [1.0; 4]);
+        // Four columns and three rows, the last of each cut off.
+        assert_eq!(quads.len(), 4 * 3);
+
+        // top left
+        assert_eq!(
+            quads[0],
+            quad([16.0, 32.0], [32.0, 16.0], [0.0, 0.0, 1.0, 1.0])
+        );
+        // top right, 4 of 32 pixels wide
+        assert_eq!(
+            quads[3],
+            quad([98.0, 32.0], [4.0, 16.0], [0.0, 0.0, 0.125, 1.0])
+        );
+        // bottom right, 8 of 16 pixels high
+        assert_eq!(
+            quads[11],
+            quad([98.0, 4.0], [4.0, 8.0], [0.0, 0.0, 0.125, 0.5])
+        );
+    }
+
+    #[test]
+    fn quads_rotate_around_pivot() {
+        let mut transform = UiTransform::new(
+            "needle".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            50.0,
+            50.0,
+            0.0,
+            40.0,
+            10.0,
+        )
+        .with_rotation(std::f32::consts::FRAC_PI_2);
+        let rotated = rotate_quad(quad([50.0, 50.0], [40.0, 10.0], [0.0; 4]), &transform);
+        let coords: [f32; 2] = rotated.coords.into();
+        let dimensions: [f32; 2] = rotated.dimensions.into();
+        assert_eq!((coords, dimensions), ([50.0, 50.0], [40.0, 10.0]));
+
+        // A glyph at the right end of the needle ends up at its top.
+        let rotated = rotate_quad(quad([65.0, 50.0], [10.0, 10.0], [0.0; 4]), &transform);
+        let [x, y]: [f32; 2] = rotated.coords.into();
+        let [cos, sin]: [f32; 2] = rotated.rotation.into();
+        assert!((x - 50.0).abs() < 1e-4 && (y - 65.0).abs() < 1e-4);
+        assert!(cos.abs() < 1e-6 && (sin - 1.0).abs() < 1e-6);
+
+        // Hit-testing follows the rotated bounds.
+        transform.pixel_x = 50.0;
+        transform.pixel_y = 50.0;
+        assert!(transform.position_inside(52.0, 65.0));
+        assert!(!transform.position_inside(65.0, 52.0));
+    }
+
+    #[test]
+    fn debug_lines_outline_every_transform() {
+        let mut world = World::new();
+        let panel = world.create_entity().build();
+        let button = world.create_entity().build();
+        let mut panel_transform = UiTransform::new(
+            "panel".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            200.0,
+            100.0,
+        );
+        crate::layout::process_root_iter(
+            std::iter::once(&mut panel_transform),
+            &ScreenDimensions::new(800, 600, 1.0),
+            1.0,
+        );
+        let button_transform = UiTransform::new(
+            "button".to_string(),
+            Anchor::TopLeft,
+            Anchor::TopLeft,
+            350.0,
+            310.0,
+            0.0,
+            50.0,
+            20.0,
+        );
+        let elements = || {
+            vec![
+                (
+                    panel,
+                    &panel_transform,
+                    anchor_position(&panel_transform, None, (800.0, 600.0)),
+                ),
+                (
+                    button,
+                    &button_transform,
+                    anchor_position(&button_transform, Some(&panel_transform), (800.0, 600.0)),
+                ),
+            ]
+        };
+
+        let mut lines = UiDebugLines::default();
+        assert!(debug_line_quads(&lines, elements()).is_empty());
+
+        lines.enabled = true;
+        let quads = debug_line_quads(&lines, elements());
+        // Four edges and the anchor point per transform.
+        assert_eq!(quads.len(), 2 * 5);
+        // The left edge of the panel, at its resolved position.
+        let [x, y]: [f32; 2] = quads[0].coords.into();
+        let [w, h]: [f32; 2] = quads[0].dimensions.into();
+        assert_eq!((x, y, w, h), (300.5, 300.0, 1.0, 100.0));
+        // The button is anchored to the top left corner of the panel.
+        let [x, y]: [f32; 2] = quads[9].coords.into();
+        assert_eq!((x, y), (300.0, 350.0));
+        assert_ne!(quads[0].color, quads[5].color);
+    }
+
+    #[test]
+    fn draw_order_sorts_by_z_then_entity() {
+        let mut world = World::new();
+        let hud = world.create_entity().build();
+        let dialog = world.create_entity().build();
+        let label = world.create_entity().build();
+        let tooltip = world.create_entity().build();
+
+        let mut cache = vec![(2.0, dialog), (0.0, hud), (5.0, tooltip), (2.0, label)];
+        sort_draw_order(&mut cache);
+        assert_eq!(
+            cache,
+            vec![(0.0, hud), (2.0, dialog), (2.0, label), (5.0, tooltip)]
+        );
+
+        let mut cache = vec![(2.0, label), (5.0, tooltip), (2.0, dialog), (0.0, hud)];
+        sort_draw_order(&mut cache);
+        assert_eq!(
+            cache,
+            vec![(0.0, hud), (2.0, dialog), (2.0, label), (5.0, tooltip)]
+        );
+    }
+
+    #[test]
+    fn clip_quad_trims_texture_coordinates() {
+        let clip = Some([0.0, 0.0, 100.0, 100.0]);
+        let args = quad([100.0, 50.0], [40.0, 20.0], [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            clip_quad(args, clip),
+            Some(quad([90.0, 50.0], [20.0, 20.0], [0.0, 0.0, 0.5, 1.0]))
+        );
+
+        let args = quad([50.0, 100.0], [20.0, 40.0], [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            clip_quad(args, clip),
+            Some(quad([50.0, 90.0], [20.0, 20.0], [0.0, 0.5, 1.0, 1.0]))
+        );
+
+        let outside = quad([150.0, 50.0], [20.0, 20.0], [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(clip_quad(outside, clip), None);
+        assert_eq!(clip_quad(outside, None), Some(outside));
+    }
+
+    #[test]
+    fn shadow_and_outline_add_glyph_copies() {
+        use crate::{FontAsset, TextOutline, TextShadow};
+        use glyph_brush::rusttype::Font;
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let mut text = UiText::new(font, "ab".to_string(), [1.0; 4], 16.0);
+        let glyphs = [
+            quad([10.0, 10.0], [8.0, 8.0], [0.0, 0.0, 0.5, 0.5]),
+            quad([20.0, 10.0], [8.0, 8.0], [0.5, 0.0, 1.0, 0.5]),
+        ];
+        assert!(text_decoration_quads(&text, &glyphs, None).is_empty());
+
+        let black = [0.0, 0.0, 0.0, 1.0];
+        text.shadow = Some(TextShadow {
+            offset: [2.0, -2.0],
+            color: black,
+        });
+        let quads = text_decoration_quads(&text, &glyphs, None);
+        assert_eq!(
+            quads,
+            vec![
+                UiArgs {
+                    color: black.into(),
+                    ..quad([12.0, 8.0], [8.0, 8.0], [0.0, 0.0, 0.5, 0.5])
+                },
+                UiArgs {
+                    color: black.into(),
+                    ..quad([22.0, 8.0], [8.0, 8.0], [0.5, 0.0, 1.0, 0.5])
+                },
+            ]
+        );
+
+        text.outline = Some(TextOutline {
+            width: 1.0,
+            color: black,
+        });
+        let quads = text_decoration_quads(&text, &glyphs, Some([1.0, 1.0, 1.0, 0.5]));
+        // The shadow, then eight outline copies of each glyph.
+        assert_eq!(quads.len(), 2 + 8 * 2);
+        assert_eq!(quads[4].coords, [10.0, 11.0].into());
+        assert_eq!(quads[8].coords, [9.0, 10.0].into());
+        assert_eq!(quads[0].color, [0.0, 0.0, 0.0, 0.5].into());
+    }
+
+    #[test]
+    fn gradient_spans_the_whole_text() {
+        const TOP: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+        const BOTTOM: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+        // A tall glyph from y = 0 to 20 and a short one from 0 to 4.
+        let glyphs = [
+            quad([5.0, 10.0], [10.0, 20.0], [0.0, 0.0, 0.5, 1.0]),
+            quad([15.0, 2.0], [10.0, 4.0], [0.5, 0.5, 1.0, 1.0]),
+        ];
+        let strips = gradient_quads(&glyphs, [TOP, BOTTOM]);
+        assert_eq!(strips.len(), 10 + 2);
+
+        let color = |strip: &UiArgs| -> [f32; 4] { strip.color.into() };
+        // The top and bottom strips of the tall glyph are half a strip from the ends.
+        assert!((color(&strips[0])[1] - 0.95).abs() < 1e-6);
+        assert!((color(&strips[9])[1] - 0.05).abs() < 1e-6);
+        assert_eq!(color(&strips[0])[0], 1.0);
+        assert_eq!(strips[0].coords, [5.0, 19.0].into());
+        assert_eq!(strips[0].tex_coord_bounds, [0.0, 0.0, 0.5, 0.1].into());
+        // The short glyph samples the same colors as the bottom of the tall one.
+        assert_eq!(color(&strips[11]), color(&strips[9]));
+        assert_eq!(strips[11].tex_coord_bounds, [0.5, 0.75, 1.0, 1.0].into());
+    }
+
+    #[test]
+    fn unchanged_ui_is_batched_once() {
+        let mut reuse = BatchReuse::default();
+        let mut dirty = UiDirty::default();
+        let mut batched = 0;
+        for _ in 0..2 {
+            if reuse.needs_batching(dirty.take(), (800.0, 600.0), vec![3]) {
+                batched += 1;
             }
         }
-        _ => {
-            batches.insert(white_tex_id, Some(args));
-            false
+        assert_eq!(batched, 1);
+
+        dirty.mark();
+        assert!(reuse.needs_batching(dirty.take(), (800.0, 600.0), vec![3]));
+        assert!(!dirty.is_dirty());
+        // Resizing the window and showing an element batch again too.
+        assert!(reuse.needs_batching(false, (1024.0, 768.0), vec![3]));
+        assert!(reuse.needs_batching(false, (1024.0, 768.0), vec![]));
+        assert!(!reuse.needs_batching(false, (1024.0, 768.0), vec![]));
+    }
+
+    #[test]
+    fn changed_images_and_tints_are_seen_once() {
+        let mut world = World::new();
+        world.register::<UiImage>();
+        world.register::<UiTint>();
+        let entity = world.create_entity().build();
+        let mut changes = StorageChanges::default();
+        assert!(!changes.any(&world.res));
+
+        world
+            .write_storage::<UiImage>()
+            .insert(entity, UiImage::SolidColor([1.0; 4]))
+            .unwrap();
+        assert!(changes.any(&world.res));
+        assert!(!changes.any(&world.res));
+
+        world
+            .write_storage::<UiTint>()
+            .insert(entity, UiTint::new(0.5))
+            .unwrap();
+        assert!(changes.any(&world.res));
+        if let Some(image) = world.write_storage::<UiImage>().get_mut(entity) {
+            *image = UiImage::SolidColor([0.0; 4]);
         }
+        assert!(changes.any(&world.res));
+        assert!(!changes.any(&world.res));
     }
 }
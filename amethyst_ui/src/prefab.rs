@@ -18,9 +18,10 @@ use amethyst_rendy::TexturePrefab;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    get_default_font, Anchor, FontAsset, Interactable, LineMode, Selectable, Stretch, TextEditing,
-    UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionType, UiImage,
-    UiPlaySoundAction, UiSoundRetrigger, UiText, UiTransform, WidgetId, Widgets,
+    get_default_font, Anchor, FontAsset, Interactable, Justify, LineMode, Overflow, RasterMode,
+    Selectable, Stretch, TabWidth, TextDirection, TextEditing, TextOutline, TextShadow, UiButton,
+    UiButtonAction, UiButtonActionRetrigger, UiButtonActionType, UiImage, UiPlaySoundAction,
+    UiSoundRetrigger, UiText, UiTransform, WidgetId, Widgets,
 };
 
 /// Loadable `UiTransform` data.
@@ -42,6 +43,8 @@ pub struct UiTransformBuilder<G> {
     pub width: f32,
     /// The height of this UI element.
     pub height: f32,
+    /// Counterclockwise rotation around the pivot, in radians.
+    pub rotation: f32,
     /// Indicates if actions on the ui can go through this element.
     /// If set to false, the element will behaves as if it was transparent and will let events go to
     /// the next element (for example, the text on a button).
@@ -99,6 +102,12 @@ impl<G> UiTransformBuilder<G> {
         self
     }
 
+    /// Set rotation
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
     /// Set to event transparent
     pub fn transparent(mut self) -> Self {
         self.opaque = false;
@@ -158,7 +167,8 @@ where
             self.z,
             self.width,
             self.height,
-        );
+        )
+        .with_rotation(self.rotation);
         if let Some(ref stretch) = self.stretch {
             transform = transform.with_stretch(stretch.clone());
         }
@@ -208,6 +218,30 @@ pub struct UiTextBuilder {
     pub align: Option<Anchor>,
     /// How should the text behave with line breaks.
     pub line_mode: Option<LineMode>,
+    /// What to do with text that doesn't fit.
+    pub overflow: Option<Overflow>,
+    /// The direction lines are laid out in.
+    pub direction: Option<TextDirection>,
+    /// Whether lines are stretched to the width of the transform.
+    pub justify: Option<Justify>,
+    /// The distance between lines, as a multiple of the font's ascent plus descent.
+    pub line_height: Option<f32>,
+    /// The most lines wrapped text is drawn on, the last one ending with "…".
+    pub max_lines: Option<usize>,
+    /// Colors at the top and bottom of the text.
+    pub gradient: Option<[[f32; 4]; 2]>,
+    /// A shadow drawn under the text.
+    pub shadow: Option<TextShadow>,
+    /// An outline drawn around the text.
+    pub outline: Option<TextOutline>,
+    /// Whether glyphs are moved to whole pixels. Defaults to true.
+    pub pixel_snap: Option<bool>,
+    /// Whether pairs of glyphs are kerned. Defaults to true.
+    pub kerning: Option<bool>,
+    /// Whether glyph edges are antialiased. Defaults to `RasterMode::Smooth`.
+    pub raster_mode: Option<RasterMode>,
+    /// The distance between tab stops. Defaults to 4 spaces.
+    pub tab_width: Option<TabWidth>,
     /// Optionally make the text editable
     #[serde(default)]
     pub editable: Option<TextEditingPrefab>,
@@ -270,6 +304,34 @@ impl<'a> PrefabData<'a> for UiTextBuilder {
             ui_text.line_mode = line_mode.clone();
         }
 
+        if let Some(overflow) = self.overflow {
+            ui_text.overflow = overflow;
+        }
+
+        if let Some(direction) = self.direction {
+            ui_text.direction = direction;
+        }
+        if let Some(justify) = self.justify {
+            ui_text.justify = justify;
+        }
+        if let Some(pixel_snap) = self.pixel_snap {
+            ui_text.pixel_snap = pixel_snap;
+        }
+        if let Some(kerning) = self.kerning {
+            ui_text.kerning = kerning;
+        }
+        if let Some(raster_mode) = self.raster_mode {
+            ui_text.raster_mode = raster_mode;
+        }
+        if let Some(tab_width) = self.tab_width {
+            ui_text.tab_width = tab_width;
+        }
+        ui_text.line_height = self.line_height;
+        ui_text.max_lines = self.max_lines;
+        ui_text.gradient = self.gradient;
+        ui_text.shadow = self.shadow.clone();
+        ui_text.outline = self.outline.clone();
+
         texts.insert(entity, ui_text)?;
         if let Some(ref editing) = self.editable {
             editables.insert(
@@ -314,6 +376,22 @@ pub enum UiImageLoadPrefab {
     Texture(TexturePrefab),
     /// Solid color image
     SolidColor(f32, f32, f32, f32),
+    /// A textured image sliced into nine parts
+    NinePatch {
+        /// The sliced texture
+        texture: TexturePrefab,
+        /// Size of the borders in texture pixels, in order left, right, top, bottom
+        border: [f32; 4],
+        /// Size of the texture in pixels
+        texture_size: [u32; 2],
+    },
+    /// A textured image repeated to fill the transform
+    Tiled {
+        /// The repeated texture
+        texture: TexturePrefab,
+        /// Size of one repetition of the texture in pixels
+        tile_size: [f32; 2],
+    },
 }
 
 impl<'a> PrefabData<'a> for UiImagePrefab {
@@ -356,10 +434,24 @@ impl<'a> PrefabData<'a> for UiImageLoadPrefab {
         children: &[Entity],
     ) -> Result<UiImage, Error> {
         let image = match self {
-            UiImageLoadPrefab::Texture(tex) => {
-                UiImage::Texture(tex.add_to_entity(entity, textures, entities, children)?)
-            }
+            UiImageLoadPrefab::Texture(tex) => UiImage::Texture {
+                texture: tex.add_to_entity(entity, textures, entities, children)?,
+                tint: [1.0; 4],
+            },
             UiImageLoadPrefab::SolidColor(r, g, b, a) => UiImage::SolidColor([*r, *g, *b, *a]),
+            UiImageLoadPrefab::NinePatch {
+                texture,
+                border,
+                texture_size,
+            } => UiImage::NinePatch {
+                texture: texture.add_to_entity(entity, textures, entities, children)?,
+                border: *border,
+                texture_size: *texture_size,
+            },
+            UiImageLoadPrefab::Tiled { texture, tile_size } => UiImage::Tiled {
+                texture: texture.add_to_entity(entity, textures, entities, children)?,
+                tile_size: *tile_size,
+            },
         };
         Ok(image)
     }
@@ -371,6 +463,10 @@ impl<'a> PrefabData<'a> for UiImageLoadPrefab {
     ) -> Result<bool, Error> {
         match self {
             UiImageLoadPrefab::Texture(tex) => tex.load_sub_assets(progress, textures),
+            UiImageLoadPrefab::NinePatch { texture, .. }
+            | UiImageLoadPrefab::Tiled { texture, .. } => {
+                texture.load_sub_assets(progress, textures)
+            }
             UiImageLoadPrefab::SolidColor(..) => Ok(false),
         }
     }
@@ -815,6 +911,18 @@ fn walk_ui_tree<C, W>(
                 password: false,
                 align: None,
                 line_mode: None,
+                overflow: None,
+                direction: None,
+                justify: None,
+                line_height: None,
+                max_lines: None,
+                gradient: None,
+                shadow: None,
+                outline: None,
+                pixel_snap: None,
+                kerning: None,
+                raster_mode: None,
+                tab_width: None,
                 text: button.text.clone(),
                 font_size: button.font_size,
             };
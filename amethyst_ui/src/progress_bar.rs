@@ -0,0 +1,225 @@
+use amethyst_core::{
+    ecs::prelude::{Component, DenseVecStorage, Entities, Entity, Join, System, WriteStorage},
+    Parent,
+};
+
+use crate::{Anchor, UiImage, UiTransform};
+
+/// A bar filled in proportion to `value / max`, like a loading or health bar.
+///
+/// The `UiTransform` of the entity is the track of the bar. The `ProgressBarSystem` sets the
+/// entity's `UiImage` to `track_image` and draws `fill_image` over it, in a child entity
+/// growing from the left, or from the bottom for vertical bars.
+#[derive(Debug, Clone)]
+pub struct UiProgressBar {
+    /// The current value, clamped to `[0, max]` when drawn.
+    pub value: f32,
+    /// The value at which the bar is full.
+    pub max: f32,
+    /// Fill the bar from the bottom up instead of from left to right.
+    pub vertical: bool,
+    /// Image of the filled part of the bar.
+    pub fill_image: UiImage,
+    /// Image of the whole bar, drawn under the fill.
+    pub track_image: UiImage,
+    fill: Option<Entity>,
+}
+
+impl UiProgressBar {
+    /// Creates an empty horizontal bar.
+    pub fn new(max: f32, fill_image: UiImage, track_image: UiImage) -> Self {
+        UiProgressBar {
+            value: 0.0,
+            max,
+            vertical: false,
+            fill_image,
+            track_image,
+            fill: None,
+        }
+    }
+
+    /// Makes this bar fill from the bottom up.
+    pub fn with_vertical(mut self, vertical: bool) -> Self {
+        self.vertical = vertical;
+        self
+    }
+
+    /// The filled fraction of the bar, in `[0, 1]`.
+    pub fn ratio(&self) -> f32 {
+        if self.max > 0.0 {
+            (self.value / self.max).max(0.0).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The entity drawing the fill, once it was created by the `ProgressBarSystem`.
+    pub fn fill_entity(&self) -> Option<Entity> {
+        self.fill
+    }
+
+    fn fill_size(&self, track_width: f32, track_height: f32) -> (f32, f32) {
+        if self.vertical {
+            (track_width, track_height * self.ratio())
+        } else {
+            (track_width * self.ratio(), track_height)
+        }
+    }
+}
+
+impl Component for UiProgressBar {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Sizes the fill of every `UiProgressBar` to match its value.
+#[derive(Debug, Default)]
+pub struct ProgressBarSystem;
+
+impl ProgressBarSystem {
+    /// Creates a new ProgressBarSystem.
+    pub fn new() -> Self {
+        ProgressBarSystem
+    }
+}
+
+impl<'a> System<'a> for ProgressBarSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiProgressBar>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiImage>,
+        WriteStorage<'a, Parent>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut bars, mut transforms, mut images, mut parents): Self::SystemData,
+    ) {
+        for (entity, bar) in (&*entities, &mut bars).join() {
+            let (id, track_width, track_height) = match transforms.get(entity) {
+                Some(t) => (t.id.clone(), t.pixel_width, t.pixel_height),
+                None => continue,
+            };
+
+            if images.get(entity) != Some(&bar.track_image) {
+                images
+                    .insert(entity, bar.track_image.clone())
+                    .expect("unreachable: We are inserting");
+            }
+
+            let fill = match bar.fill.filter(|fill| entities.is_alive(*fill)) {
+                Some(fill) => fill,
+                None => {
+                    let fill = entities.create();
+                    parents
+                        .insert(fill, Parent { entity })
+                        .expect("unreachable: We are inserting");
+                    bar.fill = Some(fill);
+                    fill
+                }
+            };
+
+            // Only touch the fill when it changed, so the layout isn't redone every frame.
+            let (width, height) = bar.fill_size(track_width, track_height);
+            let anchor = if bar.vertical {
+                Anchor::BottomMiddle
+            } else {
+                Anchor::MiddleLeft
+            };
+            let up_to_date = transforms.get(fill).map_or(false, |t| {
+                t.width == width && t.height == height && t.anchor == anchor
+            });
+            if !up_to_date {
+                let transform = UiTransform::new(
+                    format!("{}_fill", id),
+                    anchor.clone(),
+                    anchor,
+                    0.0,
+                    0.0,
+                    0.01,
+                    width,
+                    height,
+                )
+                .as_transparent();
+                transforms
+                    .insert(fill, transform)
+                    .expect("unreachable: We are inserting");
+            }
+            if images.get(fill) != Some(&bar.fill_image) {
+                images
+                    .insert(fill, bar.fill_image.clone())
+                    .expect("unreachable: We are inserting");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+
+    fn bar(value: f32) -> UiProgressBar {
+        let mut bar = UiProgressBar::new(
+            200.0,
+            UiImage::SolidColor([0.0, 1.0, 0.0, 1.0]),
+            UiImage::SolidColor([0.2, 0.2, 0.2, 1.0]),
+        );
+        bar.value = value;
+        bar
+    }
+
+    #[test]
+    fn fill_follows_value() {
+        assert_eq!(bar(0.0).fill_size(100.0, 20.0), (0.0, 20.0));
+        assert_eq!(bar(100.0).fill_size(100.0, 20.0), (50.0, 20.0));
+        assert_eq!(bar(300.0).fill_size(100.0, 20.0), (100.0, 20.0));
+        assert_eq!(bar(-50.0).fill_size(100.0, 20.0), (0.0, 20.0));
+        assert_eq!(
+            bar(50.0).with_vertical(true).fill_size(20.0, 100.0),
+            (20.0, 25.0)
+        );
+    }
+
+    #[test]
+    fn system_creates_fill_entity() {
+        let mut world = World::new();
+        let mut system = ProgressBarSystem::new();
+        System::setup(&mut system, &mut world.res);
+
+        let health = world
+            .create_entity()
+            .with(UiTransform::new(
+                "health".to_string(),
+                Anchor::Middle,
+                Anchor::Middle,
+                0.0,
+                0.0,
+                0.0,
+                100.0,
+                20.0,
+            ))
+            .with(bar(100.0))
+            .build();
+        system.run_now(&world.res);
+
+        let fill = world
+            .read_storage::<UiProgressBar>()
+            .get(health)
+            .unwrap()
+            .fill_entity()
+            .expect("Fill entity wasn't created");
+        let transforms = world.read_storage::<UiTransform>();
+        let fill_transform = transforms.get(fill).unwrap();
+        assert_eq!((fill_transform.width, fill_transform.height), (50.0, 20.0));
+        assert_eq!(fill_transform.anchor, Anchor::MiddleLeft);
+        assert_eq!(
+            world.read_storage::<Parent>().get(fill),
+            Some(&Parent { entity: health })
+        );
+        assert_eq!(
+            world.read_storage::<UiImage>().get(health),
+            Some(&UiImage::SolidColor([0.2, 0.2, 0.2, 1.0]))
+        );
+    }
+}
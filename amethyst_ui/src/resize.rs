@@ -13,10 +13,17 @@ use super::*;
 /// entity's UiTransform, along with the new width and height of the window.
 ///
 /// The function in this component is also guaranteed to be called at least once by the
-/// `ResizeSystem` when either the component is attached, or the function is changed.
+/// `ResizeSystem` when either the component is attached, or the function is changed,
+/// unless the predicate set with `with_predicate` rejects the current window size.
 pub struct UiResize {
     /// The core function of this component
     pub function: Box<dyn FnMut(&mut UiTransform, (f32, f32)) + Send + Sync>,
+    /// Called with the new width and height of the window before `function`. When it returns
+    /// false, `function` is skipped and the transform keeps its previous size.
+    ///
+    /// Like `function`, it must be `Send + Sync` because the `ResizeSystem` can run on any
+    /// thread of the dispatcher.
+    should_resize: Option<Box<dyn Fn(f32, f32) -> bool + Send + Sync>>,
 }
 
 impl UiResize {
@@ -27,6 +34,25 @@ impl UiResize {
     {
         UiResize {
             function: Box::new(function),
+            should_resize: None,
+        }
+    }
+
+    /// Only resize when `predicate` returns true for the new width and height of the window.
+    pub fn with_predicate<P>(mut self, predicate: P) -> Self
+    where
+        P: Fn(f32, f32) -> bool + Send + Sync + 'static,
+    {
+        self.should_resize = Some(Box::new(predicate));
+        self
+    }
+
+    fn resize(&mut self, transform: &mut UiTransform, screen_size: (f32, f32)) {
+        let accepted = self.should_resize.as_ref().map_or(true, |should_resize| {
+            should_resize(screen_size.0, screen_size.1)
+        });
+        if accepted {
+            (self.function)(transform, screen_size);
         }
     }
 }
@@ -81,14 +107,14 @@ impl<'a> System<'a> for ResizeSystem {
         if self.screen_size != screen_size {
             self.screen_size = screen_size;
             for (transform, resize) in (&mut transform, &mut resize).join() {
-                (resize.function)(transform, screen_size);
+                resize.resize(transform, screen_size);
             }
         } else {
             // Immutable borrow
             let self_local_modified = &*self_local_modified;
             for (transform, resize, _) in (&mut transform, &mut resize, self_local_modified).join()
             {
-                (resize.function)(transform, screen_size);
+                resize.resize(transform, screen_size);
             }
         }
 
@@ -113,3 +139,89 @@ impl<'a> System<'a> for ResizeSystem {
         self.resize_events_id = Some(resize.register_reader());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use std::sync::{Arc, Mutex};
+
+    fn hud_transform() -> UiTransform {
+        UiTransform::new(
+            "hud".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            0.0,
+            0.0,
+            0.0,
+            10.0,
+            10.0,
+        )
+    }
+
+    #[test]
+    fn resizes_within_a_frame_are_coalesced() {
+        let mut world = World::new();
+        let mut system = ResizeSystem::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(100, 100, 1.0));
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        world
+            .create_entity()
+            .with(hud_transform())
+            .with(UiResize::new(move |_, size| {
+                recorded.lock().unwrap().push(size)
+            }))
+            .build();
+        system.run_now(&world.res);
+        assert_eq!(*calls.lock().unwrap(), vec![(100.0, 100.0)]);
+
+        {
+            let mut dimensions = world.write_resource::<ScreenDimensions>();
+            dimensions.update(200.0, 150.0);
+            dimensions.update(300.0, 250.0);
+            dimensions.update(400.0, 300.0);
+        }
+        system.run_now(&world.res);
+        assert_eq!(*calls.lock().unwrap(), vec![(100.0, 100.0), (400.0, 300.0)]);
+
+        system.run_now(&world.res);
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn predicate_can_reject_resize() {
+        let mut world = World::new();
+        let mut system = ResizeSystem::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(100, 100, 1.0));
+
+        let resize = UiResize::new(|transform: &mut UiTransform, (width, _height)| {
+            transform.width = width / 2.0;
+        })
+        .with_predicate(|width, height| width / height > 1.5);
+        let hud = world
+            .create_entity()
+            .with(hud_transform())
+            .with(resize)
+            .build();
+        let width = |world: &World| world.read_storage::<UiTransform>().get(hud).unwrap().width;
+
+        system.run_now(&world.res);
+        assert_eq!(width(&world), 10.0);
+
+        world
+            .write_resource::<ScreenDimensions>()
+            .update(400.0, 200.0);
+        system.run_now(&world.res);
+        assert_eq!(width(&world), 200.0);
+
+        world
+            .write_resource::<ScreenDimensions>()
+            .update(300.0, 300.0);
+        system.run_now(&world.res);
+        assert_eq!(width(&world), 200.0);
+    }
+}
@@ -0,0 +1,207 @@
+use amethyst_core::{
+    ecs::prelude::{Component, DenseVecStorage, Entities, Join, Read, System, Write, WriteStorage},
+    shrev::EventChannel,
+    Time,
+};
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{UiEvent, UiEventType, UiText};
+
+/// How much of the text a `UiTextReveal` shows at once.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+pub enum RevealMode {
+    /// Shows the text one grapheme at a time.
+    Character,
+    /// Shows the text one word at a time, once all of the graphemes of the word are due.
+    Word,
+}
+
+/// Reveals the `UiText` of the entity over time, like a typewriter.
+///
+/// The `TextRevealSystem` sets `UiText::visible_graphemes` from the elapsed time, and writes a
+/// `UiEventType::TextRevealed` event once the whole text is shown. Set `elapsed` to a large
+/// value to skip to the end, or back to zero to replay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiTextReveal {
+    /// How many graphemes are revealed per second.
+    pub chars_per_second: f32,
+    /// Time since the reveal started, in seconds.
+    pub elapsed: f32,
+    /// Whether the text is revealed character by character or word by word.
+    pub mode: RevealMode,
+    /// Whether the whole text was shown on the last update.
+    #[serde(skip)]
+    pub(crate) complete: bool,
+}
+
+impl UiTextReveal {
+    /// Creates a reveal showing `chars_per_second` graphemes per second, one at a time.
+    pub fn new(chars_per_second: f32) -> Self {
+        UiTextReveal {
+            chars_per_second,
+            elapsed: 0.0,
+            mode: RevealMode::Character,
+            complete: false,
+        }
+    }
+
+    /// Reveals the text word by word instead.
+    pub fn by_word(mut self) -> Self {
+        self.mode = RevealMode::Word;
+        self
+    }
+
+    /// Shows the whole text on the next update.
+    pub fn skip(&mut self) {
+        self.elapsed = std::f32::INFINITY;
+    }
+
+    /// Returns true once the whole text is shown.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Returns how many graphemes of `text` are shown.
+    pub fn visible_graphemes(&self, text: &str) -> usize {
+        let due = (self.elapsed * self.chars_per_second).max(0.0);
+        let graphemes = text.graphemes(true);
+        let total = graphemes.clone().count();
+        if due >= total as f32 {
+            return total;
+        }
+        let due = due as usize;
+        match self.mode {
+            RevealMode::Character => due,
+            // Only show up to the last whitespace that is due.
+            RevealMode::Word => graphemes
+                .take(due + 1)
+                .enumerate()
+                .filter(|(_, g)| g.chars().all(char::is_whitespace))
+                .map(|(i, _)| i)
+                .last()
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl Component for UiTextReveal {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// System advancing `UiTextReveal` components and limiting the text drawn by their `UiText`.
+#[derive(Debug, Default)]
+pub struct TextRevealSystem;
+
+impl TextRevealSystem {
+    /// Creates a new instance of this system
+    pub fn new() -> Self {
+        TextRevealSystem
+    }
+}
+
+impl<'a> System<'a> for TextRevealSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiTextReveal>,
+        WriteStorage<'a, UiText>,
+        Read<'a, Time>,
+        Write<'a, EventChannel<UiEvent>>,
+    );
+
+    fn run(&mut self, (entities, mut reveals, mut texts, time, mut events): Self::SystemData) {
+        for (entity, reveal, text) in (&*entities, &mut reveals, &mut texts).join() {
+            reveal.elapsed += time.delta_seconds();
+            let visible = reveal.visible_graphemes(&text.text);
+            let complete = visible == text.text.graphemes(true).count();
+            text.visible_graphemes = if complete { None } else { Some(visible) };
+
+            if complete && !reveal.complete {
+                events.single_write(UiEvent::new(UiEventType::TextRevealed, entity));
+            }
+            reveal.complete = complete;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FontAsset;
+    use amethyst_assets::AssetStorage;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use glyph_brush::rusttype::Font;
+
+    #[test]
+    fn visible_glyphs_grow_with_time() {
+        let mut world = World::new();
+        let mut system = TextRevealSystem::new();
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let entity = world
+            .create_entity()
+            .with(UiText::new(font, "Hi there".to_string(), [1.; 4], 10.))
+            .with(UiTextReveal::new(10.0))
+            .build();
+
+        let mut step = |world: &mut World, seconds: f32| {
+            world.write_resource::<Time>().set_delta_seconds(seconds);
+            system.run_now(&world.res);
+            world
+                .read_storage::<UiText>()
+                .get(entity)
+                .unwrap()
+                .visible_graphemes
+        };
+        assert_eq!(step(&mut world, 0.0), Some(0));
+        assert_eq!(step(&mut world, 0.25), Some(2));
+        assert_eq!(step(&mut world, 0.25), Some(5));
+        assert_eq!(
+            world
+                .read_resource::<EventChannel<UiEvent>>()
+                .read(&mut reader)
+                .count(),
+            0
+        );
+
+        world
+            .write_storage::<UiTextReveal>()
+            .get_mut(entity)
+            .unwrap()
+            .skip();
+        assert_eq!(step(&mut world, 0.1), None);
+        let events = world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .map(|event| (event.event_type.clone(), event.target))
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![(UiEventType::TextRevealed, entity)]);
+    }
+
+    #[test]
+    fn word_mode_waits_for_whole_words() {
+        let mut reveal = UiTextReveal::new(1.0).by_word();
+        let text = "one two three";
+        let visible = |reveal: &UiTextReveal, elapsed: f32| {
+            UiTextReveal {
+                elapsed,
+                ..reveal.clone()
+            }
+            .visible_graphemes(text)
+        };
+        assert_eq!(visible(&reveal, 2.0), 0);
+        assert_eq!(visible(&reveal, 3.0), 3);
+        assert_eq!(visible(&reveal, 6.0), 3);
+        assert_eq!(visible(&reveal, 7.0), 7);
+        assert_eq!(visible(&reveal, 13.0), 13);
+        reveal.skip();
+        assert!(!reveal.is_complete());
+        assert_eq!(reveal.visible_graphemes(text), 13);
+    }
+}
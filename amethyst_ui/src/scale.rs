@@ -0,0 +1,34 @@
+use amethyst_window::ScreenDimensions;
+use serde::{Deserialize, Serialize};
+
+/// Resource scaling the pixel sizes of the whole UI, for an accessibility "UI scale" setting.
+///
+/// Sizes and positions of `UiTransform`s in `ScaleMode::Pixel` and the font size of `UiText`s
+/// are multiplied by `factor` and by the hidpi factor of the window. The hidpi factor is always
+/// applied, so `factor` should not include it. Percent sizes already follow their parent and
+/// are not scaled again.
+///
+/// Changing the factor lays out all elements again on the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UiScale {
+    /// Scale chosen by the user, 1 for the default size.
+    pub factor: f32,
+}
+
+impl UiScale {
+    /// Creates a new scale with the given factor.
+    pub fn new(factor: f32) -> Self {
+        UiScale { factor }
+    }
+
+    /// Returns how many screen pixels one UI pixel covers on this screen.
+    pub fn effective(&self, screen_dimensions: &ScreenDimensions) -> f32 {
+        self.factor * screen_dimensions.hidpi_factor() as f32
+    }
+}
+
+impl Default for UiScale {
+    fn default() -> Self {
+        UiScale { factor: 1.0 }
+    }
+}
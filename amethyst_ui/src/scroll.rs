@@ -0,0 +1,209 @@
+use amethyst_core::ecs::prelude::{
+    Component, DenseVecStorage, Entities, Join, Read, System, WriteStorage,
+};
+use amethyst_input::{BindingTypes, InputHandler};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::{UiCursor, UiTransform};
+
+/// Makes the children of an entity scroll vertically with the mouse wheel.
+///
+/// Children are moved up by `scroll_offset` during layout, and `DrawUi` clips them to the
+/// bounds of the container's `UiTransform`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UiScrollContainer {
+    /// The total height of the content, in pixels.
+    pub content_height: f32,
+    /// How far the content is scrolled down from its top, in pixels.
+    pub scroll_offset: f32,
+    /// How many pixels a single mouse wheel step scrolls.
+    pub scroll_speed: f32,
+}
+
+impl UiScrollContainer {
+    /// Creates a container scrolled to the top of its content.
+    pub fn new(content_height: f32, scroll_speed: f32) -> Self {
+        UiScrollContainer {
+            content_height,
+            scroll_offset: 0.0,
+            scroll_speed,
+        }
+    }
+
+    /// The largest offset the content can be scrolled to in a view of the given height.
+    /// This is zero when the content fits into the view.
+    pub fn max_offset(&self, view_height: f32) -> f32 {
+        (self.content_height - view_height).max(0.0)
+    }
+
+    /// Scrolls the content by `delta` pixels, clamped to the scrollable range.
+    pub fn scroll(&mut self, delta: f32, view_height: f32) {
+        self.scroll_offset = (self.scroll_offset + delta)
+            .min(self.max_offset(view_height))
+            .max(0.0);
+    }
+}
+
+impl Component for UiScrollContainer {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Scrolls `UiScrollContainer`s under the cursor when the mouse wheel is moved.
+///
+/// The offset of every container is also kept within its scrollable range, which can shrink
+/// when the content or the container is resized.
+/// The generic type T represent the T generic parameter of the InputHandler<T>.
+pub struct ScrollSystem<T: BindingTypes> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: BindingTypes> ScrollSystem<T> {
+    /// Creates a new ScrollSystem.
+    pub fn new() -> Self {
+        ScrollSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: BindingTypes> System<'a> for ScrollSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiScrollContainer>,
+        Read<'a, InputHandler<T>>,
+        Read<'a, UiCursor>,
+    );
+
+    fn run(&mut self, (entities, mut transforms, mut containers, input, cursor): Self::SystemData) {
+        let wheel = input.mouse_wheel_value(false) as f32;
+
+        let mut scrolled = Vec::new();
+        for (entity, transform, container) in (&*entities, &transforms, &mut containers).join() {
+            let old_offset = container.scroll_offset;
+            let delta = match cursor.pixel_position {
+                // Moving the wheel up scrolls back towards the top of the content.
+                Some((x, y)) if transform.position_inside(x, y) => -wheel * container.scroll_speed,
+                _ => 0.0,
+            };
+            container.scroll(delta, transform.pixel_height);
+            if container.scroll_offset != old_offset {
+                scrolled.push(entity);
+            }
+        }
+
+        // Flag the containers as modified so the `UiTransformSystem` moves their children.
+        for entity in scrolled {
+            transforms.get_mut(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Anchor, UiCursorSystem};
+    use amethyst_core::{
+        ecs::prelude::{Builder, RunNow, World},
+        shrev::EventChannel,
+    };
+    use amethyst_input::StringBindings;
+    use amethyst_window::ScreenDimensions;
+    use winit::{
+        dpi::LogicalPosition, DeviceId, Event, ModifiersState, MouseScrollDelta, TouchPhase,
+        WindowEvent, WindowId,
+    };
+
+    fn send_input(world: &World, event: WindowEvent) {
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_event(&event, &mut EventChannel::new(), 1.0);
+    }
+
+    fn scroll_wheel(world: &World, lines: f32) {
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_frame_begin();
+        send_input(
+            world,
+            WindowEvent::MouseWheel {
+                device_id: unsafe { DeviceId::dummy() },
+                delta: MouseScrollDelta::LineDelta(0.0, lines),
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn scroll_offset_is_clamped() {
+        let mut world = World::new();
+        let mut system = ScrollSystem::<StringBindings>::new();
+        let mut cursor_system = UiCursorSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        System::setup(&mut cursor_system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+
+        let mut transform = UiTransform::new(
+            "list".to_string(),
+            Anchor::Middle,
+            Anchor::Middle,
+            100.0,
+            100.0,
+            0.0,
+            100.0,
+            100.0,
+        );
+        transform.pixel_x = 100.0;
+        transform.pixel_y = 100.0;
+        transform.pixel_width = 100.0;
+        transform.pixel_height = 100.0;
+        let list = world
+            .create_entity()
+            .with(transform)
+            .with(UiScrollContainer::new(300.0, 50.0))
+            .build();
+
+        send_input(
+            &world,
+            WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: LogicalPosition::new(100.0, 100.0),
+                modifiers: ModifiersState::default(),
+            },
+        );
+        cursor_system.run_now(&world.res);
+        let offset = |world: &World| {
+            world
+                .read_storage::<UiScrollContainer>()
+                .get(list)
+                .unwrap()
+                .scroll_offset
+        };
+
+        for _ in 0..5 {
+            scroll_wheel(&world, -1.0);
+            system.run_now(&world.res);
+        }
+        assert_eq!(offset(&world), 200.0);
+
+        for _ in 0..5 {
+            scroll_wheel(&world, 1.0);
+            system.run_now(&world.res);
+        }
+        assert_eq!(offset(&world), 0.0);
+    }
+
+    #[test]
+    fn short_content_does_not_scroll() {
+        let mut container = UiScrollContainer::new(80.0, 50.0);
+        container.scroll(50.0, 100.0);
+        assert_eq!(container.scroll_offset, 0.0);
+        assert_eq!(container.max_offset(100.0), 0.0);
+    }
+}
@@ -52,6 +52,27 @@ impl Component for Selected {
     type Storage = DenseVecStorage<Self>;
 }
 
+/// Resource deciding how the mouse moves the focus between `Selectable` entities,
+/// in the `SelectionMouseSystem`. Tab navigation works the same with every policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FocusPolicy {
+    /// Clicking an element focuses it. This is the default.
+    ClickToFocus,
+    /// Hovering an element focuses it, clicks don't change the focus.
+    /// Elements need the `Interactable` component to be hovered.
+    FocusFollowsMouse {
+        /// Whether the last hovered element stays focused when the mouse leaves it.
+        /// Otherwise it loses the focus and nothing is focused until the next hover.
+        keep_focus: bool,
+    },
+}
+
+impl Default for FocusPolicy {
+    fn default() -> Self {
+        FocusPolicy::ClickToFocus
+    }
+}
+
 /// System managing the selection of entities.
 /// Reacts to `UiEvent`.
 /// Reacts to Tab and Shift+Tab.
@@ -126,7 +147,11 @@ where
                     selecteds.clear();
 
                     let target = if !modifiers.shift {
-                        // Up
+                        // Forward, wrapping around to the lowest order.
+                        cached.cache.get(highest + 1).unwrap_or_else(|| cached.cache.first()
+                            .expect("unreachable: A highest ui element was selected, but none exist in the cache."))
+                    } else {
+                        // Backward, wrapping around to the highest order.
                         if highest > 0 {
                             cached.cache.get(highest - 1).unwrap_or_else(|| cached.cache.last()
                                 .expect("unreachable: A highest ui element was selected, but none exist in the cache."))
@@ -134,10 +159,6 @@ where
                             cached.cache.last()
                                 .expect("unreachable: A highest ui element was selected, but none exist in the cache.")
                         }
-                    } else {
-                        // Down
-                        cached.cache.get(highest + 1).unwrap_or_else(|| cached.cache.first()
-                        .expect("unreachable: A highest ui element was selected, but none exist in the cache."))
                     };
 
                     selecteds
@@ -164,6 +185,7 @@ where
 }
 
 /// System handling the clicks on ui entities and selecting them, if applicable.
+/// Selects hovered entities instead under `FocusPolicy::FocusFollowsMouse`.
 #[derive(Debug, Default, new)]
 pub struct SelectionMouseSystem<G, T: BindingTypes> {
     #[new(default)]
@@ -182,11 +204,20 @@ where
         WriteStorage<'a, Selected>,
         ReadStorage<'a, Selectable<G>>,
         Read<'a, InputHandler<T>>,
+        Read<'a, FocusPolicy>,
         Entities<'a>,
     );
     fn run(
         &mut self,
-        (mut ui_events, cached, mut selecteds, selectables, input_handler, entities): Self::SystemData,
+        (
+            mut ui_events,
+            cached,
+            mut selecteds,
+            selectables,
+            input_handler,
+            focus_policy,
+            entities,
+        ): Self::SystemData,
     ) {
         let shift = input_handler.key_is_down(VirtualKeyCode::LShift)
             || input_handler.key_is_down(VirtualKeyCode::RShift);
@@ -197,6 +228,31 @@ where
 
         // Add clicked elements to clicked buffer
         for ev in ui_events.read(self.ui_reader_id.as_mut().unwrap()) {
+            if let FocusPolicy::FocusFollowsMouse { keep_focus } = *focus_policy {
+                match ev.event_type {
+                    UiEventType::HoverStart
+                        if selectables.contains(ev.target) && !selecteds.contains(ev.target) =>
+                    {
+                        for (entity, _) in (&*entities, &selecteds).join() {
+                            emitted.push(UiEvent::new(UiEventType::Blur, entity));
+                        }
+                        selecteds.clear();
+                        selecteds
+                            .insert(ev.target, Selected)
+                            .expect("unreachable: We are inserting");
+
+                        emitted.push(UiEvent::new(UiEventType::Focus, ev.target));
+                    }
+                    UiEventType::HoverStop if !keep_focus => {
+                        if selecteds.remove(ev.target).is_some() {
+                            emitted.push(UiEvent::new(UiEventType::Blur, ev.target));
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
             if let UiEventType::ClickStart = ev.event_type {
                 // Ignore events from elements removed between the event emission and now.
                 if selectables.get(ev.target).is_some() {
@@ -305,3 +361,132 @@ where
         self.ui_reader_id = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CacheSelectionOrderSystem;
+    use amethyst_core::ecs::prelude::{Builder, Entity, RunNow, World};
+    use amethyst_input::StringBindings;
+    use winit::{DeviceId, ModifiersState, WindowId};
+
+    fn press_tab(world: &World, shift: bool) {
+        let modifiers = ModifiersState {
+            shift,
+            ..ModifiersState::default()
+        };
+        world
+            .write_resource::<EventChannel<Event>>()
+            .single_write(Event::WindowEvent {
+                window_id: unsafe { WindowId::dummy() },
+                event: WindowEvent::KeyboardInput {
+                    device_id: unsafe { DeviceId::dummy() },
+                    input: KeyboardInput {
+                        scancode: 0,
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Tab),
+                        modifiers,
+                    },
+                },
+            });
+    }
+
+    fn selected(world: &World) -> Vec<Entity> {
+        (&*world.entities(), &world.read_storage::<Selected>())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    #[test]
+    fn tab_cycles_through_selection_order() {
+        let mut world = World::new();
+        let mut cache_system = CacheSelectionOrderSystem::<()>::new();
+        let mut keyboard_system = SelectionKeyboardSystem::<()>::new();
+        System::setup(&mut cache_system, &mut world.res);
+        System::setup(&mut keyboard_system, &mut world.res);
+
+        let third = world.create_entity().with(Selectable::<()>::new(2)).build();
+        let first = world.create_entity().with(Selectable::<()>::new(0)).build();
+        let second = world.create_entity().with(Selectable::<()>::new(1)).build();
+        cache_system.run_now(&world.res);
+
+        let mut tab = |world: &World, shift: bool| {
+            press_tab(world, shift);
+            keyboard_system.run_now(&world.res);
+            selected(world)
+        };
+        assert_eq!(tab(&world, false), vec![first]);
+        assert_eq!(tab(&world, false), vec![second]);
+        assert_eq!(tab(&world, false), vec![third]);
+        assert_eq!(tab(&world, false), vec![first]);
+        assert_eq!(tab(&world, true), vec![third]);
+        assert_eq!(tab(&world, true), vec![second]);
+    }
+
+    fn mouse_world(policy: FocusPolicy) -> (World, SelectionMouseSystem<(), StringBindings>) {
+        let mut world = World::new();
+        let mut system = SelectionMouseSystem::<(), StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(policy);
+        (world, system)
+    }
+
+    fn send(world: &World, event_type: UiEventType, target: Entity) {
+        world
+            .write_resource::<EventChannel<UiEvent>>()
+            .single_write(UiEvent::new(event_type, target));
+    }
+
+    #[test]
+    fn click_to_focus_ignores_hover() {
+        let (mut world, mut system) = mouse_world(FocusPolicy::ClickToFocus);
+        let button = world.create_entity().with(Selectable::<()>::new(0)).build();
+
+        send(&world, UiEventType::HoverStart, button);
+        system.run_now(&world.res);
+        assert!(selected(&world).is_empty());
+
+        send(&world, UiEventType::ClickStart, button);
+        system.run_now(&world.res);
+        assert_eq!(selected(&world), vec![button]);
+    }
+
+    #[test]
+    fn focus_follows_mouse() {
+        let (mut world, mut system) =
+            mouse_world(FocusPolicy::FocusFollowsMouse { keep_focus: false });
+        let mut cache_system = CacheSelectionOrderSystem::<()>::new();
+        let mut keyboard_system = SelectionKeyboardSystem::<()>::new();
+        System::setup(&mut cache_system, &mut world.res);
+        System::setup(&mut keyboard_system, &mut world.res);
+        let first = world.create_entity().with(Selectable::<()>::new(0)).build();
+        let second = world.create_entity().with(Selectable::<()>::new(1)).build();
+        cache_system.run_now(&world.res);
+
+        send(&world, UiEventType::HoverStart, first);
+        system.run_now(&world.res);
+        assert_eq!(selected(&world), vec![first]);
+
+        send(&world, UiEventType::ClickStart, second);
+        system.run_now(&world.res);
+        assert_eq!(selected(&world), vec![first]);
+
+        press_tab(&world, false);
+        keyboard_system.run_now(&world.res);
+        assert_eq!(selected(&world), vec![second]);
+
+        send(&world, UiEventType::HoverStop, first);
+        send(&world, UiEventType::HoverStart, second);
+        send(&world, UiEventType::HoverStop, second);
+        system.run_now(&world.res);
+        assert!(selected(&world).is_empty());
+
+        *world.write_resource::<FocusPolicy>() =
+            FocusPolicy::FocusFollowsMouse { keep_focus: true };
+        send(&world, UiEventType::HoverStart, first);
+        send(&world, UiEventType::HoverStop, first);
+        system.run_now(&world.res);
+        assert_eq!(selected(&world), vec![first]);
+    }
+}
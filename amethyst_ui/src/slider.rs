@@ -0,0 +1,302 @@
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Entity, Join, Read, ReaderId, Resources, System,
+        SystemData, Write, WriteStorage,
+    },
+    shrev::EventChannel,
+    Parent,
+};
+use amethyst_input::{BindingTypes, InputHandler};
+use std::marker::PhantomData;
+use winit::MouseButton;
+
+use crate::{Anchor, UiCursor, UiEvent, UiEventType, UiImage, UiTransform};
+
+/// A horizontal slider selecting a value in `[min, max]` by dragging.
+///
+/// The entity needs a `UiTransform` and the `Interactable` component so dragging it can start.
+/// The `SliderSystem` draws `handle_image` in a child entity, as a square as tall as the
+/// slider, at the position of the current value.
+#[derive(Debug, Clone)]
+pub struct UiSlider {
+    /// The current value.
+    pub value: f32,
+    /// The value at the left end of the slider.
+    pub min: f32,
+    /// The value at the right end of the slider.
+    pub max: f32,
+    /// Values are snapped to multiples of `step` above `min`. Zero disables snapping.
+    pub step: f32,
+    /// Image of the handle.
+    pub handle_image: UiImage,
+    handle: Option<Entity>,
+}
+
+impl UiSlider {
+    /// Creates a slider positioned at `min`.
+    pub fn new(min: f32, max: f32, step: f32, handle_image: UiImage) -> Self {
+        UiSlider {
+            value: min,
+            min,
+            max,
+            step,
+            handle_image,
+            handle: None,
+        }
+    }
+
+    /// The entity drawing the handle, once it was created by the `SliderSystem`.
+    pub fn handle_entity(&self) -> Option<Entity> {
+        self.handle
+    }
+
+    /// Position of the value along the slider, in `[0, 1]`.
+    pub fn ratio(&self) -> f32 {
+        if self.max > self.min {
+            ((self.value - self.min) / (self.max - self.min))
+                .max(0.0)
+                .min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The value at horizontal position `x`, for a slider starting at `left` and `width`
+    /// pixels wide. A slider with `min == max` always returns `min`.
+    fn value_at(&self, x: f32, left: f32, width: f32) -> f32 {
+        if self.max <= self.min || width <= 0.0 {
+            return self.min;
+        }
+        let ratio = ((x - left) / width).max(0.0).min(1.0);
+        let value = self.min + ratio * (self.max - self.min);
+        let value = if self.step > 0.0 {
+            self.min + ((value - self.min) / self.step).round() * self.step
+        } else {
+            value
+        };
+        value.max(self.min).min(self.max)
+    }
+}
+
+impl Component for UiSlider {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Moves `UiSlider`s dragged with the left mouse button and positions their handles.
+///
+/// Writes a `UiEventType::ValueChange` event targeting the slider whenever dragging
+/// changes its value.
+/// The generic type T represent the T generic parameter of the InputHandler<T>.
+pub struct SliderSystem<T: BindingTypes> {
+    dragging: Option<Entity>,
+    ui_reader_id: Option<ReaderId<UiEvent>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BindingTypes> SliderSystem<T> {
+    /// Creates a new SliderSystem.
+    pub fn new() -> Self {
+        SliderSystem {
+            dragging: None,
+            ui_reader_id: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: BindingTypes> System<'a> for SliderSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiSlider>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, UiImage>,
+        WriteStorage<'a, Parent>,
+        Read<'a, InputHandler<T>>,
+        Read<'a, UiCursor>,
+        Write<'a, EventChannel<UiEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut sliders, mut transforms, mut images, mut parents, input, cursor, mut events): Self::SystemData,
+    ) {
+        for event in events.read(
+            self.ui_reader_id
+                .as_mut()
+                .expect("`SliderSystem::setup` was not called before `SliderSystem::run`"),
+        ) {
+            if event.event_type == UiEventType::ClickStart && sliders.contains(event.target) {
+                self.dragging = Some(event.target);
+            }
+        }
+        if !input.mouse_button_is_down(MouseButton::Left) {
+            self.dragging = None;
+        }
+
+        if let (Some(entity), Some((x, _y))) = (self.dragging, cursor.pixel_position) {
+            if let (Some(slider), Some(transform)) =
+                (sliders.get_mut(entity), transforms.get(entity))
+            {
+                let left = transform.pixel_x - transform.pixel_width * 0.5;
+                let value = slider.value_at(x, left, transform.pixel_width);
+                if value != slider.value {
+                    slider.value = value;
+                    events.single_write(UiEvent::new(UiEventType::ValueChange, entity));
+                }
+            }
+        }
+
+        for (entity, slider) in (&*entities, &mut sliders).join() {
+            let (id, track_width, track_height) = match transforms.get(entity) {
+                Some(t) => (t.id.clone(), t.pixel_width, t.pixel_height),
+                None => continue,
+            };
+
+            let handle = match slider.handle.filter(|handle| entities.is_alive(*handle)) {
+                Some(handle) => handle,
+                None => {
+                    let handle = entities.create();
+                    parents
+                        .insert(handle, Parent { entity })
+                        .expect("unreachable: We are inserting");
+                    slider.handle = Some(handle);
+                    handle
+                }
+            };
+
+            // Only touch the handle when it moved, so the layout isn't redone every frame.
+            let x = slider.ratio() * track_width;
+            let up_to_date = transforms
+                .get(handle)
+                .map_or(false, |t| t.local_x == x && t.height == track_height);
+            if !up_to_date {
+                let transform = UiTransform::new(
+                    format!("{}_handle", id),
+                    Anchor::MiddleLeft,
+                    Anchor::Middle,
+                    x,
+                    0.0,
+                    0.01,
+                    track_height,
+                    track_height,
+                )
+                .as_transparent();
+                transforms
+                    .insert(handle, transform)
+                    .expect("unreachable: We are inserting");
+            }
+            if images.get(handle) != Some(&slider.handle_image) {
+                images
+                    .insert(handle, slider.handle_image.clone())
+                    .expect("unreachable: We are inserting");
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.ui_reader_id = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use amethyst_input::StringBindings;
+    use winit::{DeviceId, ElementState, Event, ModifiersState, WindowEvent, WindowId};
+
+    fn send_input(world: &World, event: WindowEvent) {
+        let event = Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .send_event(&event, &mut EventChannel::new(), 1.0);
+    }
+
+    fn slider(world: &mut World, min: f32, max: f32) -> Entity {
+        world
+            .create_entity()
+            .with(UiTransform::new(
+                "volume".to_string(),
+                Anchor::Middle,
+                Anchor::Middle,
+                100.0,
+                100.0,
+                0.0,
+                200.0,
+                20.0,
+            ))
+            .with(UiSlider::new(min, max, 5.0, UiImage::SolidColor([1.0; 4])))
+            .build()
+    }
+
+    fn drag_to(world: &mut World, system: &mut SliderSystem<StringBindings>, target: Entity) {
+        world.write_resource::<UiCursor>().pixel_position = Some((151.0, 100.0));
+        send_input(
+            world,
+            WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                modifiers: ModifiersState::default(),
+            },
+        );
+        world
+            .write_resource::<EventChannel<UiEvent>>()
+            .single_write(UiEvent::new(UiEventType::ClickStart, target));
+        system.run_now(&world.res);
+    }
+
+    #[test]
+    fn drag_sets_snapped_value() {
+        let mut world = World::new();
+        let mut system = SliderSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let volume = slider(&mut world, 0.0, 100.0);
+        drag_to(&mut world, &mut system, volume);
+
+        let sliders = world.read_storage::<UiSlider>();
+        let slider = sliders.get(volume).unwrap();
+        assert_eq!(slider.value, 75.0);
+        let events = world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .filter(|event| event.event_type == UiEventType::ValueChange)
+            .map(|event| event.target)
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![volume]);
+
+        let transforms = world.read_storage::<UiTransform>();
+        let handle = transforms.get(slider.handle_entity().unwrap()).unwrap();
+        assert_eq!(handle.local_x, 150.0);
+    }
+
+    #[test]
+    fn empty_range_does_not_move() {
+        let mut world = World::new();
+        let mut system = SliderSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let fixed = slider(&mut world, 10.0, 10.0);
+        drag_to(&mut world, &mut system, fixed);
+
+        assert_eq!(
+            world.read_storage::<UiSlider>().get(fixed).unwrap().value,
+            10.0
+        );
+        assert!(world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .all(|event| event.event_type != UiEventType::ValueChange));
+    }
+}
@@ -109,3 +109,36 @@ impl<'s> System<'s> for UiSoundSystem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, World};
+
+    #[test]
+    fn hover_start_triggers_one_sound() {
+        let mut world = World::new();
+        let button = world.create_entity().build();
+
+        let mut sources = AssetStorage::<Source>::new();
+        let hover_sound = sources.insert(Source { bytes: Vec::new() });
+        let retrigger = UiSoundRetrigger {
+            on_click_start: None,
+            on_click_stop: None,
+            on_hover_start: Some(UiPlaySoundAction(hover_sound.clone())),
+            on_hover_stop: None,
+        };
+
+        let mut actions = EventChannel::<UiPlaySoundAction>::new();
+        let mut reader = actions.register_reader();
+        for event_type in vec![HoverStart, ClickStart, ClickStop, Click, HoverStop] {
+            retrigger.apply(&UiEvent::new(event_type, button), &mut actions);
+        }
+
+        let played = actions
+            .read(&mut reader)
+            .map(|action| action.0.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(played, vec![hover_sound]);
+    }
+}
@@ -2,7 +2,9 @@
 
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
 use winit::{ElementState, Event, MouseButton, WindowEvent};
 
 use amethyst_core::{
@@ -15,7 +17,7 @@ use amethyst_core::{
 };
 use amethyst_window::ScreenDimensions;
 
-use super::*;
+use super::{cursor::cursor_pixels, *};
 
 /// How lines should behave when they are longer than the maximum line length.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
@@ -26,6 +28,148 @@ pub enum LineMode {
     Wrap,
 }
 
+/// What to do with the parts of a `UiText` that don't fit in its `UiTransform`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+pub enum Overflow {
+    /// Draw the text past the edges of the transform.
+    Show,
+    /// Cut the glyphs off at the edges of the transform.
+    Clip,
+    /// Shorten single line text to the chars that fit, followed by "…". Wrapped and
+    /// editable text is clipped instead.
+    Ellipsis,
+    /// Shorten single line text to its first `head` and last `tail` chars with "…" between
+    /// them, like "C:\\…\\file.txt" for paths. When that is still too wide, leading chars are
+    /// dropped first, then trailing ones. Wrapped and editable text is clipped instead.
+    MiddleEllipsis {
+        /// How many chars to keep at the start of the text.
+        head: usize,
+        /// How many chars to keep at the end of the text.
+        tail: usize,
+    },
+}
+
+/// How the lines of a `UiText` fill the width of its `UiTransform`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+pub enum Justify {
+    /// Lines are placed by the horizontal alignment of `UiText::align`.
+    Ragged,
+    /// The spaces of every wrapped line but the last are widened so the line fills the whole
+    /// width. The last line and lines without spaces are aligned to the left.
+    Full,
+}
+
+/// The distance between the tab stops of a `UiText`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum TabWidth {
+    /// A number of spaces of the font.
+    Spaces(f32),
+    /// A width in pixels, scaled with the UI like the font size.
+    Pixels(f32),
+}
+
+/// How the glyphs of a `UiText` are rasterized.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum RasterMode {
+    /// Glyph edges are antialiased.
+    Smooth,
+    /// Glyph edges are cut to fully opaque or fully transparent pixels, for pixel-art fonts
+    /// drawn from vector sources.
+    Sharp,
+}
+
+/// Where the glyphs of a `TextSpan` sit relative to the baseline of the text around them.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+pub enum TextBaseline {
+    /// On the baseline, at the font size of the text.
+    Normal,
+    /// Smaller and raised above the baseline, for exponents and footnote marks.
+    Superscript,
+    /// Smaller and lowered below the baseline, for chemical formulae and indices.
+    Subscript,
+}
+
+impl Default for TextBaseline {
+    fn default() -> Self {
+        TextBaseline::Normal
+    }
+}
+
+/// The order in which the characters of a `UiText` are laid out on each line.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+pub enum TextDirection {
+    /// Left to right, like latin scripts.
+    Ltr,
+    /// Right to left, like arabic and hebrew. The first character is drawn at the right end
+    /// of its line.
+    Rtl,
+    /// Right to left if the first character with a strong direction is from a right to left
+    /// script, left to right otherwise.
+    Auto,
+}
+
+impl TextDirection {
+    /// Whether `text` is laid out right to left with this direction.
+    pub fn is_rtl(self, text: &str) -> bool {
+        match self {
+            TextDirection::Ltr => false,
+            TextDirection::Rtl => true,
+            TextDirection::Auto => text.chars().filter_map(strong_direction).next() == Some(true),
+        }
+    }
+}
+
+/// `Some(true)` for characters of right to left scripts, `Some(false)` for other letters and
+/// `None` for characters without a strong direction, like digits, punctuation and spaces.
+fn strong_direction(c: char) -> Option<bool> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    // Hebrew, Arabic and the other right to left blocks of the basic plane, their
+    // presentation forms, then the historic right to left scripts and Arabic mathematical
+    // symbols of the supplementary planes.
+    match c as u32 {
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF => Some(true),
+        0x10800..=0x10FFF | 0x1E800..=0x1EFFF => Some(true),
+        _ => Some(false),
+    }
+}
+
+/// A copy of the glyphs of a `UiText` drawn under them, moved by `offset`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TextShadow {
+    /// How far the shadow is moved from the glyphs, in pixels. Positive values move it
+    /// right and up.
+    pub offset: [f32; 2],
+    /// The color of the shadow, using a range of 0.0 to 1.0 per channel.
+    pub color: [f32; 4],
+}
+
+/// An outline around the glyphs of a `UiText`, drawn as copies of the glyphs moved `width`
+/// pixels in eight directions.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TextOutline {
+    /// The width of the outline in pixels.
+    pub width: f32,
+    /// The color of the outline, using a range of 0.0 to 1.0 per channel.
+    pub color: [f32; 4],
+}
+
+/// Overrides the color of a part of a `UiText`, and can turn it into a superscript or a
+/// subscript.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TextSpan {
+    /// Byte range of the text this applies to. Must start and end on char boundaries,
+    /// otherwise the span is ignored.
+    pub range: Range<usize>,
+    /// The color of the text in this range, using a range of 0.0 to 1.0 per channel.
+    pub color: [f32; 4],
+    /// Where the text in this range sits relative to the baseline. Lines are moved apart so
+    /// raised and lowered glyphs don't overlap the lines around them.
+    #[serde(default)]
+    pub baseline: TextBaseline,
+}
+
 /// A component used to display text in this entity's UiTransform
 #[derive(Clone, Derivative, Serialize)]
 #[derivative(Debug)]
@@ -36,18 +180,65 @@ pub struct UiText {
     pub font_size: f32,
     /// The color of the rendered text, using a range of 0.0 to 1.0 per channel.
     pub color: [f32; 4],
+    /// Parts of the text rendered with a different color or baseline. Where spans overlap,
+    /// the last one wins. Ignored when rendering as a password.
+    pub spans: Vec<TextSpan>,
     /// The font used for rendering.
     #[serde(skip)]
     pub font: FontHandle,
+    /// Fonts used in order for the characters `font` has no glyph for, like emoji in a
+    /// latin font. Ignored when rendering as a password.
+    #[serde(skip)]
+    pub fallback_fonts: Vec<FontHandle>,
     /// If true this will be rendered as dots instead of the text.
     pub password: bool,
     /// How the text should handle new lines.
     pub line_mode: LineMode,
     /// How to align the text within its `UiTransform`.
     pub align: Anchor,
+    /// What to do with text that doesn't fit in the transform. Defaults to `Overflow::Clip`.
+    pub overflow: Overflow,
+    /// The direction lines are laid out in. Defaults to `TextDirection::Ltr`.
+    pub direction: TextDirection,
+    /// Whether lines are stretched to the width of the transform. Defaults to
+    /// `Justify::Ragged`.
+    pub justify: Justify,
+    /// The distance between the baselines of two lines, as a multiple of the font's ascent
+    /// plus descent. `None` uses the spacing of the font. Clamped to at least 0.1.
+    pub line_height: Option<f32>,
+    /// The most lines wrapped text is drawn on. When the text needs more, the last line ends
+    /// with "…". `Some(0)` draws nothing, also for single line text. Editable text is never
+    /// shortened. Defaults to `None`, any number of lines.
+    pub max_lines: Option<usize>,
+    /// Colors at the top and bottom of the whole text, blended from one to the other in
+    /// between and multiplied with the colors of the text.
+    pub gradient: Option<[[f32; 4]; 2]>,
+    /// A shadow drawn under the text.
+    pub shadow: Option<TextShadow>,
+    /// An outline drawn around the text, over the shadow.
+    pub outline: Option<TextOutline>,
+    /// How many graphemes from the start of the text are drawn. The rest is laid out but
+    /// not drawn, so revealing more doesn't move the text. `None` draws everything.
+    /// Ignored when rendering as a password.
+    pub visible_graphemes: Option<usize>,
+    /// Whether glyphs are moved to whole pixels, which keeps small text sharp.
+    /// Defaults to true. Ignored for rotated text and when the UI is scaled, either by
+    /// `UiScale` or the hidpi factor, where it would make the glyphs jitter.
+    pub pixel_snap: bool,
+    /// Whether pairs of glyphs are moved closer or apart as the kerning table of the font
+    /// says, like the "V" of "AV" tucked under the "A". Defaults to true.
+    pub kerning: bool,
+    /// Whether glyph edges are antialiased. Defaults to `RasterMode::Smooth`.
+    pub raster_mode: RasterMode,
+    /// The distance between tab stops, measured from the left edge of the text. A tab
+    /// moves the following glyphs to the next stop. Defaults to 4 spaces.
+    pub tab_width: TabWidth,
     /// Cached glyph positions, used to process mouse highlighting
     #[serde(skip)]
     pub(crate) cached_glyphs: Vec<CachedGlyph>,
+    /// Whether the `UiGlyphsSystem` shortened the text to fit on the last frame.
+    #[serde(skip)]
+    pub(crate) truncated: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -70,14 +261,37 @@ impl UiText {
         UiText {
             text,
             color,
+            spans: Vec::new(),
             font_size,
             font: font.clone(),
+            fallback_fonts: Vec::new(),
             password: false,
             line_mode: LineMode::Single,
             align: Anchor::Middle,
+            overflow: Overflow::Clip,
+            direction: TextDirection::Ltr,
+            justify: Justify::Ragged,
+            line_height: None,
+            max_lines: None,
+            gradient: None,
+            shadow: None,
+            outline: None,
+            visible_graphemes: None,
+            pixel_snap: true,
+            kerning: true,
+            raster_mode: RasterMode::Smooth,
+            tab_width: TabWidth::Spaces(4.0),
             cached_glyphs: Vec::new(),
+            truncated: false,
         }
     }
+
+    /// Whether the text was shortened with an ellipsis to fit in its transform, by
+    /// `Overflow::Ellipsis`, `Overflow::MiddleEllipsis` or `max_lines`, when it was last
+    /// drawn.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
 }
 
 impl Component for UiText {
@@ -100,11 +314,18 @@ pub struct TextEditing {
     /// If this is true the text will use a block cursor for editing.  Otherwise this uses a
     /// standard line cursor.  This is not recommended if your font is not monospace.
     pub use_block_cursor: bool,
+    /// How long a full on and off blink of the cursor takes, in seconds. Defaults to 0.5.
+    pub cursor_blink_period: f32,
+    /// The text being composed with an input method, shown underlined at the cursor.
+    /// It is not part of the `UiText` and doesn't count against `max_length` until committed.
+    pub composition: String,
+    /// Characters this returns false for are not inserted when typed or pasted.
+    pub filter: Option<Box<dyn Fn(char) -> bool + Send + Sync>>,
 
     /// This value is used to control cursor blinking.
     ///
-    /// When it is greater than 0.5 / CURSOR_BLINK_RATE the cursor should not display, when it
-    /// is greater than or equal to 1.0 / CURSOR_BLINK_RATE it should be reset to 0.  When the
+    /// When it is greater than half of `cursor_blink_period` the cursor should not display, when
+    /// it is greater than or equal to `cursor_blink_period` it should be reset to 0.  When the
     /// player types it should be reset to 0.
     pub(crate) cursor_blink_timer: f32,
 }
@@ -124,23 +345,65 @@ impl TextEditing {
             selected_text_color,
             selected_background_color,
             use_block_cursor,
+            cursor_blink_period: 0.5,
+            composition: String::new(),
+            filter: None,
             cursor_blink_timer: 0.0,
         }
     }
+
+    /// Only accepts the characters `filter` returns true for.
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(char) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Sets how long a full on and off blink of the cursor takes, in seconds.
+    pub fn with_cursor_blink_period(mut self, period: f32) -> Self {
+        self.cursor_blink_period = period;
+        self
+    }
+
+    /// Only accepts ASCII digits, for numeric fields.
+    pub fn digits_only(self) -> Self {
+        self.with_filter(|c| c.is_ascii_digit())
+    }
+
+    /// Only accepts letters and digits.
+    pub fn alphanumeric(self) -> Self {
+        self.with_filter(char::is_alphanumeric)
+    }
+
+    /// Whether `c` can be inserted, according to the `filter`.
+    pub fn accepts(&self, c: char) -> bool {
+        self.filter.as_ref().map_or(true, |filter| filter(c))
+    }
 }
 
 impl Component for TextEditing {
     type Storage = DenseVecStorage<Self>;
 }
 
+/// Two clicks closer together than this, in seconds, are a double click.
+const DOUBLE_CLICK_TIME: f64 = 0.5;
+
 /// This system processes the underlying UI data as needed.
+///
+/// Clicking into the focused text field moves the cursor, dragging selects text and double
+/// clicking selects a word.
+/// Presses are placed at the `UiCursor`, the position the focused field was picked at.
 pub struct TextEditingMouseSystem {
     /// A reader for winit events.
     reader: Option<ReaderId<Event>>,
     /// This is set to true while the left mouse button is pressed.
     left_mouse_button_pressed: bool,
-    /// The screen coordinates of the mouse
+    /// The screen pixel position of the mouse after the last event read, followed by drags.
     mouse_position: (f32, f32),
+    /// When the left mouse button was last pressed, to detect double clicks.
+    last_press: Option<f64>,
 }
 
 impl TextEditingMouseSystem {
@@ -150,6 +413,7 @@ impl TextEditingMouseSystem {
             reader: None,
             left_mouse_button_pressed: false,
             mouse_position: (0., 0.),
+            last_press: None,
         }
     }
 }
@@ -161,12 +425,13 @@ impl<'a> System<'a> for TextEditingMouseSystem {
         ReadStorage<'a, Selected>,
         Read<'a, EventChannel<Event>>,
         ReadExpect<'a, ScreenDimensions>,
+        Read<'a, UiCursor>,
         Read<'a, Time>,
     );
 
     fn run(
         &mut self,
-        (mut texts, mut text_editings, selecteds, events, screen_dimensions, time): Self::SystemData,
+        (mut texts, mut text_editings, selecteds, events, screen_dimensions, cursor, time): Self::SystemData,
     ) {
         // Normalize text to ensure we can properly count the characters.
         // TODO: Possible improvement to be made if this can be moved only when inserting characters into ui text.
@@ -181,84 +446,80 @@ impl<'a> System<'a> for TextEditingMouseSystem {
         {
             for (text_editing, _) in (&mut text_editings, &selecteds).join() {
                 text_editing.cursor_blink_timer += time.delta_real_seconds();
-                if text_editing.cursor_blink_timer >= 0.5 {
+                if text_editing.cursor_blink_timer >= text_editing.cursor_blink_period {
                     text_editing.cursor_blink_timer = 0.0;
                 }
             }
         }
 
-        // Process only if an editable text is selected.
         for event in events.read(
             self.reader
                 .as_mut()
                 .expect("`UiKeyboardSystem::setup` was not called before `UiKeyboardSystem::run`"),
         ) {
-            for (ref mut text, ref mut text_editing, _) in
-                (&mut texts, &mut text_editings, &selecteds).join()
-            {
-                // Process events for the whole UI.
-                match *event {
-                    Event::WindowEvent {
-                        event: WindowEvent::CursorMoved { position, .. },
-                        ..
-                    } => {
-                        let hidpi = screen_dimensions.hidpi_factor() as f32;
-                        self.mouse_position = (
-                            position.x as f32 * hidpi,
-                            (screen_dimensions.height() - position.y as f32) * hidpi,
-                        );
-                        if self.left_mouse_button_pressed {
-                            let (mouse_x, mouse_y) = self.mouse_position;
+            match *event {
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => {
+                    // The `UiCursor` only holds the last position of the frame, but drags
+                    // have to follow every move between presses.
+                    let position = position.to_physical(screen_dimensions.hidpi_factor());
+                    self.mouse_position =
+                        cursor_pixels(position.x as f32, position.y as f32, &screen_dimensions);
+                    if self.left_mouse_button_pressed {
+                        let (mouse_x, mouse_y) = self.mouse_position;
+                        for (text, text_editing, _) in
+                            (&texts, &mut text_editings, &selecteds).join()
+                        {
                             text_editing.highlight_vector =
-                                closest_glyph_index_to_mouse(mouse_x, mouse_y, &text.cached_glyphs)
+                                glyph_boundary_at(mouse_x, mouse_y, &text.cached_glyphs)
                                     - text_editing.cursor_position;
-                            // The end of the text, while not a glyph, is still something
-                            // you'll likely want to click your cursor to, so if the cursor is
-                            // near the end of the text, check if we should put it at the end
-                            // of the text.
-                            if should_advance_to_end(mouse_x, text_editing, text) {
-                                text_editing.highlight_vector += 1;
-                            }
                         }
                     }
-                    Event::WindowEvent {
-                        event:
-                            WindowEvent::MouseInput {
-                                button: MouseButton::Left,
-                                state,
-                                ..
-                            },
-                        ..
-                    } => {
-                        match state {
-                            ElementState::Pressed => {
-                                self.left_mouse_button_pressed = true;
-
-                                // If we focused an editable text field be sure to position the cursor
-                                // in it.
-                                let (mouse_x, mouse_y) = self.mouse_position;
-                                text_editing.highlight_vector = 0;
-                                text_editing.cursor_position = closest_glyph_index_to_mouse(
-                                    mouse_x,
-                                    mouse_y,
-                                    &text.cached_glyphs,
-                                );
-
-                                // The end of the text, while not a glyph, is still something
-                                // you'll likely want to click your cursor to, so if the cursor is
-                                // near the end of the text, check if we should put it at the end
-                                // of the text.
-                                if should_advance_to_end(mouse_x, text_editing, text) {
-                                    text_editing.cursor_position += 1;
-                                }
-                            }
-                            ElementState::Released => {
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::MouseInput {
+                            button: MouseButton::Left,
+                            state,
+                            ..
+                        },
+                    ..
+                } => match state {
+                    ElementState::Pressed => {
+                        self.left_mouse_button_pressed = true;
+
+                        // The press is placed where the `UiCursor` is, which is also where
+                        // the text field it focuses was picked.
+                        let (mouse_x, mouse_y) =
+                            cursor.pixel_position.unwrap_or(self.mouse_position);
+                        let now = time.absolute_real_time_seconds();
+                        let recent_press = self
+                            .last_press
+                            .map_or(false, |last| now - last < DOUBLE_CLICK_TIME);
+                        self.last_press = Some(now);
+                        for (text, text_editing, _) in
+                            (&texts, &mut text_editings, &selecteds).join()
+                        {
+                            text_editing.highlight_vector = 0;
+                            text_editing.cursor_position =
+                                glyph_boundary_at(mouse_x, mouse_y, &text.cached_glyphs);
+                            if recent_press && !text.password {
+                                let word = word_at(&text.text, text_editing.cursor_position);
+                                text_editing.cursor_position = word.start as isize;
+                                text_editing.highlight_vector = word.len() as isize;
+                                // Dragging after a double click doesn't shrink the word.
                                 self.left_mouse_button_pressed = false;
+                                self.last_press = None;
                             }
                         }
                     }
-                    _ => {}
-                }
+                    ElementState::Released => {
+                        self.left_mouse_button_pressed = false;
+                    }
+                },
+                _ => {}
             }
         }
     }
@@ -270,32 +531,197 @@ impl<'a> System<'a> for TextEditingMouseSystem {
     }
 }
 
-fn should_advance_to_end(mouse_x: f32, text_editing: &mut TextEditing, text: &mut UiText) -> bool {
-    let cursor_pos = text_editing.cursor_position + text_editing.highlight_vector;
-    let len = text.cached_glyphs.len() as isize;
-    if cursor_pos + 1 == len {
-        if let Some(last_glyph) = text.cached_glyphs.last() {
-            if mouse_x - last_glyph.x > last_glyph.advance_width / 2.0 {
-                return true;
-            }
+/// Returns the grapheme index of the glyph boundary closest to the mouse, on the line
+/// closest to it. Clicks past the end of a line are clamped to its end.
+fn glyph_boundary_at(mouse_x: f32, mouse_y: f32, glyphs: &[CachedGlyph]) -> isize {
+    let line_y = match glyphs.iter().min_by(|g1, g2| {
+        let dist = |g: &CachedGlyph| (g.y - mouse_y).abs();
+        dist(g1).partial_cmp(&dist(g2)).expect("Unexpected NaN!")
+    }) {
+        Some(glyph) => glyph.y,
+        None => return 0,
+    };
+
+    let mut end = 0;
+    for (i, glyph) in glyphs.iter().enumerate().filter(|(_, g)| g.y == line_y) {
+        if mouse_x < glyph.x + glyph.advance_width / 2.0 {
+            return i as isize;
         }
+        end = i + 1;
     }
+    end as isize
+}
 
-    false
+/// Returns the range of grapheme indices of the word at the grapheme index `position`.
+/// Whitespace and punctuation between words count as a word of their own.
+fn word_at(text: &str, position: isize) -> Range<usize> {
+    let mut start = 0;
+    for word in text.split_word_bounds() {
+        let end = start + word.graphemes(true).count();
+        if (position as usize) < end {
+            return start..end;
+        }
+        start = end;
+    }
+    // Past the end, select the last word.
+    text.split_word_bounds()
+        .last()
+        .map_or(start..start, |word| {
+            start - word.graphemes(true).count()..start
+        })
 }
 
-fn closest_glyph_index_to_mouse(mouse_x: f32, mouse_y: f32, glyphs: &[CachedGlyph]) -> isize {
-    glyphs
-        .iter()
-        .enumerate()
-        .min_by(|(_, g1), (_, g2)| {
-            let dist = |g: &CachedGlyph| {
-                let dx = g.x - mouse_x;
-                let dy = g.y - mouse_y;
-                dx * dx + dy * dy
-            };
-            dist(g1).partial_cmp(&dist(g2)).expect("Unexpected NaN!")
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_assets::AssetStorage;
+    use amethyst_core::ecs::prelude::{Builder, Entity, RunNow, World};
+    use glyph_brush::rusttype::Font;
+    use winit::{dpi::LogicalPosition, DeviceId, ModifiersState, WindowId};
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    fn move_to(x: f32) -> Event {
+        window_event(WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: LogicalPosition::new(x.into(), 10.0),
+            modifiers: ModifiersState::default(),
+        })
+    }
+
+    fn mouse(state: ElementState) -> Event {
+        window_event(WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::default(),
         })
-        .map(|(i, _)| i)
-        .unwrap_or(0) as isize
+    }
+
+    fn setup() -> (World, TextEditingMouseSystem, Entity) {
+        let mut world = World::new();
+        let mut system = TextEditingMouseSystem::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(800, 600, 1.0));
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let mut text = UiText::new(font, "hello world".to_string(), [1.; 4], 10.);
+        // Glyphs 10 pixels wide on the line at the top of the screen.
+        text.cached_glyphs = (0..11)
+            .map(|i| CachedGlyph {
+                x: i as f32 * 10.0,
+                y: 590.0,
+                advance_width: 10.0,
+            })
+            .collect();
+        let entity = world
+            .create_entity()
+            .with(text)
+            .with(TextEditing::new(
+                20,
+                [0., 0., 0., 1.],
+                [1., 1., 1., 1.],
+                false,
+            ))
+            .with(Selected)
+            .build();
+        (world, system, entity)
+    }
+
+    fn send(world: &mut World, system: &mut TextEditingMouseSystem, events: Vec<Event>) {
+        world
+            .write_resource::<EventChannel<Event>>()
+            .iter_write(events);
+        system.run_now(&world.res);
+    }
+
+    fn selection(world: &World, entity: Entity) -> Range<isize> {
+        let editings = world.read_storage::<TextEditing>();
+        let editing = editings.get(entity).unwrap();
+        let (a, b) = (
+            editing.cursor_position,
+            editing.cursor_position + editing.highlight_vector,
+        );
+        a.min(b)..a.max(b)
+    }
+
+    /// Moves the `UiCursor` to `x` on the line of the text, where the next presses are placed.
+    fn cursor_at(world: &mut World, x: f32) {
+        world.write_resource::<UiCursor>().pixel_position = Some((x, 590.0));
+    }
+
+    #[test]
+    fn drag_selects_text() {
+        let (mut world, mut system, entity) = setup();
+
+        cursor_at(&mut world, 12.0);
+        send(
+            &mut world,
+            &mut system,
+            vec![move_to(12.0), mouse(ElementState::Pressed)],
+        );
+        send(&mut world, &mut system, vec![move_to(30.0), move_to(47.0)]);
+        assert_eq!(selection(&world, entity), 1..5);
+
+        // Dragging past the end of the text selects up to the end.
+        send(
+            &mut world,
+            &mut system,
+            vec![move_to(500.0), mouse(ElementState::Released), move_to(0.0)],
+        );
+        assert_eq!(selection(&world, entity), 1..11);
+
+        world.write_resource::<Time>().set_delta_seconds(1.0);
+        cursor_at(&mut world, 72.0);
+        send(
+            &mut world,
+            &mut system,
+            vec![
+                move_to(72.0),
+                mouse(ElementState::Pressed),
+                mouse(ElementState::Released),
+            ],
+        );
+        assert_eq!(selection(&world, entity), 7..7);
+
+        world.write_resource::<Time>().set_delta_seconds(0.1);
+        send(&mut world, &mut system, vec![mouse(ElementState::Pressed)]);
+        send(&mut world, &mut system, vec![move_to(0.0)]);
+        assert_eq!(selection(&world, entity), 6..11);
+    }
+
+    #[test]
+    fn focusing_press_is_placed_at_the_cursor() {
+        let (mut world, mut system, entity) = setup();
+        world.write_storage::<Selected>().remove(entity);
+
+        // The mouse moves over the field before it is focused.
+        send(&mut world, &mut system, vec![move_to(32.0)]);
+        world
+            .write_storage::<Selected>()
+            .insert(entity, Selected)
+            .unwrap();
+        cursor_at(&mut world, 52.0);
+        send(&mut world, &mut system, vec![mouse(ElementState::Pressed)]);
+        assert_eq!(selection(&world, entity), 5..5);
+
+        // Dragging then follows every move of the mouse.
+        send(&mut world, &mut system, vec![move_to(81.0)]);
+        assert_eq!(selection(&world, entity), 5..8);
+    }
+
+    #[test]
+    fn word_bounds() {
+        assert_eq!(word_at("hello world", 2), 0..5);
+        assert_eq!(word_at("hello world", 5), 5..6);
+        assert_eq!(word_at("hello world", 11), 6..11);
+        assert_eq!(word_at("", 0), 0..0);
+    }
 }
@@ -12,21 +12,39 @@ use amethyst_core::{
     shrev::{EventChannel, ReaderId},
 };
 
+/// Input method events for the focused text field.
+///
+/// winit doesn't report input method composition, so these are written to an
+/// `EventChannel<ImeEvent>` by the platform integration, for example to type CJK text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeEvent {
+    /// The in-progress composition changed. An empty string ends the composition.
+    Preedit(String),
+    /// The composition is done and the string is inserted at the cursor.
+    Commit(String),
+}
+
 /// System managing the keyboard inputs for the editable text fields.
 /// ## Features
 /// * Adds and removes text.
 /// * Moves selection cursor.
 /// * Grows and shrinks selected text zone.
+/// * Composes text with an input method, from `ImeEvent`s.
 #[derive(Default)]
 pub struct TextEditingInputSystem {
     /// A reader for winit events.
     reader: Option<ReaderId<Event>>,
+    /// A reader for input method events.
+    ime_reader: Option<ReaderId<ImeEvent>>,
 }
 
 impl TextEditingInputSystem {
     /// Creates a new instance of this system
     pub fn new() -> Self {
-        Self { reader: None }
+        Self {
+            reader: None,
+            ime_reader: None,
+        }
     }
 }
 
@@ -37,12 +55,13 @@ impl<'a> System<'a> for TextEditingInputSystem {
         WriteStorage<'a, TextEditing>,
         ReadStorage<'a, Selected>,
         Read<'a, EventChannel<Event>>,
+        Read<'a, EventChannel<ImeEvent>>,
         Write<'a, EventChannel<UiEvent>>,
     );
 
     fn run(
         &mut self,
-        (entities, mut texts, mut editables, selecteds, events, mut edit_events): Self::SystemData,
+        (entities, mut texts, mut editables, selecteds, events, ime_events, mut edit_events): Self::SystemData,
     ) {
         for text in (&mut texts).join() {
             if (*text.text).chars().any(is_combining_mark) {
@@ -67,7 +86,7 @@ impl<'a> System<'a> for TextEditingInputSystem {
                         event: WindowEvent::ReceivedCharacter(input),
                         ..
                     } => {
-                        if should_skip_char(input) {
+                        if should_skip_char(input) || !focused_edit.accepts(input) {
                             continue;
                         }
                         focused_edit.cursor_blink_timer = 0.0;
@@ -84,7 +103,11 @@ impl<'a> System<'a> for TextEditingInputSystem {
                             });
                         if focused_text.text.graphemes(true).count() < focused_edit.max_length {
                             focused_text.text.insert(start_byte, input);
-                            focused_edit.cursor_position += 1;
+                            // The char can join the grapheme before it, like the second half
+                            // of a flag, so the cursor doesn't always move.
+                            let end_byte = start_byte + input.len_utf8();
+                            focused_edit.cursor_position =
+                                focused_text.text[..end_byte].graphemes(true).count() as isize;
 
                             edit_events
                                 .single_write(UiEvent::new(UiEventType::ValueChange, entity));
@@ -262,20 +285,7 @@ impl<'a> System<'a> for TextEditingInputSystem {
                                     .and_then(|mut ctx: ClipboardContext| ctx.get_contents())
                                 {
                                     Ok(contents) => {
-                                        let index = cursor_byte_index(focused_edit, focused_text);
-                                        let empty_space = focused_edit.max_length
-                                            - focused_text.text.graphemes(true).count();
-                                        let contents = contents
-                                            .graphemes(true)
-                                            .take(empty_space)
-                                            .fold(String::new(), |mut init, new| {
-                                                init.push_str(new);
-                                                init
-                                            });
-                                        focused_text.text.insert_str(index, &contents);
-                                        focused_edit.cursor_position +=
-                                            contents.graphemes(true).count() as isize;
-
+                                        insert_pasted(focused_edit, focused_text, &contents);
                                         edit_events.single_write(UiEvent::new(
                                             UiEventType::ValueChange,
                                             entity,
@@ -331,12 +341,38 @@ impl<'a> System<'a> for TextEditingInputSystem {
                 }
             }
         }
+
+        for event in ime_events.read(
+            self.ime_reader
+                .as_mut()
+                .expect("`UiKeyboardSystem::setup` was not called before `UiKeyboardSystem::run`"),
+        ) {
+            if let Some((entity, focused_text, focused_edit, _)) =
+                (&*entities, &mut texts, &mut editables, &selecteds)
+                    .join()
+                    .next()
+            {
+                focused_edit.cursor_blink_timer = 0.0;
+                match event {
+                    ImeEvent::Preedit(composition) => {
+                        focused_edit.composition = composition.clone();
+                    }
+                    ImeEvent::Commit(committed) => {
+                        focused_edit.composition.clear();
+                        delete_highlighted(focused_edit, focused_text);
+                        insert_pasted(focused_edit, focused_text, committed);
+                        edit_events.single_write(UiEvent::new(UiEventType::ValueChange, entity));
+                    }
+                }
+            }
+        }
     }
 
     fn setup(&mut self, res: &mut Resources) {
         use amethyst_core::ecs::prelude::SystemData;
         Self::SystemData::setup(res);
         self.reader = Some(res.fetch_mut::<EventChannel<Event>>().register_reader());
+        self.ime_reader = Some(res.fetch_mut::<EventChannel<ImeEvent>>().register_reader());
     }
 }
 
@@ -354,7 +390,7 @@ fn read_highlighted<'a>(edit: &TextEditing, text: &'a UiText) -> &'a str {
 /// Removes the highlighted text and returns it in a String.
 fn extract_highlighted(edit: &mut TextEditing, text: &mut UiText) -> String {
     let range = highlighted_bytes(edit, text);
-    edit.cursor_position = range.start as isize;
+    edit.cursor_position = highlight_start(edit);
     edit.highlight_vector = 0;
     text.text.drain(range).collect::<String>()
 }
@@ -363,7 +399,7 @@ fn extract_highlighted(edit: &mut TextEditing, text: &mut UiText) -> String {
 fn delete_highlighted(edit: &mut TextEditing, text: &mut UiText) -> bool {
     if edit.highlight_vector != 0 {
         let range = highlighted_bytes(edit, text);
-        edit.cursor_position = range.start as isize;
+        edit.cursor_position = highlight_start(edit);
         edit.highlight_vector = 0;
         text.text.drain(range);
         return true;
@@ -371,7 +407,35 @@ fn delete_highlighted(edit: &mut TextEditing, text: &mut UiText) -> bool {
     false
 }
 
+/// The grapheme index of the start of the highlighted text.
+fn highlight_start(edit: &TextEditing) -> isize {
+    edit.cursor_position
+        .min(edit.cursor_position + edit.highlight_vector)
+}
+
 // Gets the byte index of the cursor.
+/// Inserts pasted text at the cursor, truncated to the room left under `max_length`.
+/// Characters rejected by the filter are dropped, and so are line breaks when pasting into
+/// a single line text.
+fn insert_pasted(edit: &mut TextEditing, text: &mut UiText, contents: &str) {
+    let index = cursor_byte_index(edit, text);
+    let empty_space = edit
+        .max_length
+        .saturating_sub(text.text.graphemes(true).count());
+    let single_line = text.line_mode == LineMode::Single;
+    let contents = contents
+        .chars()
+        .filter(|c| edit.accepts(*c))
+        .collect::<String>();
+    let contents = contents
+        .graphemes(true)
+        .filter(|g| !(single_line && (*g == "\n" || *g == "\r\n" || *g == "\r")))
+        .take(empty_space)
+        .collect::<String>();
+    text.text.insert_str(index, &contents);
+    edit.cursor_position += contents.graphemes(true).count() as isize;
+}
+
 fn cursor_byte_index(edit: &TextEditing, text: &UiText) -> usize {
     text.text
         .grapheme_indices(true)
@@ -416,3 +480,290 @@ fn should_skip_char(input: char) -> bool {
     || (input >= '\u{F0000}' && input <= '\u{FFFFF}')
     || (input >= '\u{100000}' && input <= '\u{10FFFF}')
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FontAsset;
+    use amethyst_assets::AssetStorage;
+    use amethyst_core::ecs::prelude::{Builder, Entity, RunNow, World};
+    use glyph_brush::rusttype::Font;
+    use winit::{DeviceId, WindowId};
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    fn char_event(c: char) -> Event {
+        window_event(WindowEvent::ReceivedCharacter(c))
+    }
+
+    fn key_event(key: VirtualKeyCode) -> Event {
+        window_event(WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::default(),
+            },
+        })
+    }
+
+    fn setup() -> (World, TextEditingInputSystem, Entity) {
+        let mut world = World::new();
+        let mut system = TextEditingInputSystem::new();
+        System::setup(&mut system, &mut world.res);
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let entity = world
+            .create_entity()
+            .with(UiText::new(font, String::new(), [1., 1., 1., 1.], 10.))
+            .with(TextEditing::new(
+                10,
+                [0., 0., 0., 1.],
+                [1., 1., 1., 1.],
+                false,
+            ))
+            .with(Selected)
+            .build();
+        (world, system, entity)
+    }
+
+    fn send(world: &mut World, system: &mut TextEditingInputSystem, events: Vec<Event>) {
+        world
+            .write_resource::<EventChannel<Event>>()
+            .iter_write(events);
+        system.run_now(&world.res);
+    }
+
+    fn state(world: &World, entity: Entity) -> (String, isize) {
+        (
+            world
+                .read_storage::<UiText>()
+                .get(entity)
+                .unwrap()
+                .text
+                .clone(),
+            world
+                .read_storage::<TextEditing>()
+                .get(entity)
+                .unwrap()
+                .cursor_position,
+        )
+    }
+
+    #[test]
+    fn typing_and_deleting_multi_byte_chars() {
+        let (mut world, mut system, entity) = setup();
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        send(
+            &mut world,
+            &mut system,
+            vec![char_event('h'), char_event('é'), char_event('y')],
+        );
+        assert_eq!(state(&world, entity), ("héy".to_string(), 3));
+        assert_eq!(
+            world
+                .read_resource::<EventChannel<UiEvent>>()
+                .read(&mut reader)
+                .filter(|e| e.event_type == UiEventType::ValueChange)
+                .count(),
+            3
+        );
+
+        send(
+            &mut world,
+            &mut system,
+            vec![
+                key_event(VirtualKeyCode::Left),
+                key_event(VirtualKeyCode::Back),
+            ],
+        );
+        assert_eq!(state(&world, entity), ("hy".to_string(), 1));
+
+        send(
+            &mut world,
+            &mut system,
+            vec![key_event(VirtualKeyCode::Delete)],
+        );
+        assert_eq!(state(&world, entity), ("h".to_string(), 1));
+    }
+
+    #[test]
+    fn flag_emoji_is_deleted_as_one_grapheme() {
+        let (mut world, mut system, entity) = setup();
+        // A flag is made of two regional indicator chars.
+        send(
+            &mut world,
+            &mut system,
+            "a\u{1F1EB}\u{1F1F7}b".chars().map(char_event).collect(),
+        );
+        assert_eq!(
+            state(&world, entity),
+            ("a\u{1F1EB}\u{1F1F7}b".to_string(), 3)
+        );
+
+        send(
+            &mut world,
+            &mut system,
+            vec![
+                key_event(VirtualKeyCode::Left),
+                key_event(VirtualKeyCode::Back),
+            ],
+        );
+        assert_eq!(state(&world, entity), ("ab".to_string(), 1));
+
+        send(
+            &mut world,
+            &mut system,
+            vec![
+                char_event('\u{1F1EB}'),
+                char_event('\u{1F1F7}'),
+                key_event(VirtualKeyCode::Left),
+                key_event(VirtualKeyCode::Delete),
+            ],
+        );
+        assert_eq!(state(&world, entity), ("ab".to_string(), 1));
+    }
+
+    #[test]
+    fn home_and_end_move_cursor() {
+        let (mut world, mut system, entity) = setup();
+        send(
+            &mut world,
+            &mut system,
+            "añb".chars().map(char_event).collect(),
+        );
+
+        send(
+            &mut world,
+            &mut system,
+            vec![key_event(VirtualKeyCode::Home)],
+        );
+        assert_eq!(state(&world, entity), ("añb".to_string(), 0));
+
+        send(&mut world, &mut system, vec![char_event('x')]);
+        assert_eq!(state(&world, entity), ("xañb".to_string(), 1));
+
+        send(
+            &mut world,
+            &mut system,
+            vec![key_event(VirtualKeyCode::End)],
+        );
+        assert_eq!(state(&world, entity), ("xañb".to_string(), 4));
+    }
+
+    #[test]
+    fn paste_inserts_at_cursor_and_truncates() {
+        let (mut world, mut system, entity) = setup();
+        send(
+            &mut world,
+            &mut system,
+            vec![
+                char_event('a'),
+                char_event('b'),
+                key_event(VirtualKeyCode::Left),
+            ],
+        );
+
+        {
+            let mut texts = world.write_storage::<UiText>();
+            let mut edits = world.write_storage::<TextEditing>();
+            insert_pasted(
+                edits.get_mut(entity).unwrap(),
+                texts.get_mut(entity).unwrap(),
+                "12\r\n345678901",
+            );
+        }
+        assert_eq!(state(&world, entity), ("a12345678b".to_string(), 9));
+
+        {
+            let mut texts = world.write_storage::<UiText>();
+            let mut edits = world.write_storage::<TextEditing>();
+            let text = texts.get_mut(entity).unwrap();
+            text.line_mode = LineMode::Wrap;
+            text.text = "ab".to_string();
+            let edit = edits.get_mut(entity).unwrap();
+            edit.cursor_position = 2;
+            insert_pasted(edit, text, "c\nd");
+        }
+        assert_eq!(state(&world, entity), ("abc\nd".to_string(), 5));
+    }
+
+    #[test]
+    fn composition_is_inserted_on_commit() {
+        let (mut world, mut system, entity) = setup();
+        send(
+            &mut world,
+            &mut system,
+            "abcdefgh".chars().map(char_event).collect(),
+        );
+
+        world
+            .write_resource::<EventChannel<ImeEvent>>()
+            .single_write(ImeEvent::Preedit("にほん".to_string()));
+        system.run_now(&world.res);
+        assert_eq!(state(&world, entity), ("abcdefgh".to_string(), 8));
+        assert_eq!(
+            world
+                .read_storage::<TextEditing>()
+                .get(entity)
+                .unwrap()
+                .composition,
+            "にほん"
+        );
+
+        // The composition doesn't take room in the field yet.
+        send(&mut world, &mut system, vec![char_event('i')]);
+        assert_eq!(state(&world, entity), ("abcdefghi".to_string(), 9));
+
+        world
+            .write_resource::<EventChannel<ImeEvent>>()
+            .single_write(ImeEvent::Commit("日本".to_string()));
+        system.run_now(&world.res);
+        assert_eq!(state(&world, entity), ("abcdefghi日".to_string(), 10));
+        assert!(world
+            .read_storage::<TextEditing>()
+            .get(entity)
+            .unwrap()
+            .composition
+            .is_empty());
+    }
+
+    #[test]
+    fn digits_only_filter_drops_letters() {
+        let (mut world, mut system, entity) = setup();
+        {
+            let mut edits = world.write_storage::<TextEditing>();
+            let edit = edits.get_mut(entity).unwrap();
+            *edit = TextEditing::new(10, [0., 0., 0., 1.], [1., 1., 1., 1.], false).digits_only();
+        }
+
+        send(
+            &mut world,
+            &mut system,
+            "1a2b3".chars().map(char_event).collect(),
+        );
+        assert_eq!(state(&world, entity), ("123".to_string(), 3));
+
+        {
+            let mut texts = world.write_storage::<UiText>();
+            let mut edits = world.write_storage::<TextEditing>();
+            insert_pasted(
+                edits.get_mut(entity).unwrap(),
+                texts.get_mut(entity).unwrap(),
+                "4 five 6",
+            );
+        }
+        assert_eq!(state(&world, entity), ("12346".to_string(), 5));
+    }
+}
@@ -0,0 +1,85 @@
+use amethyst_core::{
+    ecs::prelude::{Component, DenseVecStorage, Entity, FlaggedStorage, ReadStorage},
+    Parent,
+};
+use serde::{Deserialize, Serialize};
+
+/// Opacity of a UI element and all of its children, to fade a whole panel at once.
+///
+/// The alpha of every `UiTint` above an element in the hierarchy is multiplied into the
+/// alpha of its images and text when it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UiTint {
+    /// Opacity, from 0 (invisible) to 1 (unchanged). Clamped to that range when drawn.
+    pub alpha: f32,
+}
+
+impl UiTint {
+    /// Creates a new tint with the given opacity.
+    pub fn new(alpha: f32) -> Self {
+        UiTint { alpha }
+    }
+}
+
+impl Default for UiTint {
+    fn default() -> Self {
+        UiTint { alpha: 1.0 }
+    }
+}
+
+impl Component for UiTint {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+/// Returns the product of the alphas of the `UiTint`s on the entity and its ancestors.
+pub(crate) fn inherited_alpha(
+    entity: Entity,
+    parents: &ReadStorage<'_, Parent>,
+    tints: &ReadStorage<'_, UiTint>,
+) -> f32 {
+    let mut alpha = 1.0;
+    let mut current = Some(entity);
+    while let Some(entity) = current {
+        if let Some(tint) = tints.get(entity) {
+            alpha *= tint.alpha.max(0.0).min(1.0);
+        }
+        current = parents.get(entity).map(|parent| parent.entity);
+    }
+    alpha
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, World};
+
+    #[test]
+    fn child_alpha_is_product_of_ancestors() {
+        let mut world = World::new();
+        world.register::<Parent>();
+        world.register::<UiTint>();
+        let dialog = world.create_entity().with(UiTint::new(0.5)).build();
+        let panel = world
+            .create_entity()
+            .with(Parent { entity: dialog })
+            .build();
+        let label = world
+            .create_entity()
+            .with(Parent { entity: panel })
+            .with(UiTint::new(0.4))
+            .build();
+        let glow = world
+            .create_entity()
+            .with(Parent { entity: panel })
+            .with(UiTint::new(3.0))
+            .build();
+
+        let parents = world.read_storage::<Parent>();
+        let tints = world.read_storage::<UiTint>();
+        assert_eq!(inherited_alpha(dialog, &parents, &tints), 0.5);
+        assert_eq!(inherited_alpha(panel, &parents, &tints), 0.5);
+        assert_eq!(inherited_alpha(label, &parents, &tints), 0.2);
+        // Alphas above one are clamped.
+        assert_eq!(inherited_alpha(glow, &parents, &tints), 0.5);
+    }
+}
@@ -0,0 +1,157 @@
+use amethyst_core::{
+    ecs::prelude::{
+        Component, DenseVecStorage, Entities, Join, ReaderId, Resources, System, SystemData, Write,
+        WriteStorage,
+    },
+    shrev::EventChannel,
+};
+
+use crate::{UiEvent, UiEventType, UiImage};
+
+/// A checkbox or toggle button, flipped by clicking it.
+///
+/// The entity needs a `UiTransform` and the `Interactable` component to receive clicks.
+/// The `ToggleSystem` keeps its `UiImage` set to `checked_image` or `unchecked_image`.
+#[derive(Debug, Clone)]
+pub struct UiToggle {
+    /// Whether the toggle is currently checked.
+    pub checked: bool,
+    /// Image shown while checked.
+    pub checked_image: UiImage,
+    /// Image shown while unchecked.
+    pub unchecked_image: UiImage,
+}
+
+impl UiToggle {
+    /// Creates a new toggle with the given state.
+    pub fn new(checked: bool, checked_image: UiImage, unchecked_image: UiImage) -> Self {
+        UiToggle {
+            checked,
+            checked_image,
+            unchecked_image,
+        }
+    }
+
+    /// The image matching the current state.
+    pub fn image(&self) -> &UiImage {
+        if self.checked {
+            &self.checked_image
+        } else {
+            &self.unchecked_image
+        }
+    }
+}
+
+impl Component for UiToggle {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Flips `UiToggle`s when they are clicked and updates their image.
+///
+/// Only `UiEventType::Click` toggles, so pressing and releasing the mouse over an element
+/// flips it once. Each flip writes a `UiEventType::ValueChange` event targeting the toggle;
+/// the new state can be read from its `UiToggle` component.
+#[derive(Debug, Default)]
+pub struct ToggleSystem {
+    ui_reader_id: Option<ReaderId<UiEvent>>,
+}
+
+impl ToggleSystem {
+    /// Creates a new ToggleSystem.
+    pub fn new() -> Self {
+        ToggleSystem::default()
+    }
+}
+
+impl<'a> System<'a> for ToggleSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, UiToggle>,
+        WriteStorage<'a, UiImage>,
+        Write<'a, EventChannel<UiEvent>>,
+    );
+
+    fn run(&mut self, (entities, mut toggles, mut images, mut events): Self::SystemData) {
+        let mut emitted = Vec::new();
+        for event in events.read(
+            self.ui_reader_id
+                .as_mut()
+                .expect("`ToggleSystem::setup` was not called before `ToggleSystem::run`"),
+        ) {
+            if event.event_type != UiEventType::Click {
+                continue;
+            }
+            if let Some(toggle) = toggles.get_mut(event.target) {
+                toggle.checked = !toggle.checked;
+                emitted.push(UiEvent::new(UiEventType::ValueChange, event.target));
+            }
+        }
+        events.iter_write(emitted);
+
+        for (entity, toggle) in (&*entities, &toggles).join() {
+            if images.get(entity) != Some(toggle.image()) {
+                images
+                    .insert(entity, toggle.image().clone())
+                    .expect("unreachable: We are inserting");
+            }
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.ui_reader_id = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+
+    #[test]
+    fn clicking_twice_restores_state() {
+        let mut world = World::new();
+        let mut system = ToggleSystem::new();
+        System::setup(&mut system, &mut world.res);
+        let mut reader = world
+            .write_resource::<EventChannel<UiEvent>>()
+            .register_reader();
+
+        let checked = UiImage::SolidColor([0.0, 1.0, 0.0, 1.0]);
+        let unchecked = UiImage::SolidColor([1.0, 0.0, 0.0, 1.0]);
+        let mute = world
+            .create_entity()
+            .with(UiToggle::new(false, checked.clone(), unchecked.clone()))
+            .build();
+        let click = |world: &World| {
+            let mut events = world.write_resource::<EventChannel<UiEvent>>();
+            events.single_write(UiEvent::new(UiEventType::ClickStart, mute));
+            events.single_write(UiEvent::new(UiEventType::ClickStop, mute));
+            events.single_write(UiEvent::new(UiEventType::Click, mute));
+        };
+        let state = |world: &World| {
+            (
+                world.read_storage::<UiToggle>().get(mute).unwrap().checked,
+                world.read_storage::<UiImage>().get(mute).cloned(),
+            )
+        };
+
+        system.run_now(&world.res);
+        assert_eq!(state(&world), (false, Some(unchecked.clone())));
+
+        click(&world);
+        system.run_now(&world.res);
+        assert_eq!(state(&world), (true, Some(checked)));
+
+        click(&world);
+        system.run_now(&world.res);
+        assert_eq!(state(&world), (false, Some(unchecked)));
+
+        let value_changes = world
+            .read_resource::<EventChannel<UiEvent>>()
+            .read(&mut reader)
+            .filter(|event| event.event_type == UiEventType::ValueChange)
+            .count();
+        assert_eq!(value_changes, 2);
+    }
+}
@@ -0,0 +1,355 @@
+use amethyst_core::{
+    ecs::{
+        prelude::{
+            Component, DenseVecStorage, Entities, Entity, Join, Read, ReadExpect, ReadStorage,
+            ReaderId, Resources, System, SystemData, WriteStorage,
+        },
+        storage::NullStorage,
+    },
+    shrev::EventChannel,
+    Hidden, Time,
+};
+use amethyst_input::BindingTypes;
+use amethyst_window::ScreenDimensions;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::{Anchor, UiCursor, UiEvent, UiEventType, UiText, UiTransform};
+
+/// Distance between the cursor and the corner of the tooltip, in pixels.
+const CURSOR_OFFSET: f32 = 16.0;
+
+/// Default hover time before the full text of a truncated `UiText` shows, in seconds.
+const TRUNCATED_TEXT_DELAY: f32 = 0.5;
+
+/// Shows a tooltip after the cursor hovered the entity for `delay` seconds.
+/// The entity needs a `UiTransform` and the `Interactable` component to be hovered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiTooltip {
+    /// The text of the tooltip.
+    pub text: String,
+    /// How long the entity has to be hovered before the tooltip shows, in seconds.
+    pub delay: f32,
+}
+
+impl UiTooltip {
+    /// Creates a new tooltip.
+    pub fn new(text: String, delay: f32) -> Self {
+        UiTooltip { text, delay }
+    }
+}
+
+impl Component for UiTooltip {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Tags the entity every tooltip is displayed with. It needs a `UiTransform` sized to fit
+/// the tooltips and a `UiText`, and usually a `UiImage` as background. The transform should
+/// be transparent, so an edge-clamped tooltip under the cursor doesn't end the hover.
+///
+/// The `TooltipSystem` hides it with the `Hidden` component while there is no tooltip to show.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UiTooltipDisplay;
+
+impl Component for UiTooltipDisplay {
+    type Storage = NullStorage<Self>;
+}
+
+/// Shows the `UiTooltip` of the hovered entity in the `UiTooltipDisplay`, near the cursor.
+///
+/// Hovered `UiText`s that were shortened with an ellipsis and have no `UiTooltip` show their
+/// full text instead. Like tooltips, they need the `Interactable` component to be hovered.
+///
+/// The display is kept inside the screen, and hidden again as soon as the hover stops.
+/// The cursor is read from the `UiCursor`, the generic type T of the InputHandler<T> is only
+/// kept for compatibility.
+pub struct TooltipSystem<T: BindingTypes> {
+    hovered: Option<Entity>,
+    dwell: f32,
+    truncated_text_delay: f32,
+    ui_reader_id: Option<ReaderId<UiEvent>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BindingTypes> TooltipSystem<T> {
+    /// Creates a new TooltipSystem.
+    pub fn new() -> Self {
+        TooltipSystem {
+            hovered: None,
+            dwell: 0.0,
+            truncated_text_delay: TRUNCATED_TEXT_DELAY,
+            ui_reader_id: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets how long a truncated text has to be hovered before its full text shows, in
+    /// seconds. Defaults to 0.5.
+    pub fn with_truncated_text_delay(mut self, delay: f32) -> Self {
+        self.truncated_text_delay = delay;
+        self
+    }
+}
+
+impl<'a, T: BindingTypes> System<'a> for TooltipSystem<T> {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, UiTooltip>,
+        ReadStorage<'a, UiTooltipDisplay>,
+        WriteStorage<'a, UiText>,
+        WriteStorage<'a, UiTransform>,
+        WriteStorage<'a, Hidden>,
+        Read<'a, EventChannel<UiEvent>>,
+        Read<'a, UiCursor>,
+        Read<'a, Time>,
+        ReadExpect<'a, ScreenDimensions>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            tooltips,
+            displays,
+            mut texts,
+            mut transforms,
+            mut hiddens,
+            events,
+            ui_cursor,
+            time,
+            screen_dimensions,
+        ): Self::SystemData,
+    ) {
+        for event in events.read(
+            self.ui_reader_id
+                .as_mut()
+                .expect("`TooltipSystem::setup` was not called before `TooltipSystem::run`"),
+        ) {
+            match event.event_type {
+                UiEventType::HoverStart
+                    if tooltips.contains(event.target)
+                        || texts.get(event.target).map_or(false, UiText::is_truncated) =>
+                {
+                    self.hovered = Some(event.target);
+                    self.dwell = 0.0;
+                }
+                UiEventType::HoverStop if self.hovered == Some(event.target) => {
+                    self.hovered = None;
+                }
+                _ => {}
+            }
+        }
+        if self.hovered.is_some() {
+            self.dwell += time.delta_seconds();
+        }
+
+        let cursor = ui_cursor.pixel_position;
+        let truncated_text_delay = self.truncated_text_delay;
+        let content = self.hovered.and_then(|entity| match tooltips.get(entity) {
+            Some(tooltip) => Some((tooltip.text.clone(), tooltip.delay)),
+            None => texts
+                .get(entity)
+                .filter(|text| text.is_truncated())
+                .map(|text| (text.text.clone(), truncated_text_delay)),
+        });
+        let shown = match (content, cursor) {
+            (Some((text, delay)), Some(cursor)) if self.dwell >= delay => Some((text, cursor)),
+            _ => None,
+        };
+
+        for (entity, _) in (&*entities, &displays).join() {
+            let (tooltip_text, cursor) = match &shown {
+                Some((text, cursor)) => (text, *cursor),
+                None => {
+                    if !hiddens.contains(entity) {
+                        hiddens
+                            .insert(entity, Hidden)
+                            .expect("unreachable: We are inserting");
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(text) = texts.get_mut(entity) {
+                if text.text != *tooltip_text {
+                    text.text = tooltip_text.clone();
+                }
+            }
+            let placement = transforms.get(entity).map(|transform| {
+                let position = tooltip_position(
+                    cursor,
+                    (transform.width, transform.height),
+                    (screen_dimensions.width(), screen_dimensions.height()),
+                );
+                let placed = transform.anchor == Anchor::BottomLeft
+                    && transform.pivot == Anchor::TopLeft
+                    && (transform.local_x, transform.local_y) == position;
+                (position, placed)
+            });
+            // Only touch the transform when the tooltip moved, so the layout isn't redone
+            // every frame.
+            if let Some(((x, y), false)) = placement {
+                let transform = transforms
+                    .get_mut(entity)
+                    .expect("unreachable: We just read the transform");
+                transform.anchor = Anchor::BottomLeft;
+                transform.pivot = Anchor::TopLeft;
+                transform.local_x = x;
+                transform.local_y = y;
+            }
+            hiddens.remove(entity);
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.ui_reader_id = Some(res.fetch_mut::<EventChannel<UiEvent>>().register_reader());
+    }
+}
+
+/// The top left corner of a tooltip of the given size, below and to the right of the cursor,
+/// and moved back inside the screen when it would go past an edge.
+fn tooltip_position(cursor: (f32, f32), size: (f32, f32), screen: (f32, f32)) -> (f32, f32) {
+    let x = (cursor.0 + CURSOR_OFFSET).min(screen.0 - size.0).max(0.0);
+    let y = (cursor.1 - CURSOR_OFFSET).max(size.1).min(screen.1);
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FontAsset, Overflow};
+    use amethyst_assets::AssetStorage;
+    use amethyst_core::ecs::prelude::{Builder, RunNow, World};
+    use amethyst_input::StringBindings;
+    use glyph_brush::rusttype::Font;
+
+    #[test]
+    fn position_stays_on_screen() {
+        let screen = (200.0, 200.0);
+        assert_eq!(
+            tooltip_position((50.0, 100.0), (100.0, 30.0), screen),
+            (66.0, 84.0)
+        );
+        assert_eq!(
+            tooltip_position((190.0, 20.0), (100.0, 30.0), screen),
+            (100.0, 30.0)
+        );
+    }
+
+    #[test]
+    fn tooltip_shows_after_delay() {
+        let mut world = World::new();
+        let mut system = TooltipSystem::<StringBindings>::new();
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let display = world
+            .create_entity()
+            .with(UiTransform::new(
+                "tooltip".to_string(),
+                Anchor::BottomLeft,
+                Anchor::TopLeft,
+                0.0,
+                0.0,
+                10.0,
+                100.0,
+                30.0,
+            ))
+            .with(UiText::new(font, String::new(), [1.0; 4], 16.0))
+            .with(UiTooltipDisplay)
+            .build();
+        let button = world
+            .create_entity()
+            .with(UiTooltip::new("Saves the game".to_string(), 0.5))
+            .build();
+
+        world.write_resource::<UiCursor>().pixel_position = Some((190.0, 100.0));
+        world
+            .write_resource::<EventChannel<UiEvent>>()
+            .single_write(UiEvent::new(UiEventType::HoverStart, button));
+        world.write_resource::<Time>().set_delta_seconds(0.3);
+
+        let visible = |world: &World| !world.read_storage::<Hidden>().contains(display);
+        system.run_now(&world.res);
+        assert!(!visible(&world));
+
+        system.run_now(&world.res);
+        assert!(visible(&world));
+        assert_eq!(
+            world.read_storage::<UiText>().get(display).unwrap().text,
+            "Saves the game"
+        );
+        {
+            let transforms = world.read_storage::<UiTransform>();
+            let transform = transforms.get(display).unwrap();
+            assert_eq!((transform.local_x, transform.local_y), (100.0, 84.0));
+        }
+
+        world
+            .write_resource::<EventChannel<UiEvent>>()
+            .single_write(UiEvent::new(UiEventType::HoverStop, button));
+        system.run_now(&world.res);
+        assert!(!visible(&world));
+    }
+    #[test]
+    fn truncated_text_shows_in_full() {
+        let mut world = World::new();
+        let mut system = TooltipSystem::<StringBindings>::new().with_truncated_text_delay(0.0);
+        System::setup(&mut system, &mut world.res);
+        world.add_resource(ScreenDimensions::new(200, 200, 1.0));
+
+        let font = Font::from_bytes(include_bytes!("./font/square.ttf").to_vec())
+            .expect("Unable to parse square.ttf");
+        let font = AssetStorage::<FontAsset>::new().insert(FontAsset(font));
+        let display = world
+            .create_entity()
+            .with(UiTransform::new(
+                "tooltip".to_string(),
+                Anchor::BottomLeft,
+                Anchor::TopLeft,
+                0.0,
+                0.0,
+                10.0,
+                100.0,
+                30.0,
+            ))
+            .with(UiText::new(font.clone(), String::new(), [1.0; 4], 16.0))
+            .with(UiTooltipDisplay)
+            .build();
+        let full_text = "C:\\Users\\jöel\\Documents\\save.ron";
+        let mut shortened = UiText::new(font.clone(), full_text.to_string(), [1.0; 4], 16.0);
+        shortened.overflow = Overflow::Ellipsis;
+        // Set by the `UiGlyphsSystem` when the text doesn't fit.
+        shortened.truncated = true;
+        let shortened = world.create_entity().with(shortened).build();
+        let fitting = world
+            .create_entity()
+            .with(UiText::new(font, "Save".to_string(), [1.0; 4], 16.0))
+            .build();
+
+        world.write_resource::<UiCursor>().pixel_position = Some((50.0, 100.0));
+        let hover = |world: &mut World, event_type, target| {
+            world
+                .write_resource::<EventChannel<UiEvent>>()
+                .single_write(UiEvent::new(event_type, target));
+        };
+        let visible = |world: &World| !world.read_storage::<Hidden>().contains(display);
+
+        hover(&mut world, UiEventType::HoverStart, shortened);
+        system.run_now(&world.res);
+        assert!(visible(&world));
+        assert_eq!(
+            world.read_storage::<UiText>().get(display).unwrap().text,
+            full_text
+        );
+
+        hover(&mut world, UiEventType::HoverStop, shortened);
+        hover(&mut world, UiEventType::HoverStart, fitting);
+        system.run_now(&world.res);
+        assert!(!visible(&world));
+    }
+}
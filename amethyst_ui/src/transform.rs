@@ -4,6 +4,7 @@ use amethyst_core::ecs::prelude::{
     Component, DenseVecStorage, Entities, Entity, FlaggedStorage, Join, ReadStorage,
 };
 
+use amethyst_window::ScreenDimensions;
 use serde::{Deserialize, Serialize};
 use shred_derive::SystemData;
 
@@ -56,6 +57,10 @@ pub struct UiTransform {
     pub width: f32,
     /// The height of this UI element.
     pub height: f32,
+    /// Counterclockwise rotation around the pivot, in radians. Only this element is rotated
+    /// when drawn and hit-tested, not its children.
+    #[serde(default)]
+    pub rotation: f32,
     /// Global x position set by the `UiTransformSystem`.
     pub(crate) pixel_x: f32,
     /// Global y position set by the `UiTransformSystem`.
@@ -72,6 +77,9 @@ pub struct UiTransform {
     /// If set to false, the element will behaves as if it was transparent and will let events go to
     /// the next element (for example, the text on a button).
     pub opaque: bool,
+    /// Whether the `UiTransformSystem` computed the global position yet.
+    #[serde(skip)]
+    pub(crate) laid_out: bool,
     /// A private field to keep this from being initialized without new.
     pd: PhantomData<()>,
 }
@@ -99,6 +107,7 @@ impl UiTransform {
             local_z: z,
             width,
             height,
+            rotation: 0.0,
             pixel_x: x,
             pixel_y: y,
             global_z: z,
@@ -106,6 +115,7 @@ impl UiTransform {
             pixel_height: height,
             scale_mode: ScaleMode::Pixel,
             opaque: true,
+            laid_out: false,
             pd: PhantomData,
         }
     }
@@ -120,6 +130,7 @@ impl UiTransform {
 
     /// Checks if the input position is in the UiTransform rectangle.
     pub fn position_inside(&self, x: f32, y: f32) -> bool {
+        let (x, y) = self.unrotate(x, y);
         x > self.pixel_x - self.pixel_width / 2.0
             && y > self.pixel_y - self.pixel_height / 2.0
             && x < self.pixel_x + self.pixel_width / 2.0
@@ -139,6 +150,12 @@ impl UiTransform {
         self
     }
 
+    /// Rotates this ui element counterclockwise around its pivot, in radians.
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
     /// Adds stretching to this ui element so it can fill its parent.
     pub fn with_stretch(mut self, stretch: Stretch) -> Self {
         self.stretch = stretch;
@@ -159,6 +176,48 @@ impl UiTransform {
     pub fn global_z(&self) -> f32 {
         self.global_z
     }
+
+    /// Returns the global position of the pivot, which this UiTransform is rotated around.
+    pub fn pivot_position(&self) -> (f32, f32) {
+        let (x, y) = self.pivot.norm_offset();
+        (
+            self.pixel_x + self.pixel_width * x,
+            self.pixel_y + self.pixel_height * y,
+        )
+    }
+
+    /// Rotates a global position into the unrotated space of this UiTransform.
+    pub(crate) fn unrotate(&self, x: f32, y: f32) -> (f32, f32) {
+        if self.rotation == 0.0 {
+            return (x, y);
+        }
+        let (sin, cos) = (-self.rotation).sin_cos();
+        let (pivot_x, pivot_y) = self.pivot_position();
+        let (dx, dy) = (x - pivot_x, y - pivot_y);
+        (pivot_x + dx * cos - dy * sin, pivot_y + dx * sin + dy * cos)
+    }
+
+    /// Returns the center of this UiTransform in UI coordinates, from the bottom left of the
+    /// screen, after anchoring and scaling by the `UiTransformSystem`.
+    ///
+    /// Before the first layout, this is the local position.
+    pub fn global_position(&self) -> (f32, f32) {
+        if self.laid_out {
+            (self.pixel_x, self.pixel_y)
+        } else {
+            (self.local_x, self.local_y)
+        }
+    }
+}
+
+/// Returns the center of the transform in window coordinates, from the top left of the
+/// screen like the mouse position of the `InputHandler`.
+pub fn screen_position(
+    transform: &UiTransform,
+    screen_dimensions: &ScreenDimensions,
+) -> (f32, f32) {
+    let (x, y) = transform.global_position();
+    (x, screen_dimensions.height() - y)
 }
 
 impl Component for UiTransform {
@@ -203,4 +262,46 @@ mod tests {
         let pos = (-1.49, 1.20);
         assert!(!tr.position_inside(pos.0, pos.1));
     }
+
+    #[test]
+    fn inside_rotated() {
+        // A bar 100 wide and 10 high, standing upright around its bottom left corner.
+        let mut tr = UiTransform::new(
+            "".to_string(),
+            Anchor::BottomLeft,
+            Anchor::BottomLeft,
+            50.0,
+            5.0,
+            0.0,
+            100.0,
+            10.0,
+        )
+        .with_rotation(std::f32::consts::FRAC_PI_2);
+        tr.pixel_x = 50.0;
+        tr.pixel_y = 5.0;
+        assert_eq!(tr.pivot_position(), (0.0, 0.0));
+        assert!(tr.position_inside(-5.0, 90.0));
+        assert!(!tr.position_inside(50.0, 5.0));
+        assert!(!tr.position_inside(-5.0, 110.0));
+    }
+
+    #[test]
+    fn screen_position_of_anchored_transform() {
+        let mut tr = UiTransform::new(
+            "health".to_string(),
+            Anchor::TopRight,
+            Anchor::TopRight,
+            -20.0,
+            -10.0,
+            0.0,
+            100.0,
+            40.0,
+        );
+        let screen_dimensions = ScreenDimensions::new(800, 600, 1.0);
+        assert_eq!(screen_position(&tr, &screen_dimensions), (-20.0, 610.0));
+
+        crate::layout::process_root_iter(std::iter::once(&mut tr), &screen_dimensions, 1.0);
+        assert_eq!(tr.global_position(), (730.0, 570.0));
+        assert_eq!(screen_position(&tr, &screen_dimensions), (730.0, 30.0));
+    }
 }